@@ -5,6 +5,7 @@ use crate::tpixel::rect::Rect;
 use crate::tpixel::color::Color;
 use crate::tpixel::point_light::PointLight;
 use crate::tpixel::matrix3x2::Matrix3x2;
+use crate::tpixel::tilemap::Tilemap;
 
 use rand::Rng;
 use glfw::Key;
@@ -25,23 +26,25 @@ impl Game {
             "target/debug/assets/test_material.png",
             "target/debug/assets/test_normal.png");
         let mut rng = rand::thread_rng();
-        for i in 0..1024 {
-            let tile_x : f32 = (i % 32) as f32 * 128.0;
-            let tile_y : f32 = (i / 32) as f32 * 128.0;
+
+        let tilemap_entity = engine.registry.create_entity();
+        let mut tilemap = Tilemap::new(material_id, Vector2 { x : 128f32, y : 128f32 }, 32, 32);
+        tilemap.transform = Matrix3x2::new_translation(Vector2 { x : -576f32, y : -576f32 });
+        tilemap.fill(0);
+        engine.registry.get_map_mut::<Tilemap>().insert(tilemap_entity, tilemap);
+
+        for _ in 0..4 {
             let ent = engine.registry.create_entity();
             self.entities.push(ent);
-            let mut sprite1 : Sprite = engine.new_sprite(material_id);
-            sprite1.transform = Matrix3x2::new_transform(
-                Vector2{ x : tile_x - 512.0f32, y : tile_y - 512.0f32 },
-                Vector2{ x : 1f32 + 0f32 * rng.gen::<f32>(), y : 1f32 + 0f32 * rng.gen::<f32>() },
-                rng.gen::<f32>() * std::f32::consts::PI * 0.0f32,
-            );
-            sprite1.pivot = Vector2 {x : 0.5f32, y : 0.5f32};
-            sprite1.color = Color {r : rng.gen::<f32>(), g : rng.gen::<f32>(), b : rng.gen::<f32>(), a : 1.0f32};
-            sprite1.uv_rect = Rect {begin : Vector2 {x : 0.0f32, y : 0.0f32}, end : Vector2 {x : 1.0f32, y : 1.0f32}};
-            sprite1.z = rng.gen::<f32>();
-            engine.registry.get_map_mut::<Sprite>().insert(ent, sprite1);
         }
+        let mut sprite1 : Sprite = engine.new_sprite(material_id);
+        sprite1.transform = Matrix3x2::new_translation(Vector2 { x : 0f32, y : 0f32 });
+        sprite1.pivot = Vector2 {x : 0.5f32, y : 0.5f32};
+        sprite1.color = Color {r : rng.gen::<f32>(), g : rng.gen::<f32>(), b : rng.gen::<f32>(), a : 1.0f32};
+        sprite1.uv_rect = Rect {begin : Vector2 {x : 0.0f32, y : 0.0f32}, end : Vector2 {x : 1.0f32, y : 1.0f32}};
+        sprite1.z = 0.5f32;
+        sprite1.height = 16f32;
+        engine.registry.get_map_mut::<Sprite>().insert(self.entities[1], sprite1);
         for i in 0..4 {
             let ent = self.entities[i];
             let x : f32 = (i % 2) as f32;
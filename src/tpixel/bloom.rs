@@ -0,0 +1,19 @@
+
+#[derive(Copy, Clone)]
+pub struct Bloom {
+    pub enabled : bool, // off unless turned on
+    pub intensity : f32,
+    pub threshold : f32, // lit scene brightness where bloom starts
+    pub radius : f32, // spread of the blur in texels of each mip level
+}
+
+impl Bloom {
+    pub fn new() -> Bloom {
+        Bloom {
+            enabled : false,
+            intensity : 0.5f32,
+            threshold : 1f32,
+            radius : 1f32,
+        }
+    }
+}
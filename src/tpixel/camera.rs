@@ -1,9 +1,20 @@
 use crate::tpixel::matrix3x2::Matrix3x2;
 use crate::tpixel::vector2::Vector2;
+use crate::tpixel::rect::Rect;
+use crate::tpixel::color::Color;
+
+// layers are bits of a u32 mask
+pub const MAX_LAYERS : u32 = 32;
 
 pub struct Camera {
     pub transform : Matrix3x2,
     pub view_size : Vector2,
+    pub viewport : Rect, // normalized part of the window, 0, 0 is the bottom left
+    pub priority : i32, // higher priorities are drawn on top of lower ones
+    pub clear : bool, // when false the background is left transparent so the cameras below show through
+    pub clear_color : Color,
+    pub layer_mask : u32, // bit n set means sprites on layer n are drawn, see MAX_LAYERS
+    pub render_target : Option<u32>, // material id from Engine::new_render_target, None renders to the window
 }
 
 impl Camera {
@@ -14,6 +25,28 @@ impl Camera {
                 0f32,
             ),
             view_size : Vector2 { x : 1280.0f32, y : 720.0f32 },
+            viewport : Rect::new_uv(),
+            priority : 0,
+            clear : true,
+            clear_color : Color { r : 0f32, g : 0f32, b : 0f32, a : 1f32 },
+            layer_mask : u32::MAX,
+            render_target : None,
         }
     }
+    pub(crate) fn clone(&self) -> Camera {
+        Camera {
+            transform : self.transform,
+            view_size : self.view_size,
+            viewport : self.viewport,
+            priority : self.priority,
+            clear : self.clear,
+            clear_color : self.clear_color,
+            layer_mask : self.layer_mask,
+            render_target : self.render_target,
+        }
+    }
+}
+// layers past MAX_LAYERS are never drawn instead of wrapping onto the low bits
+pub(crate) fn layer_in_mask(layer_mask : u32, layer : u32) -> bool {
+    layer < MAX_LAYERS && layer_mask & (1 << layer) != 0
 }
@@ -0,0 +1,896 @@
+use crate::tpixel::render_backend::{RenderBackend, TextureDesc, TextureFormat, TextureFilter, TextureWrap, BackendFrame, BackendMaterial,
+    BackendGBuffer, SpritePass, ParticleSimulation};
+use crate::tpixel::shader_factory::ShaderFactory;
+use crate::tpixel::shader_error::ShaderError;
+use crate::tpixel::shader_quality::ShaderQuality;
+use crate::tpixel::resource_error::ResourceError;
+use crate::tpixel::uniform_value::UniformValue;
+use crate::tpixel::sprite::{Sprite, SPRITE_UNLIT, SPRITE_FLIP_X, SPRITE_FLIP_Y, SPRITE_ALPHA_CUTOUT};
+use crate::tpixel::point_light::PointLight;
+use crate::tpixel::spot_light::SpotLight;
+use crate::tpixel::directional_light::DirectionalLight;
+use crate::tpixel::tone_mapping::{ToneMapping, ToneMappingOperator};
+use crate::tpixel::particle_emitter::ParticleSpawn;
+use crate::tpixel::matrix3x2::Matrix3x2;
+use crate::tpixel::vector2::Vector2;
+
+use std::collections::HashMap;
+
+struct CpuTexture {
+    width : i32,
+    height : i32,
+    format : TextureFormat,
+    filter : TextureFilter,
+    wrap : TextureWrap,
+    texels : Vec<[f32; 4]>, // rows starting at v = 0
+}
+
+impl CpuTexture {
+    fn texel(&self, x : i32, y : i32) -> [f32; 4] {
+        let (x, y) = match self.wrap {
+            TextureWrap::Repeat => (x.rem_euclid(self.width), y.rem_euclid(self.height)),
+            TextureWrap::Clamp => (x.max(0).min(self.width - 1), y.max(0).min(self.height - 1)),
+            TextureWrap::RepeatX => (x.rem_euclid(self.width), y.max(0).min(self.height - 1)),
+        };
+        self.texels[(y * self.width + x) as usize]
+    }
+    // like texture() in glsl on the base level
+    fn sample(&self, uv : Vector2) -> [f32; 4] {
+        if self.width == 0 || self.height == 0 {
+            return [0f32, 0f32, 0f32, 1f32];
+        }
+        let x = uv.x * self.width as f32;
+        let y = uv.y * self.height as f32;
+        if self.filter == TextureFilter::Nearest {
+            return self.texel(x.floor() as i32, y.floor() as i32);
+        }
+        let x = x - 0.5f32;
+        let y = y - 0.5f32;
+        let (x0, y0) = (x.floor() as i32, y.floor() as i32);
+        let (fx, fy) = (x - x.floor(), y - y.floor());
+        let (a, b, c, d) = (self.texel(x0, y0), self.texel(x0 + 1, y0), self.texel(x0, y0 + 1), self.texel(x0 + 1, y0 + 1));
+        let mut out = [0f32; 4];
+        for channel in 0..4 {
+            let bottom = a[channel] + (b[channel] - a[channel]) * fx;
+            let top = c[channel] + (d[channel] - c[channel]) * fx;
+            out[channel] = bottom + (top - bottom) * fy;
+        }
+        out
+    }
+}
+
+// runs the same math as the gl shaders on the cpu, so rendering works and can be checked without a gpu,
+// rgba8 targets are quantized like gl's, Rgba16F is kept at full float precision,
+// it has no shaders, shadows, bloom, debug lines or gpu particles, those draws do nothing
+pub struct CpuBackend {
+    textures : HashMap<u32, CpuTexture>,
+    buffers : HashMap<u32, Vec<u8>>,
+    next_id : u32,
+    window : u32, // a texture like the others, resized by begin_frame
+    targets : Vec<u32>,
+    viewport : (i32, i32, i32, i32),
+    depth : Vec<f32>,
+    depth_test : bool,
+    frame : Option<BackendFrame>,
+}
+
+impl CpuBackend {
+    pub fn new() -> CpuBackend {
+        CpuBackend {
+            textures : HashMap::new(),
+            buffers : HashMap::new(),
+            next_id : 1, // 0 is no texture like in gl
+            window : 0,
+            targets : Vec::new(),
+            viewport : (0, 0, 0, 0),
+            depth : Vec::new(),
+            depth_test : false,
+            frame : None,
+        }
+    }
+    fn next_id(&mut self) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+    fn texture_size(&self, texture : u32) -> (i32, i32) {
+        match self.textures.get(&texture) {
+            Some(texture) => (texture.width, texture.height),
+            None => (0, 0),
+        }
+    }
+    fn target_size(&self) -> (i32, i32) {
+        match self.targets.first() {
+            Some(target) => self.texture_size(*target),
+            None => (0, 0),
+        }
+    }
+    // missing textures read as opaque black like unbound ones in gl
+    fn sample(&self, texture : u32, uv : Vector2) -> [f32; 4] {
+        match self.textures.get(&texture) {
+            Some(texture) => texture.sample(uv),
+            None => [0f32, 0f32, 0f32, 1f32],
+        }
+    }
+    fn write_target(&mut self, target_index : usize, texel_index : usize, value : [f32; 4]) {
+        let textures = &mut self.textures;
+        if let Some(texture) = self.targets.get(target_index).and_then(|target| textures.get_mut(target)) {
+            texture.texels[texel_index] = store(texture.format, value);
+        }
+    }
+    fn read_target(&self, texel_index : usize) -> [f32; 4] {
+        match self.targets.first().and_then(|target| self.textures.get(target)) {
+            Some(texture) => texture.texels[texel_index],
+            None => [0f32; 4],
+        }
+    }
+    // gl's blend with the given factors of the source color and alpha
+    fn blend_target(&mut self, texel_index : usize, value : [f32; 4], source_factor : f32, destination_factor : f32, keep_alpha : bool) {
+        let texel = self.read_target(texel_index);
+        let mut out = [0f32; 4];
+        for channel in 0..4 {
+            out[channel] = value[channel] * source_factor + texel[channel] * destination_factor;
+        }
+        if keep_alpha {
+            out[3] = texel[3];
+        }
+        self.write_target(0, texel_index, out);
+    }
+    fn sample_gbuffer(&self, gbuffer : &BackendGBuffer, uv : Vector2) -> GBufferSample {
+        let nor = self.sample(gbuffer.normal, uv);
+        let mat = self.sample(gbuffer.material, uv);
+        GBufferSample {
+            color : self.sample(gbuffer.color, uv),
+            normal : normalize3([nor[0] * 2f32 - 1f32, nor[1] * 2f32 - 1f32, nor[2] * 2f32 - 1f32]),
+            height : self.sample(gbuffer.height, uv)[0],
+            specular : mat[0],
+            shininess : (mat[1] * 10f32 + 1f32).exp2(),
+            emissive : mat[2],
+            unlit : 1f32 - mat[3],
+        }
+    }
+    // the target pixels inside the viewport with the UV and WORLD_POSITION the fullscreen vertex shader gives them
+    fn fullscreen_pixels(&self) -> Vec<(usize, Vector2, Vector2)> {
+        let (width, height) = self.target_size();
+        let (viewport_x, viewport_y, viewport_width, viewport_height) = self.viewport;
+        let mut pixels : Vec<(usize, Vector2, Vector2)> = Vec::new();
+        if viewport_width <= 0 || viewport_height <= 0 {
+            return pixels;
+        }
+        for y in viewport_y.max(0)..(viewport_y + viewport_height).min(height) {
+            for x in viewport_x.max(0)..(viewport_x + viewport_width).min(width) {
+                let uv = Vector2 {
+                    x : ((x - viewport_x) as f32 + 0.5f32) / viewport_width as f32,
+                    y : ((y - viewport_y) as f32 + 0.5f32) / viewport_height as f32,
+                };
+                let world_position = match self.frame {
+                    Some(frame) => frame.camera_transform_inverse.transform_point(Vector2 {
+                        x : (uv.x * 2f32 - 1f32) * frame.camera_view.x,
+                        y : (uv.y * 2f32 - 1f32) * frame.camera_view.y,
+                    }),
+                    None => Vector2::new(),
+                };
+                pixels.push(((y * width + x) as usize, uv, world_position));
+            }
+        }
+        pixels
+    }
+    // the target pixels whose centers the sprite's quad covers, with their uv in the material,
+    // only the quad's bounding box on the target is visited
+    fn rasterize_sprite(&self, material : &BackendMaterial, sprite : &Sprite, frame : &BackendFrame) -> Vec<(usize, Vector2, f32)> {
+        let mut pixels : Vec<(usize, Vector2, f32)> = Vec::new();
+        // clipped like gl_Position.z = -z
+        if sprite.z < -1f32 || sprite.z > 1f32 {
+            return pixels;
+        }
+        let size = Vector2 {
+            x : (sprite.uv_rect.end.x - sprite.uv_rect.begin.x) * material.size.x,
+            y : (sprite.uv_rect.end.y - sprite.uv_rect.begin.y) * material.size.y,
+        };
+        if size.x == 0f32 || size.y == 0f32 || frame.camera_view.x == 0f32 || frame.camera_view.y == 0f32 {
+            return pixels;
+        }
+        let (instance_inverse, view_inverse) = match (affine_inverse(&sprite.transform), affine_inverse(&frame.camera_transform)) {
+            (Some(instance_inverse), Some(view_inverse)) => (instance_inverse, view_inverse),
+            _ => return pixels,
+        };
+        let (width, height) = self.target_size();
+        let (viewport_x, viewport_y, viewport_width, viewport_height) = self.viewport;
+        if viewport_width <= 0 || viewport_height <= 0 {
+            return pixels;
+        }
+
+        // the corners through the same transforms as VERTEX_GEO_SHADER_SOURCE, in pixels
+        let mut min = Vector2 { x : f32::INFINITY, y : f32::INFINITY };
+        let mut max = Vector2 { x : f32::NEG_INFINITY, y : f32::NEG_INFINITY };
+        for corner in [(0f32, 0f32), (1f32, 0f32), (0f32, 1f32), (1f32, 1f32)].iter() {
+            let local = Vector2 { x : corner.0 * size.x - size.x * sprite.pivot.x, y : corner.1 * size.y - size.y * sprite.pivot.y };
+            let view = frame.camera_transform.transform_point(sprite.transform.transform_point(local));
+            let pixel = Vector2 {
+                x : viewport_x as f32 + (view.x / frame.camera_view.x * 0.5f32 + 0.5f32) * viewport_width as f32,
+                y : viewport_y as f32 + (view.y / frame.camera_view.y * 0.5f32 + 0.5f32) * viewport_height as f32,
+            };
+            min = Vector2 { x : min.x.min(pixel.x), y : min.y.min(pixel.y) };
+            max = Vector2 { x : max.x.max(pixel.x), y : max.y.max(pixel.y) };
+        }
+        let begin_x = (min.x.floor() as i32).max(viewport_x).max(0);
+        let begin_y = (min.y.floor() as i32).max(viewport_y).max(0);
+        let end_x = (max.x.ceil() as i32).min(viewport_x + viewport_width).min(width);
+        let end_y = (max.y.ceil() as i32).min(viewport_y + viewport_height).min(height);
+
+        let flip_x = sprite.has_flag(SPRITE_FLIP_X);
+        let flip_y = sprite.has_flag(SPRITE_FLIP_Y);
+        let depth = -sprite.z * 0.5f32 + 0.5f32;
+        for y in begin_y..end_y {
+            for x in begin_x..end_x {
+                // back through the camera and the instance transform onto the quad
+                let view = Vector2 {
+                    x : ((x - viewport_x) as f32 + 0.5f32) / viewport_width as f32 * 2f32 - 1f32,
+                    y : ((y - viewport_y) as f32 + 0.5f32) / viewport_height as f32 * 2f32 - 1f32,
+                };
+                let view = Vector2 { x : view.x * frame.camera_view.x, y : view.y * frame.camera_view.y };
+                let local = instance_inverse.transform_point(view_inverse.transform_point(view));
+                let quad = Vector2 {
+                    x : (local.x + size.x * sprite.pivot.x) / size.x,
+                    y : (local.y + size.y * sprite.pivot.y) / size.y,
+                };
+                if quad.x < 0f32 || quad.x >= 1f32 || quad.y < 0f32 || quad.y >= 1f32 {
+                    continue;
+                }
+                // the quad's top row has the uv rect's begin, see VERTEX_GEO_SHADER_SOURCE
+                let u = if flip_x { 1f32 - quad.x } else { quad.x };
+                let v = if flip_y { quad.y } else { 1f32 - quad.y };
+                let uv = Vector2 {
+                    x : sprite.uv_rect.begin.x + (sprite.uv_rect.end.x - sprite.uv_rect.begin.x) * u,
+                    y : sprite.uv_rect.begin.y + (sprite.uv_rect.end.y - sprite.uv_rect.begin.y) * v,
+                };
+                pixels.push(((y * width + x) as usize, uv, depth));
+            }
+        }
+        pixels
+    }
+    fn draw_sprite(&mut self, pass : SpritePass, material : &BackendMaterial, sprite : &Sprite, frame : &BackendFrame) {
+        let pixels = self.rasterize_sprite(material, sprite, frame);
+        let t = &sprite.transform.elements;
+        let axis_x = normalize2([t[0], t[1]], if sprite.has_flag(SPRITE_FLIP_X) { -1f32 } else { 1f32 });
+        let axis_y = normalize2([t[2], t[3]], if sprite.has_flag(SPRITE_FLIP_Y) { -1f32 } else { 1f32 });
+        for (index, uv, depth) in pixels {
+            if self.depth_test && !(depth < self.depth[index]) {
+                continue;
+            }
+            let tex = self.sample(material.color, uv);
+            let color = [sprite.color.r * tex[0], sprite.color.g * tex[1], sprite.color.b * tex[2], sprite.color.a * tex[3]];
+            match pass {
+                SpritePass::Geometry => {
+                    if sprite.has_flag(SPRITE_ALPHA_CUTOUT) && color[3] < 0.1f32 {
+                        continue;
+                    }
+                    let nor = self.sample(material.normal, uv);
+                    let n = [nor[0] * 2f32 - 1f32, nor[1] * 2f32 - 1f32];
+                    let normal = normalize3([
+                        axis_x[0] * n[0] + axis_y[0] * n[1],
+                        axis_x[1] * n[0] + axis_y[1] * n[1],
+                        (1f32 - n[0] * n[0] - n[1] * n[1]).max(0f32).sqrt(),
+                    ]);
+                    let mut mat = self.sample(material.material, uv);
+                    if sprite.has_flag(SPRITE_UNLIT) {
+                        mat[3] = 0f32;
+                    }
+                    let height_value = sprite.height + nor[3] * sprite.height_map_scale;
+
+                    self.write_target(0, index, color);
+                    self.write_target(1, index, [normal[0] * 0.5f32 + 0.5f32, normal[1] * 0.5f32 + 0.5f32, normal[2] * 0.5f32 + 0.5f32, nor[3]]);
+                    self.write_target(2, index, mat);
+                    self.write_target(3, index, [height_value, 0f32, 0f32, 1f32]);
+                },
+                SpritePass::Overlay => self.blend_target(index, color, color[3], 1f32 - color[3], false),
+                // the alpha of the light buffer is the coverage of the view, leave it alone
+                SpritePass::Additive => self.blend_target(index, color, color[3], 1f32, true),
+                SpritePass::Occluder => return,
+            }
+            if self.depth_test {
+                self.depth[index] = depth;
+            }
+        }
+    }
+}
+
+// GBufferSample from GLSL_GBUFFER_INCLUDE
+struct GBufferSample {
+    color : [f32; 4],
+    normal : [f32; 3],
+    height : f32,
+    specular : f32,
+    shininess : f32,
+    emissive : f32,
+    unlit : f32,
+}
+
+impl RenderBackend for CpuBackend {
+    fn create_texture(&mut self, desc : &TextureDesc, pixels : Option<&[u8]>) -> u32 {
+        let count = (desc.width.max(0) * desc.height.max(0)) as usize;
+        let texels : Vec<[f32; 4]> = match pixels {
+            Some(pixels) => (0..count).map(|index| {
+                let p = &pixels[index * 4..index * 4 + 4];
+                store(desc.format, [p[0] as f32 / 255f32, p[1] as f32 / 255f32, p[2] as f32 / 255f32, p[3] as f32 / 255f32])
+            }).collect(),
+            None => vec![store(desc.format, [0f32; 4]); count],
+        };
+        let id = self.next_id();
+        self.textures.insert(id, CpuTexture {
+            width : desc.width.max(0),
+            height : desc.height.max(0),
+            format : desc.format,
+            filter : desc.filter,
+            wrap : desc.wrap,
+            texels : texels,
+        });
+        id
+    }
+    fn read_texture(&mut self, texture : u32) -> Vec<[f32; 4]> {
+        match self.textures.get(&texture) {
+            Some(texture) => texture.texels.clone(),
+            None => Vec::new(),
+        }
+    }
+    fn delete_texture(&mut self, texture : u32) {
+        self.textures.remove(&texture);
+        self.targets.retain(|target| *target != texture);
+    }
+    fn create_buffer(&mut self, size : usize) -> u32 {
+        let id = self.next_id();
+        self.buffers.insert(id, vec![0u8; size]);
+        id
+    }
+    fn write_buffer(&mut self, buffer : u32, offset : usize, data : &[u8]) {
+        if let Some(buffer) = self.buffers.get_mut(&buffer) {
+            let end = (offset + data.len()).min(buffer.len());
+            if offset < end {
+                buffer[offset..end].copy_from_slice(&data[..end - offset]);
+            }
+        }
+    }
+    fn delete_buffer(&mut self, buffer : u32) {
+        self.buffers.remove(&buffer);
+    }
+
+    fn init(&mut self, _shader_factory : &mut ShaderFactory) -> Vec<ShaderError> {
+        Vec::new()
+    }
+    fn build_shaders(&mut self, _shader_factory : &mut ShaderFactory, _changed : Option<&[String]>) -> Vec<ShaderError> {
+        Vec::new()
+    }
+    fn set_shader_quality(&mut self, _shader_factory : &mut ShaderFactory, _shader_quality : ShaderQuality) -> Vec<ShaderError> {
+        Vec::new()
+    }
+    fn create_material_shader(&mut self, _shader_factory : &ShaderFactory, _surface_source : &str) -> Result<u32, ShaderError> {
+        Err(ShaderError::new_read("material shader", "the cpu backend has no shaders"))
+    }
+    fn create_post_effect(&mut self, _shader_factory : &ShaderFactory, name : &str, _fragment_source : &str) -> Result<u32, ShaderError> {
+        Err(ShaderError::new_read(name, "the cpu backend has no shaders"))
+    }
+    fn delete_post_effect(&mut self, _effect : u32) {
+    }
+
+    fn begin_frame(&mut self, window_width : i32, window_height : i32) {
+        if self.texture_size(self.window) != (window_width.max(0), window_height.max(0)) {
+            self.textures.remove(&self.window);
+            self.window = self.create_texture(&TextureDesc {
+                width : window_width,
+                height : window_height,
+                format : TextureFormat::Rgba8,
+                filter : TextureFilter::Nearest,
+                wrap : TextureWrap::Clamp,
+            }, None);
+        }
+        if let Some(window) = self.textures.get_mut(&self.window) {
+            for texel in window.texels.iter_mut() {
+                *texel = [0f32, 0f32, 0f32, 1f32];
+            }
+        }
+        self.targets = vec![self.window];
+        self.viewport = (0, 0, window_width, window_height);
+    }
+    fn end_frame(&mut self) {
+    }
+    fn state_changes(&self) -> (u32, u32) {
+        (0, 0)
+    }
+    fn read_window(&mut self) -> Vec<[f32; 4]> {
+        let window = self.window;
+        self.read_texture(window)
+    }
+
+    fn begin_pass(&mut self, targets : &[u32], clear_colors : &[[f32; 4]], depth_test : bool) -> Result<(), ResourceError> {
+        if let Some(target) = targets.iter().find(|target| !self.textures.contains_key(*target)) {
+            return Err(ResourceError::new("render target", &format!("texture {} does not exist", target)));
+        }
+        self.targets = if targets.is_empty() { vec![self.window] } else { targets.to_vec() };
+        self.depth_test = depth_test;
+        for (target, clear_color) in self.targets.iter().zip(clear_colors.iter()) {
+            if let Some(texture) = self.textures.get_mut(target) {
+                let value = store(texture.format, *clear_color);
+                for texel in texture.texels.iter_mut() {
+                    *texel = value;
+                }
+            }
+        }
+        let (width, height) = self.target_size();
+        self.viewport = (0, 0, width, height);
+        let count = (width * height) as usize;
+        if depth_test && (!clear_colors.is_empty() || self.depth.len() != count) {
+            self.depth = vec![1f32; count];
+        }
+        Ok(())
+    }
+    fn set_viewport(&mut self, x : i32, y : i32, width : i32, height : i32) {
+        self.viewport = (x, y, width, height);
+    }
+    fn set_frame(&mut self, frame : &BackendFrame) {
+        self.frame = Some(*frame);
+    }
+
+    fn draw_sprites(&mut self, pass : SpritePass, material : &BackendMaterial, instance_buffer : u32, count : usize) {
+        let frame = match self.frame {
+            Some(frame) => frame,
+            None => return,
+        };
+        let stride = std::mem::size_of::<Sprite>();
+        let sprites : Vec<Sprite> = match self.buffers.get(&instance_buffer) {
+            Some(buffer) => (0..count.min(buffer.len() / stride)).map(|index| unsafe {
+                std::ptr::read_unaligned(buffer[index * stride..].as_ptr() as *const Sprite)
+            }).collect(),
+            None => return,
+        };
+        for sprite in sprites.iter() {
+            self.draw_sprite(pass, material, sprite, &frame);
+        }
+    }
+    fn draw_polygons(&mut self, _vertices : &[f32]) {
+    }
+    fn draw_lines(&mut self, _vertices : &[f32]) {
+    }
+    fn draw_shadow_map(&mut self, _occlusion : u32, _light : &PointLight, _shadow_map_size : f32) {
+    }
+    fn draw_ambience(&mut self, gbuffer : &BackendGBuffer) {
+        let frame = match self.frame {
+            Some(frame) => frame,
+            None => return,
+        };
+        let ambience = [frame.ambient.r * frame.ambient.a, frame.ambient.g * frame.ambient.a, frame.ambient.b * frame.ambient.a];
+        for (index, uv, _) in self.fullscreen_pixels() {
+            let gbuffer_sample = self.sample_gbuffer(gbuffer, uv);
+            let col = gbuffer_sample.color;
+            // ambience comes from straight above, so the half vector is the view vector
+            let highlight = specular_highlight(&gbuffer_sample, [0f32, 0f32, 1f32], 1f32);
+            let mut out = [0f32, 0f32, 0f32, col[3]];
+            for channel in 0..3 {
+                let lit = col[channel] * ambience[channel] + ambience[channel] * highlight;
+                out[channel] = lit + (col[channel] - lit) * gbuffer_sample.unlit + col[channel] * gbuffer_sample.emissive;
+            }
+            self.write_target(0, index, out);
+        }
+    }
+    // there are no shadow maps, every light is unshadowed
+    fn draw_point_light(&mut self, gbuffer : &BackendGBuffer, light : &PointLight, _shadow : [f32; 4]) {
+        let light_position = [light.position.x, light.position.y, light.height];
+        let light_color = [light.color.r * light.color.a, light.color.g * light.color.a, light.color.b * light.color.a];
+        for (index, uv, world_position) in self.fullscreen_pixels() {
+            let gbuffer_sample = self.sample_gbuffer(gbuffer, uv);
+            let to_light = [light_position[0] - world_position.x, light_position[1] - world_position.y, light_position[2] - gbuffer_sample.height];
+            let attenuation = distance_attenuation(to_light, light.range);
+            let shade = shade(&gbuffer_sample, normalize3(to_light), light_color);
+            // only the ambience pass carries coverage
+            self.blend_target(index, [shade[0] * attenuation, shade[1] * attenuation, shade[2] * attenuation, 0f32], 1f32, 1f32, false);
+        }
+    }
+    fn draw_spot_light(&mut self, gbuffer : &BackendGBuffer, light : &SpotLight) {
+        let light_position = [light.position.x, light.position.y, light.height];
+        let light_color = [light.color.r * light.color.a, light.color.g * light.color.a, light.color.b * light.color.a];
+        let cone_dir = normalize2([light.direction.x, light.direction.y], 1f32);
+        let cos_inner = light.inner_angle.cos();
+        let cos_outer = light.outer_angle.cos();
+        for (index, uv, world_position) in self.fullscreen_pixels() {
+            let gbuffer_sample = self.sample_gbuffer(gbuffer, uv);
+            let to_light = [light_position[0] - world_position.x, light_position[1] - world_position.y, light_position[2] - gbuffer_sample.height];
+            let attenuation = distance_attenuation(to_light, light.range);
+            let to_pixel = [-to_light[0], -to_light[1]];
+            let cos_angle = if to_pixel[0] != 0f32 || to_pixel[1] != 0f32 {
+                let to_pixel = normalize2(to_pixel, 1f32);
+                to_pixel[0] * cone_dir[0] + to_pixel[1] * cone_dir[1]
+            } else {
+                1f32
+            };
+            // smoothstep is undefined for an empty range, equal angles make a hard edged cone
+            let cone = if cos_inner > cos_outer {
+                smoothstep(cos_outer, cos_inner, cos_angle)
+            } else if cos_angle >= cos_outer {
+                1f32
+            } else {
+                0f32
+            };
+            let shade = shade(&gbuffer_sample, normalize3(to_light), light_color);
+            let scale = attenuation * cone;
+            self.blend_target(index, [shade[0] * scale, shade[1] * scale, shade[2] * scale, 0f32], 1f32, 1f32, false);
+        }
+    }
+    fn draw_directional_light(&mut self, gbuffer : &BackendGBuffer, light : &DirectionalLight) {
+        let light_color = [
+            light.color.r * light.color.a * light.intensity,
+            light.color.g * light.color.a * light.intensity,
+            light.color.b * light.color.a * light.intensity,
+        ];
+        let dir = normalize3([-light.direction.x, -light.direction.y, -light.direction.z]);
+        for (index, uv, _) in self.fullscreen_pixels() {
+            let gbuffer_sample = self.sample_gbuffer(gbuffer, uv);
+            let shade = shade(&gbuffer_sample, dir, light_color);
+            self.blend_target(index, [shade[0], shade[1], shade[2], 0f32], 1f32, 1f32, false);
+        }
+    }
+    fn draw_bloom_prefilter(&mut self, _source : u32, _threshold : f32) {
+    }
+    fn draw_bloom_downsample(&mut self, _source : u32) {
+    }
+    fn draw_bloom_upsample(&mut self, _source : u32, _radius : f32) {
+    }
+    fn draw_tone_mapping(&mut self, scene : u32, bloom : u32, tone_mapping : &ToneMapping, bloom_intensity : f32) {
+        let mut scene_exposure = tone_mapping.exposure;
+        if tone_mapping.auto_exposure {
+            // what the smallest mip level of the scene holds
+            let texels = self.read_texture(scene);
+            let mut average = [0f32; 3];
+            for texel in texels.iter() {
+                for channel in 0..3 {
+                    average[channel] += texel[channel] / texels.len() as f32;
+                }
+            }
+            let average_luminance = (average[0] * 0.2126f32 + average[1] * 0.7152f32 + average[2] * 0.0722f32).max(0.0001f32);
+            scene_exposure *= (tone_mapping.auto_exposure_key / average_luminance).max(tone_mapping.min_exposure).min(tone_mapping.max_exposure);
+        }
+        for (index, uv, _) in self.fullscreen_pixels() {
+            let hdr_texel = self.sample(scene, uv);
+            let bloom_texel = self.sample(bloom, uv);
+            let mut out = [0f32, 0f32, 0f32, hdr_texel[3]];
+            for channel in 0..3 {
+                let hdr = (hdr_texel[channel] + bloom_texel[channel] * bloom_intensity) * scene_exposure;
+                out[channel] = match tone_mapping.operator {
+                    ToneMappingOperator::None => hdr.max(0f32).min(1f32),
+                    ToneMappingOperator::Reinhard => hdr / (1f32 + hdr),
+                    ToneMappingOperator::Aces => aces(hdr),
+                    ToneMappingOperator::Filmic => hable(hdr * 2f32) / hable(11.2f32),
+                };
+            }
+            self.write_target(0, index, out);
+        }
+    }
+    fn draw_post_effect(&mut self, _effect : u32, _source : u32, _scene : u32, _gbuffer : &BackendGBuffer, _params : &[(String, UniformValue)], _time : f32) {
+    }
+    fn draw_composite(&mut self, source : u32, viewport : (i32, i32, i32, i32), scissor : (i32, i32, i32, i32), blend : bool) {
+        self.viewport = viewport;
+        let (width, height) = self.target_size();
+        let begin_x = scissor.0.max(viewport.0).max(0);
+        let begin_y = scissor.1.max(viewport.1).max(0);
+        let end_x = (scissor.0 + scissor.2).min(viewport.0 + viewport.2).min(width);
+        let end_y = (scissor.1 + scissor.3).min(viewport.1 + viewport.3).min(height);
+        for y in begin_y..end_y {
+            for x in begin_x..end_x {
+                let uv = Vector2 {
+                    x : ((x - viewport.0) as f32 + 0.5f32) / viewport.2 as f32,
+                    y : ((y - viewport.1) as f32 + 0.5f32) / viewport.3 as f32,
+                };
+                let color = self.sample(source, uv);
+                let index = (y * width + x) as usize;
+                if blend {
+                    self.blend_target(index, color, color[3], 1f32 - color[3], false);
+                } else {
+                    self.write_target(0, index, color);
+                }
+            }
+        }
+    }
+
+    fn supports_particle_simulation(&self) -> bool {
+        false
+    }
+    fn create_particle_system(&mut self, _capacity : usize) -> u32 {
+        0
+    }
+    fn delete_particle_system(&mut self, _system : u32) {
+    }
+    fn simulate_particles(&mut self, _system : u32, _spawns : &[(usize, ParticleSpawn)], _simulation : &ParticleSimulation) {
+    }
+    fn particle_instances(&self, _system : u32) -> u32 {
+        0
+    }
+
+    fn drop(&mut self) {
+        self.textures.clear();
+        self.buffers.clear();
+        self.targets.clear();
+        self.depth.clear();
+        self.window = 0;
+        self.frame = None;
+    }
+}
+
+// blinn phong with the viewer straight above, see GLSL_LIGHTING_INCLUDE
+fn specular_highlight(gbuffer : &GBufferSample, dir : [f32; 3], theta : f32) -> f32 {
+    if theta <= 0f32 {
+        return 0f32;
+    }
+    let half_dir = normalize3([dir[0], dir[1], dir[2] + 1f32]);
+    gbuffer.specular * dot3(gbuffer.normal, half_dir).max(0f32).powf(gbuffer.shininess)
+}
+fn shade(gbuffer : &GBufferSample, dir : [f32; 3], light_color : [f32; 3]) -> [f32; 3] {
+    let theta = dot3(dir, gbuffer.normal).max(0f32);
+    let highlight = specular_highlight(gbuffer, dir, theta);
+    let mut shade = [0f32; 3];
+    for channel in 0..3 {
+        shade[channel] = (gbuffer.color[channel] * theta + highlight) * light_color[channel] * (1f32 - gbuffer.unlit);
+    }
+    shade
+}
+fn distance_attenuation(to_light : [f32; 3], range : f32) -> f32 {
+    let linear_attenuation = (range - dot3(to_light, to_light).sqrt()) / range;
+    linear_attenuation.max(0f32).powf(2f32)
+}
+fn smoothstep(edge0 : f32, edge1 : f32, x : f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).max(0f32).min(1f32);
+    t * t * (3f32 - 2f32 * t)
+}
+// the curves of FRAGMENT_TONE_MAPPING_SHADER_SOURCE
+fn aces(x : f32) -> f32 {
+    ((x * (2.51f32 * x + 0.03f32)) / (x * (2.43f32 * x + 0.59f32) + 0.14f32)).max(0f32).min(1f32)
+}
+fn hable(x : f32) -> f32 {
+    let (a, b, c, d, e, f) = (0.15f32, 0.50f32, 0.10f32, 0.20f32, 0.02f32, 0.30f32);
+    ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+}
+// what a texture of the format keeps of the value
+fn store(format : TextureFormat, value : [f32; 4]) -> [f32; 4] {
+    let quantize = |channel : f32| (channel.max(0f32).min(1f32) * 255f32).round() / 255f32;
+    match format {
+        TextureFormat::Rgba8 => [quantize(value[0]), quantize(value[1]), quantize(value[2]), quantize(value[3])],
+        TextureFormat::Rgba16F => value,
+        TextureFormat::R8 => [quantize(value[0]), 0f32, 0f32, 1f32],
+        TextureFormat::R32F => [value[0], 0f32, 0f32, 1f32],
+    }
+}
+fn dot3(a : [f32; 3], b : [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+// a zero vector has no direction, it faces the viewer like a flat normal
+fn normalize3(v : [f32; 3]) -> [f32; 3] {
+    let length = dot3(v, v).sqrt();
+    if length <= f32::EPSILON {
+        return [0f32, 0f32, 1f32];
+    }
+    [v[0] / length, v[1] / length, v[2] / length]
+}
+// a zero vector stays zero
+fn normalize2(v : [f32; 2], sign : f32) -> [f32; 2] {
+    let length = (v[0] * v[0] + v[1] * v[1]).sqrt();
+    if length <= f32::EPSILON {
+        return [0f32, 0f32];
+    }
+    [v[0] / length * sign, v[1] / length * sign]
+}
+// Matrix3x2::inverse only handles rotation and translation, sprites can be scaled
+fn affine_inverse(matrix : &Matrix3x2) -> Option<Matrix3x2> {
+    let e = &matrix.elements;
+    let determinant = e[0] * e[3] - e[2] * e[1];
+    if determinant == 0f32 {
+        return None;
+    }
+    let a = e[3] / determinant;
+    let b = -e[1] / determinant;
+    let c = -e[2] / determinant;
+    let d = e[0] / determinant;
+    Some(Matrix3x2 {
+        elements : [
+            a, b,
+            c, d,
+            -(a * e[4] + c * e[5]), -(b * e[4] + d * e[5]),
+        ]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tpixel::render_backend::{max_difference, sprite_bytes};
+    use crate::tpixel::sprite::SPRITE_VISIBLE;
+    use crate::tpixel::color::Color;
+    use crate::tpixel::rect::Rect;
+    use crate::tpixel::vector3::Vector3;
+
+    const CLEAR : [f32; 4] = [0f32; 4];
+    const RED : [f32; 4] = [1f32, 0f32, 0f32, 1f32];
+    const GREEN : [f32; 4] = [0f32, 1f32, 0f32, 1f32];
+
+    fn new_texture(backend : &mut CpuBackend, width : i32, height : i32, format : TextureFormat, pixels : Option<&[u8]>) -> u32 {
+        backend.create_texture(&TextureDesc {
+            width : width,
+            height : height,
+            format : format,
+            filter : TextureFilter::Nearest,
+            wrap : TextureWrap::Clamp,
+        }, pixels)
+    }
+    // a 4x4 target with a world unit per pixel and the origin in its middle
+    fn new_frame(ambient : Color) -> BackendFrame {
+        BackendFrame {
+            camera_transform : Matrix3x2::new(),
+            camera_transform_inverse : Matrix3x2::new(),
+            camera_view : Vector2 { x : 2f32, y : 2f32 },
+            resolution : (4, 4),
+            ambient : ambient,
+            time : 0f32,
+        }
+    }
+    fn new_sprite(position : Vector2, z : f32) -> Sprite {
+        Sprite {
+            transform : Matrix3x2::new_translation(position),
+            pivot : Vector2::new(),
+            color : Color::new(),
+            uv_rect : Rect::new_uv(),
+            z : z,
+            height : 0f32,
+            height_map_scale : 0f32,
+            material_id : 0,
+            layer : 0,
+            flags : SPRITE_VISIBLE,
+        }
+    }
+    fn new_gbuffer(backend : &mut CpuBackend) -> BackendGBuffer {
+        BackendGBuffer {
+            buffer_width : 4,
+            buffer_height : 4,
+            color : new_texture(backend, 4, 4, TextureFormat::Rgba8, None),
+            normal : new_texture(backend, 4, 4, TextureFormat::Rgba8, None),
+            material : new_texture(backend, 4, 4, TextureFormat::Rgba8, None),
+            height : new_texture(backend, 4, 4, TextureFormat::R32F, None),
+            shadow_map : 0,
+        }
+    }
+    // the 2x2 material every sprite below is drawn with, one world unit per texel
+    fn draw_solid_sprites(backend : &mut CpuBackend, gbuffer : &BackendGBuffer, sprites : &[(Sprite, [u8; 4])]) {
+        backend.set_frame(&new_frame(Color::new()));
+        backend.begin_pass(&[gbuffer.color, gbuffer.normal, gbuffer.material, gbuffer.height], &[CLEAR, [0.5, 0.5, 1.0, 0.0], CLEAR, CLEAR], true).unwrap();
+        let instance_buffer = backend.create_buffer(std::mem::size_of::<Sprite>());
+        for (sprite, pixel) in sprites.iter() {
+            let color = new_texture(backend, 2, 2, TextureFormat::Rgba8, Some(&pixel.repeat(4)));
+            let params : Vec<(String, UniformValue)> = Vec::new();
+            let material = BackendMaterial {
+                color : color,
+                normal : 0,
+                material : 0,
+                size : Vector2 { x : 2f32, y : 2f32 },
+                shader : None,
+                params : &params,
+            };
+            backend.write_buffer(instance_buffer, 0, sprite_bytes(std::slice::from_ref(sprite)));
+            backend.draw_sprites(SpritePass::Geometry, &material, instance_buffer, 1);
+        }
+    }
+    // the gbuffer of a flat, white, lit surface without specular
+    fn new_lit_gbuffer(backend : &mut CpuBackend) -> BackendGBuffer {
+        let gbuffer = new_gbuffer(backend);
+        backend.begin_pass(&[gbuffer.color, gbuffer.normal, gbuffer.material, gbuffer.height],
+            &[[1.0, 1.0, 1.0, 1.0], [0.5, 0.5, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0], CLEAR], false).unwrap();
+        gbuffer
+    }
+    fn texels(pixels : &[(i32, i32)], inside : [f32; 4], outside : [f32; 4]) -> Vec<[f32; 4]> {
+        (0..16).map(|index| if pixels.contains(&(index % 4, index / 4)) { inside } else { outside }).collect()
+    }
+
+    #[test]
+    fn sprite_covers_only_its_pixels() {
+        let mut backend = CpuBackend::new();
+        let gbuffer = new_gbuffer(&mut backend);
+        draw_solid_sprites(&mut backend, &gbuffer, &[(new_sprite(Vector2::new(), 0f32), [255, 0, 0, 255])]);
+        let expected = texels(&[(2, 2), (3, 2), (2, 3), (3, 3)], RED, CLEAR);
+        assert!(max_difference(&backend.read_texture(gbuffer.color), &expected) < 1e-6);
+    }
+
+    #[test]
+    fn sprite_partly_outside_the_target_is_clipped() {
+        let mut backend = CpuBackend::new();
+        let gbuffer = new_gbuffer(&mut backend);
+        draw_solid_sprites(&mut backend, &gbuffer, &[(new_sprite(Vector2 { x : -3f32, y : 1f32 }, 0f32), [255, 0, 0, 255])]);
+        let expected = texels(&[(0, 3)], RED, CLEAR);
+        assert!(max_difference(&backend.read_texture(gbuffer.color), &expected) < 1e-6);
+    }
+
+    #[test]
+    fn closer_sprite_wins_the_depth_test() {
+        let mut backend = CpuBackend::new();
+        let gbuffer = new_gbuffer(&mut backend);
+        draw_solid_sprites(&mut backend, &gbuffer, &[
+            (new_sprite(Vector2::new(), 0.5f32), [255, 0, 0, 255]),
+            (new_sprite(Vector2 { x : -1f32, y : 0f32 }, 0f32), [0, 255, 0, 255]),
+        ]);
+        let mut expected = texels(&[(2, 2), (3, 2), (2, 3), (3, 3)], RED, CLEAR);
+        expected[2 * 4 + 1] = GREEN;
+        expected[3 * 4 + 1] = GREEN;
+        assert!(max_difference(&backend.read_texture(gbuffer.color), &expected) < 1e-6);
+    }
+
+    #[test]
+    fn ambience_and_directional_light_add_up() {
+        let mut backend = CpuBackend::new();
+        let gbuffer = new_lit_gbuffer(&mut backend);
+        let light = new_texture(&mut backend, 4, 4, TextureFormat::Rgba16F, None);
+        backend.set_frame(&new_frame(Color { r : 0.25f32, g : 0.25f32, b : 0.25f32, a : 1f32 }));
+        backend.begin_pass(&[light], &[CLEAR], false).unwrap();
+        backend.draw_ambience(&gbuffer);
+        backend.draw_directional_light(&gbuffer, &DirectionalLight {
+            color : Color::new(),
+            intensity : 0.5f32,
+            direction : Vector3 { x : 0f32, y : 0f32, z : -1f32 },
+        });
+        let expected = vec![[0.75f32, 0.75f32, 0.75f32, 1f32]; 16];
+        assert!(max_difference(&backend.read_texture(light), &expected) < 1e-3);
+    }
+
+    #[test]
+    fn point_light_falls_off_to_nothing_at_its_range() {
+        let mut backend = CpuBackend::new();
+        let gbuffer = new_lit_gbuffer(&mut backend);
+        let light = new_texture(&mut backend, 4, 4, TextureFormat::Rgba16F, None);
+        backend.set_frame(&new_frame(Color::new()));
+        backend.begin_pass(&[light], &[CLEAR], false).unwrap();
+        backend.draw_point_light(&gbuffer, &PointLight {
+            color : Color::new(),
+            position : Vector2::new(),
+            height : 1f32,
+            range : 2f32,
+        }, [-1f32, 0f32, 0f32, 0f32]);
+        let texels = backend.read_texture(light);
+        // the pixel at 1, 1 is centered on -0.5, -0.5 in the world
+        let to_light = [0.5f32, 0.5f32, 1f32];
+        let distance = dot3(to_light, to_light).sqrt();
+        let lit = ((2f32 - distance) / 2f32).powf(2f32) * (1f32 / distance);
+        assert!(max_difference(&texels[1 * 4 + 1..1 * 4 + 2], &[[lit, lit, lit, 0f32]]) < 1e-3);
+        // the corners are out of range
+        assert!(max_difference(&texels[0..1], &[CLEAR]) < 1e-6);
+    }
+
+    #[test]
+    fn tone_mapping_without_an_operator_clamps() {
+        let mut backend = CpuBackend::new();
+        let scene = new_texture(&mut backend, 1, 1, TextureFormat::Rgba16F, None);
+        let bloom = new_texture(&mut backend, 1, 1, TextureFormat::Rgba16F, None);
+        let output = new_texture(&mut backend, 1, 1, TextureFormat::Rgba8, None);
+        backend.begin_pass(&[scene], &[[2f32, 0.5f32, -1f32, 1f32]], false).unwrap();
+        backend.begin_pass(&[output], &[], false).unwrap();
+        backend.draw_tone_mapping(scene, bloom, &ToneMapping::new(), 0f32);
+        assert!(max_difference(&backend.read_texture(output), &[[1f32, 0.5f32, 0f32, 1f32]]) <= 1f32 / 255f32);
+    }
+
+    #[test]
+    fn composite_scales_the_source_over_the_viewport() {
+        let mut backend = CpuBackend::new();
+        let source = new_texture(&mut backend, 2, 1, TextureFormat::Rgba8, Some(&[255, 0, 0, 255, 0, 255, 0, 255]));
+        backend.begin_frame(4, 4);
+        backend.begin_pass(&[], &[], false).unwrap();
+        backend.draw_composite(source, (0, 0, 4, 4), (0, 0, 4, 2), false);
+        let mut expected = texels(&[(0, 0), (1, 0), (0, 1), (1, 1)], RED, [0f32, 0f32, 0f32, 1f32]);
+        for pixel in [(2, 0), (3, 0), (2, 1), (3, 1)].iter() {
+            expected[(pixel.1 * 4 + pixel.0) as usize] = GREEN;
+        }
+        assert!(max_difference(&backend.read_window(), &expected) < 1e-6);
+    }
+
+    #[test]
+    fn begin_pass_into_a_missing_texture_fails() {
+        let mut backend = CpuBackend::new();
+        assert!(backend.begin_pass(&[42], &[CLEAR], false).is_err());
+    }
+
+    #[test]
+    fn zero_vectors_normalize_to_a_flat_normal() {
+        assert_eq!(normalize3([0f32; 3]), [0f32, 0f32, 1f32]);
+        assert_eq!(normalize2([0f32; 2], 1f32), [0f32, 0f32]);
+    }
+}
@@ -0,0 +1,147 @@
+use crate::tpixel::vector2::Vector2;
+use crate::tpixel::color::Color;
+use crate::tpixel::rect::Rect;
+
+const DEBUG_CIRCLE_SEGMENTS : usize = 32;
+const DEBUG_ARROW_HEAD_SIZE : f32 = 0.2; // fraction of the arrow length
+const DEBUG_CROSS_SIZE : f32 = 4.0;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum DebugSpace {
+    World, // drawn into every camera's view
+    Screen, // canvas pixels from the bottom left, drawn on top of the window
+}
+
+pub(crate) struct DebugLine {
+    pub begin : Vector2,
+    pub end : Vector2,
+    pub color : Color,
+    pub space : DebugSpace,
+    remaining : f32,
+}
+
+pub(crate) struct DebugText {
+    pub position : Vector2,
+    pub text : String,
+    pub color : Color,
+    pub space : DebugSpace,
+    remaining : f32,
+}
+
+// everything queued for drawing, lines and texts live until their lifetime has run out
+pub struct DebugDraw {
+    pub(crate) lines : Vec<DebugLine>,
+    pub(crate) texts : Vec<DebugText>,
+    pub(crate) font_id : Option<u32>,
+}
+
+// queues shapes with the space and lifetime it was set up with, get one from Engine::debug_draw
+pub struct DebugDrawer<'a> {
+    debug_draw : &'a mut DebugDraw,
+    space : DebugSpace,
+    lifetime : f32,
+}
+
+impl DebugDraw {
+    pub fn new() -> DebugDraw {
+        DebugDraw {
+            lines : Vec::new(),
+            texts : Vec::new(),
+            font_id : None,
+        }
+    }
+    pub fn drawer(&mut self) -> DebugDrawer<'_> {
+        DebugDrawer {
+            debug_draw : self,
+            space : DebugSpace::World,
+            lifetime : 0f32,
+        }
+    }
+    pub fn set_font(&mut self, font_id : u32) {
+        self.font_id = Some(font_id);
+    }
+    pub fn clear(&mut self) {
+        self.lines.clear();
+        self.texts.clear();
+    }
+    // called after a frame is drawn, anything without a lifetime is drawn exactly once
+    pub(crate) fn advance(&mut self, delta_time : f32) {
+        self.lines.retain(|line| line.remaining > delta_time);
+        self.texts.retain(|text| text.remaining > delta_time);
+        for line in self.lines.iter_mut() {
+            line.remaining -= delta_time;
+        }
+        for text in self.texts.iter_mut() {
+            text.remaining -= delta_time;
+        }
+    }
+}
+
+impl<'a> DebugDrawer<'a> {
+    pub fn screen(&mut self) -> &mut DebugDrawer<'a> {
+        self.space = DebugSpace::Screen;
+        self
+    }
+    pub fn world(&mut self) -> &mut DebugDrawer<'a> {
+        self.space = DebugSpace::World;
+        self
+    }
+    // seconds to keep drawing what is queued from here on
+    pub fn lifetime(&mut self, seconds : f32) -> &mut DebugDrawer<'a> {
+        self.lifetime = seconds;
+        self
+    }
+    pub fn line(&mut self, begin : Vector2, end : Vector2, color : Color) -> &mut DebugDrawer<'a> {
+        self.debug_draw.lines.push(DebugLine {
+            begin : begin,
+            end : end,
+            color : color,
+            space : self.space,
+            remaining : self.lifetime,
+        });
+        self
+    }
+    pub fn circle(&mut self, center : Vector2, radius : f32, color : Color) -> &mut DebugDrawer<'a> {
+        let point = |i : usize| -> Vector2 {
+            let angle = i as f32 / DEBUG_CIRCLE_SEGMENTS as f32 * std::f32::consts::PI * 2f32;
+            Vector2 { x : center.x + angle.cos() * radius, y : center.y + angle.sin() * radius }
+        };
+        for i in 0..DEBUG_CIRCLE_SEGMENTS {
+            self.line(point(i), point(i + 1), color);
+        }
+        self
+    }
+    pub fn rect(&mut self, rect : Rect, color : Color) -> &mut DebugDrawer<'a> {
+        let top_left = Vector2 { x : rect.begin.x, y : rect.end.y };
+        let bottom_right = Vector2 { x : rect.end.x, y : rect.begin.y };
+        self.line(rect.begin, bottom_right, color);
+        self.line(bottom_right, rect.end, color);
+        self.line(rect.end, top_left, color);
+        self.line(top_left, rect.begin, color);
+        self
+    }
+    pub fn arrow(&mut self, begin : Vector2, end : Vector2, color : Color) -> &mut DebugDrawer<'a> {
+        let back = Vector2 { x : (begin.x - end.x) * DEBUG_ARROW_HEAD_SIZE, y : (begin.y - end.y) * DEBUG_ARROW_HEAD_SIZE };
+        let side = Vector2 { x : -back.y * 0.5f32, y : back.x * 0.5f32 };
+        self.line(begin, end, color);
+        self.line(end, Vector2 { x : end.x + back.x + side.x, y : end.y + back.y + side.y }, color);
+        self.line(end, Vector2 { x : end.x + back.x - side.x, y : end.y + back.y - side.y }, color);
+        self
+    }
+    pub fn cross(&mut self, center : Vector2, color : Color) -> &mut DebugDrawer<'a> {
+        self.line(Vector2 { x : center.x - DEBUG_CROSS_SIZE, y : center.y - DEBUG_CROSS_SIZE }, Vector2 { x : center.x + DEBUG_CROSS_SIZE, y : center.y + DEBUG_CROSS_SIZE }, color);
+        self.line(Vector2 { x : center.x - DEBUG_CROSS_SIZE, y : center.y + DEBUG_CROSS_SIZE }, Vector2 { x : center.x + DEBUG_CROSS_SIZE, y : center.y - DEBUG_CROSS_SIZE }, color);
+        self
+    }
+    // needs a font set with Engine::set_debug_font, otherwise it is dropped
+    pub fn text(&mut self, position : Vector2, text : &str, color : Color) -> &mut DebugDrawer<'a> {
+        self.debug_draw.texts.push(DebugText {
+            position : position,
+            text : text.to_string(),
+            color : color,
+            space : self.space,
+            remaining : self.lifetime,
+        });
+        self
+    }
+}
@@ -1,562 +1,1090 @@
 use crate::tpixel::registry::Registry;
-use crate::tpixel::sprite::Sprite;
+use crate::tpixel::sprite::{Sprite, SPRITE_VISIBLE, SPRITE_CASTS_SHADOW};
 use crate::tpixel::sparse_map::SparseMap;
 use crate::tpixel::vector2::Vector2;
 use crate::tpixel::material_info::MaterialInfo;
+use crate::tpixel::uniform_value::UniformValue;
+use crate::tpixel::shader_error::ShaderError;
+use crate::tpixel::resource_error::ResourceError;
+use crate::tpixel::shader_quality::ShaderQuality;
 use crate::tpixel::shader_factory::ShaderFactory;
-use crate::tpixel::shader_program::ShaderProgram;
-use crate::tpixel::camera::Camera;
+use crate::tpixel::camera::{Camera, layer_in_mask};
 use crate::tpixel::color::Color;
 use crate::tpixel::point_light::PointLight;
+use crate::tpixel::directional_light::DirectionalLight;
+use crate::tpixel::spot_light::SpotLight;
+use crate::tpixel::shadow_caster::{ShadowCaster, ShadowShape, triangulate};
 use crate::tpixel::matrix3x2::Matrix3x2;
-
-use gl::types::*;
-use std::ptr;
-use std::str;
-use std::os::raw::c_void;
-
-const VERTEX_GEO_SHADER_SOURCE : &str = r#"
-    #version 420 core
-    // vertex
-    layout (location = 0) in vec2 vertex_pos;
-    // instance
-    layout (location = 1) in mat3x2 instance_transform;
-    layout (location = 4) in vec2 instance_pivot;
-    layout (location = 5) in vec4 instance_color;
-    layout (location = 6) in vec4 instance_uv;
-    layout (location = 7) in float instance_z;
-    layout (location = 8) in float instance_height;
-    layout (location = 9) in uint instance_id;
-
-    out vec2 UV;
-    out vec4 COLOR;
-
-    uniform mat3x2 camera_transform;
-    uniform vec2 camera_view;
-
-    uniform vec2 image_size;
-
-    void main() {
-        int uv_x = gl_VertexID % 2;
-        int uv_y = gl_VertexID / 2;
-        UV.x = instance_uv[uv_x*2];//[0];
-        UV.y = instance_uv[uv_y*2+1];//[1];
-
-        vec2 quad_size = instance_uv.zw - instance_uv.xy;
-        vec2 instance_size = quad_size * image_size;
-
-        vec2 world_pos = instance_transform * vec3(vertex_pos.xy * instance_size - instance_size * instance_pivot, 1.0f);
-        vec2 view_pos = camera_transform * vec3(world_pos, 1.0f);
-        view_pos /= camera_view;
-        gl_Position = vec4(view_pos.x, view_pos.y, -instance_z, 1.0);
-        COLOR = instance_color;
-    }
-"#;
-
-const FRAGMENT_GEO_SHADER_SOURCE : &str = r#"
-    #version 420 core
-    layout(location=0) out vec4 out_color;
-    layout(location=1) out vec4 out_normal;
-    layout(location=2) out vec4 out_material;
-
-    in vec2 UV;
-    in vec4 COLOR;
-
-    layout(binding=0) uniform sampler2D image_color;
-    layout(binding=1) uniform sampler2D image_normal;
-    layout(binding=2) uniform sampler2D image_material;
-
-    void main() {
-        vec4 color = texture(image_color, UV);
-        if (color.a * COLOR.a < 0.1f) {
-            //discard;
-        }
-        out_color = COLOR * color;
-        out_normal = texture(image_normal, UV);
-        out_material = texture(image_material, UV);
-    }
-"#;
-
-const VERTEX_FULLSCREEN_SHADER_SOURCE : &str = r#"
-    #version 420 core
-
-    out vec2 UV;
-    out vec2 WORLD_POSITION;
-
-    uniform mat3x2 camera_transform_inverse;
-    uniform vec2 camera_view;
-
-    void main()
-    {
-        float x = -1.0 + float((gl_VertexID & 1) << 2);
-        float y = -1.0 + float((gl_VertexID & 2) << 1);
-        UV.x = (x+1.0)*0.5;
-        UV.y = (y+1.0)*0.5;
-
-        vec2 view_pos = vec2(x, y);
-        WORLD_POSITION = view_pos / camera_view;
-        WORLD_POSITION = camera_transform_inverse * vec3(WORLD_POSITION, 1.0);
-        
-        WORLD_POSITION = view_pos * camera_view;
-        WORLD_POSITION = camera_transform_inverse * vec3(WORLD_POSITION, 1.0);
-
-        gl_Position = vec4(x, y, 0, 1);
-    }
-"#;
-
-const FRAGMENT_AMBIENCE_SHADER_SOURCE : &str = r#"
-    #version 420 core
-    out vec4 out_color;
-
-    layout(binding=0) uniform sampler2D image_color;
-    layout(binding=1) uniform sampler2D image_normal;
-    layout(binding=2) uniform sampler2D image_material;
-
-    in vec2 UV;
-    in vec2 WORLD_POSITION;
-
-    uniform vec4 world_ambience;
-
-    void main() {
-        vec4 col = texture(image_color, UV);
-        vec4 mat = texture(image_material, UV);
-        vec4 nor = texture(image_normal, UV);
-        
-        out_color.rgba = col.rgba;
-        out_color.rgb *= world_ambience.rgb * world_ambience.a;
-    }
-"#;
-
-const FRAGMENT_POINT_LIGHT_SHADER_SOURCE : &str = r#"
-    #version 420 core
-    out vec4 out_color;
-
-    layout(binding=0) uniform sampler2D image_color;
-    layout(binding=1) uniform sampler2D image_normal;
-    layout(binding=2) uniform sampler2D image_material;
-
-    in vec2 UV;
-    in vec2 WORLD_POSITION;
-
-    uniform vec4 light_color;
-    uniform vec4 light_position;
-
-    void main() {
-        vec4 col = texture(image_color, UV);
-        vec4 mat = texture(image_material, UV);
-        vec4 nor = texture(image_normal, UV);
-
-        float light_height = light_position.z;
-        float light_range = light_position.w;
-        vec3 world_pos = vec3(WORLD_POSITION.xy, 0.0); // TODO fix height
-        float linear_attenuation = (light_range - length(world_pos - light_position.xyz)) / light_range; 
-        float attenuation = pow(max(0.0, linear_attenuation), 2.0);
-        vec3 light_color = light_color.rgb * light_color.a;
-
-        vec3 normal = vec3(nor.x * 2.0 - 1.0, nor.y * 2.0 - 1, 0.0);
-        normal.z = 1 - (normal.x * normal.x + normal.y * normal.y);
-        vec3 dir = normalize(light_position.xyz - world_pos);
-        float theta = max(0.0, dot(dir, normal));
-
-        out_color.rgb = col.rgb * light_color * attenuation * theta;
-        out_color.a = 1.0;
-    }
-"#;
+use crate::tpixel::rect::Rect;
+use crate::tpixel::tone_mapping::ToneMapping;
+use crate::tpixel::bloom::Bloom;
+use crate::tpixel::post_process::PostProcessStack;
+use crate::tpixel::tilemap::{Tilemap, EMPTY_TILE, TILEMAP_CHUNK_SIZE};
+use crate::tpixel::text::{Text, TextAlign, TextSpace};
+use crate::tpixel::font::FontInfo;
+use crate::tpixel::debug_draw::{DebugDraw, DebugSpace};
+use crate::tpixel::particle_emitter::{ParticleEmitter, ParticleBlend};
+use crate::tpixel::nine_slice::NineSlice;
+use crate::tpixel::render_backend::{RenderBackend, TextureDesc, TextureFormat, TextureFilter, TextureWrap, BackendFrame, BackendMaterial,
+    BackendGBuffer, SpritePass, ParticleSimulation, sprite_bytes};
 
 struct MaterialPrepInfo {
     material : MaterialInfo,
+    params : Vec<(String, UniformValue)>,
     batch : Vec<Sprite>,
 }
 
 const MAX_GEO_INSTANCE_COUNT : usize = 1024usize;
 
+const BLOOM_MIP_COUNT : usize = 6usize;
+
+const SHADOW_MAP_RESOLUTION : i32 = 512;
+const MAX_SHADOW_CASTING_LIGHTS : i32 = 64;
+const SHADOW_SOFTNESS_SCALE : f32 = 8.0f32; // softness in shadow map texels for a light as high as its range
+
 struct GBuffer {
-    renderbuffer : u32,
-    framebuffer : u32,
     color : u32,
     normal : u32,
     material : u32,
+    height : u32,
 }
 
-impl GBuffer {
-    pub fn new() -> GBuffer {
-        GBuffer {
-            renderbuffer : 0,
-            framebuffer : 0,
-            color : 0,
-            normal : 0,
-            material : 0,
+struct ShadowBuffer {
+    occlusion : u32,
+    shadow_map : u32, // one row per light, wrapping around horizontally so sampling across -PI/PI blends correctly
+}
+
+struct BloomMip {
+    color : u32,
+    width : i32,
+    height : i32,
+}
+
+// everything that has to match the size of a camera's viewport
+struct ViewTargets {
+    width : i32,
+    height : i32,
+    gbuffer : GBuffer,
+    shadow_buffer : ShadowBuffer,
+    light : u32, // the lit hdr scene, its smaller levels are the average for auto exposure
+    bloom_mips : Vec<BloomMip>,
+    post_buffers : [u32; 2], // ping pong targets of the post effects
+    output : u32,
+}
+
+impl ViewTargets {
+    pub fn new() -> ViewTargets {
+        ViewTargets {
+            width : 0,
+            height : 0,
+            gbuffer : GBuffer { color : 0, normal : 0, material : 0, height : 0 },
+            shadow_buffer : ShadowBuffer { occlusion : 0, shadow_map : 0 },
+            light : 0,
+            bloom_mips : Vec::new(),
+            post_buffers : [0; 2],
+            output : 0,
         }
     }
-    pub fn drop(&mut self) {
-        self.free();
-    }
-    pub fn build(&mut self, width : i32, height : i32) {
-        unsafe {
-            gl::GenFramebuffers(1, &mut self.framebuffer);
-            gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
-
-            gl::GenTextures(1, &mut self.color);
-            gl::BindTexture(gl::TEXTURE_2D, self.color);
-            gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA8 as GLint, width, height, 0, gl::RGBA, gl::UNSIGNED_BYTE, ptr::null());
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
-            gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, self.color, 0);
-
-            gl::GenTextures(1, &mut self.normal);
-            gl::BindTexture(gl::TEXTURE_2D, self.normal);
-            gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA8 as GLint, width, height, 0, gl::RGBA, gl::UNSIGNED_BYTE, ptr::null());
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
-            gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT1, gl::TEXTURE_2D, self.normal, 0);
-
-            gl::GenTextures(1, &mut self.material);
-            gl::BindTexture(gl::TEXTURE_2D, self.material);
-            gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA8 as GLint, width, height, 0, gl::RGBA, gl::UNSIGNED_BYTE, ptr::null());
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
-            gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT2, gl::TEXTURE_2D, self.material, 0);
-
-            let attachments = [gl::COLOR_ATTACHMENT0, gl::COLOR_ATTACHMENT1, gl::COLOR_ATTACHMENT2];
-            gl::DrawBuffers(3, &attachments[0]);
-
-            gl::GenRenderbuffers(1, &mut self.renderbuffer);
-            gl::BindRenderbuffer(gl::RENDERBUFFER, self.renderbuffer);
-            gl::RenderbufferStorage(gl::RENDERBUFFER, gl::DEPTH_COMPONENT, width, height);
-            gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, gl::RENDERBUFFER, self.renderbuffer);
-
-            if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
-                println!("Framebuffer did not complete!");
-            }
-            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+    pub fn build(&mut self, backend : &mut dyn RenderBackend, width : i32, height : i32) {
+        self.width = width;
+        self.height = height;
+        self.gbuffer = GBuffer {
+            color : new_target(backend, width, height, TextureFormat::Rgba8, TextureFilter::Nearest, TextureWrap::Clamp),
+            normal : new_target(backend, width, height, TextureFormat::Rgba8, TextureFilter::Nearest, TextureWrap::Clamp),
+            material : new_target(backend, width, height, TextureFormat::Rgba8, TextureFilter::Nearest, TextureWrap::Clamp),
+            height : new_target(backend, width, height, TextureFormat::R32F, TextureFilter::Nearest, TextureWrap::Clamp),
+        };
+        self.shadow_buffer = ShadowBuffer {
+            occlusion : new_target(backend, width, height, TextureFormat::R8, TextureFilter::Nearest, TextureWrap::Clamp),
+            shadow_map : new_target(backend, SHADOW_MAP_RESOLUTION, MAX_SHADOW_CASTING_LIGHTS, TextureFormat::R32F, TextureFilter::Nearest, TextureWrap::RepeatX),
+        };
+        self.light = new_target(backend, width, height, TextureFormat::Rgba16F, TextureFilter::LinearMipmaps, TextureWrap::Clamp);
+        let mut mip_width = width;
+        let mut mip_height = height;
+        for _ in 0..BLOOM_MIP_COUNT {
+            mip_width = (mip_width / 2).max(1);
+            mip_height = (mip_height / 2).max(1);
+            self.bloom_mips.push(BloomMip {
+                color : new_target(backend, mip_width, mip_height, TextureFormat::Rgba16F, TextureFilter::Linear, TextureWrap::Clamp),
+                width : mip_width,
+                height : mip_height,
+            });
+        }
+        for post_buffer in self.post_buffers.iter_mut() {
+            *post_buffer = new_target(backend, width, height, TextureFormat::Rgba16F, TextureFilter::Linear, TextureWrap::Clamp);
         }
+        self.output = new_target(backend, width, height, TextureFormat::Rgba8, TextureFilter::Nearest, TextureWrap::Clamp);
     }
-    pub fn rebuild(&mut self, width : i32, height : i32) {
-        self.free();
-        self.build(width, height);
+    pub fn rebuild(&mut self, backend : &mut dyn RenderBackend, width : i32, height : i32) {
+        self.free(backend);
+        self.build(backend, width, height);
     }
-    pub fn free(&mut self) {
-        unsafe {
-            gl::DeleteRenderbuffers(1, &self.framebuffer);
-            gl::DeleteFramebuffers(1, &self.framebuffer);
-            gl::DeleteTextures(1, &self.color);
-            gl::DeleteTextures(1, &self.normal);
-            gl::DeleteTextures(1, &self.material);
+    pub fn free(&mut self, backend : &mut dyn RenderBackend) {
+        let mut textures = vec![self.gbuffer.color, self.gbuffer.normal, self.gbuffer.material, self.gbuffer.height,
+            self.shadow_buffer.occlusion, self.shadow_buffer.shadow_map, self.light, self.post_buffers[0], self.post_buffers[1], self.output];
+        textures.extend(self.bloom_mips.drain(..).map(|mip| mip.color));
+        // a second free mustn't delete ids the backend has handed out again since
+        for texture in textures.into_iter().filter(|texture| *texture != 0) {
+            backend.delete_texture(texture);
+        }
+        *self = ViewTargets::new();
+    }
+    fn backend_gbuffer(&self) -> BackendGBuffer {
+        BackendGBuffer {
+            buffer_width : self.width,
+            buffer_height : self.height,
+            color : self.gbuffer.color,
+            normal : self.gbuffer.normal,
+            material : self.gbuffer.material,
+            height : self.gbuffer.height,
+            shadow_map : self.shadow_buffer.shadow_map,
         }
     }
 }
 
-pub struct DeferredRenderer {
-    geometry_shader : ShaderProgram,
-    ambience_shader : ShaderProgram,
-    point_light_shader : ShaderProgram,
+// instance data of one chunk of a tilemap, only rebuilt when the chunk's revision changes
+struct TilemapChunk {
+    instance_buffer : u32,
+    revision : u32,
+    count : usize,
+}
 
-    gbuffer : GBuffer,
+struct TilemapCache {
+    chunks : Vec<TilemapChunk>,
+    // everything baked into the instances besides the tiles, a change rebuilds all chunks
+    appearance : [f32; 13],
+    layer : u32,
+    material_id : u32,
+    seen : bool,
+}
 
-    geometry_shader_camera_transform : i32,
-    geometry_shader_camera_view : i32,
-    
-    geometry_shader_image_size : i32,
-    geometry_shader_image_color : i32,
-    geometry_shader_image_material : i32,
-    geometry_shader_image_normal : i32,
+impl TilemapCache {
+    pub fn drop(&mut self, backend : &mut dyn RenderBackend) {
+        for chunk in self.chunks.iter() {
+            backend.delete_buffer(chunk.instance_buffer);
+        }
+        self.chunks.clear();
+    }
+}
 
-    ambience_shader_camera_transform_inverse : i32,
-    ambience_shader_camera_view : i32,
+// a gpu simulated emitter and the step of it the backend's system holds
+struct ParticleCache {
+    system : u32,
+    capacity : usize,
+    generation : u32,
+    step : u32,
+    seen : bool,
+}
 
-    ambience_shader_world_ambience : i32,
+// what a camera renders into, the color texture of a render target material
+struct RenderTarget {
+    color : u32,
+    width : i32,
+    height : i32,
+}
 
-    point_light_shader_camera_transform_inverse : i32,
-    point_light_shader_camera_view : i32,
+// where a camera's view ends up and how it is laid out there
+struct ViewDestination {
+    color : u32, // 0 is the window
+    canvas_width : i32,
+    canvas_height : i32,
+    scale : i32,
+    offset_x : i32,
+    offset_y : i32,
+    pixel_snap : bool,
+    border : i32,
+}
 
-    point_light_shader_light_color : i32,
-    point_light_shader_light_position : i32,
+// the passes of the renderer, the backend does the drawing
+pub struct DeferredRenderer {
+    backend : Box<dyn RenderBackend>,
+
+    view_targets : Vec<ViewTargets>,
+    render_targets : SparseMap<RenderTarget>,
+    tilemap_caches : SparseMap<TilemapCache>,
+    particle_caches : SparseMap<ParticleCache>,
+    fonts : SparseMap<FontInfo>,
+    pub(crate) post_process : PostProcessStack,
+
+    window_width : i32,
+    window_height : i32,
+    internal_resolution : Option<(i32, i32)>,
+    subpixel_smoothing : bool,
+
+    instance_buffer : u32, // the material batches go through it MAX_GEO_INSTANCE_COUNT at a time
+    occluder_vertices : Vec<f32>,
+    debug_vertices : Vec<f32>,
 
-    instance_buffer_object : u32,
-    vertex_buffer_object : u32,
-    vertex_array_object : u32,
-    
     material_preps : SparseMap<MaterialPrepInfo>,
+
+    shader_quality : ShaderQuality,
+    render_errors : Vec<ResourceError>, // the passes of the frame being rendered that couldn't begin
 }
 
 impl DeferredRenderer {
-    pub fn new() -> DeferredRenderer {
+    pub fn new(backend : Box<dyn RenderBackend>) -> DeferredRenderer {
         DeferredRenderer {
-            geometry_shader : ShaderProgram::new(),
-            ambience_shader : ShaderProgram::new(),
-            point_light_shader : ShaderProgram::new(),
-
-            gbuffer : GBuffer::new(),
-
-            geometry_shader_camera_transform : 0,
-            geometry_shader_camera_view : 0,
-
-            geometry_shader_image_size : 0,
-            geometry_shader_image_color : 0,
-            geometry_shader_image_material : 0,
-            geometry_shader_image_normal : 0,
-
-            ambience_shader_camera_transform_inverse : 0,
-            ambience_shader_camera_view : 0,
-
-            ambience_shader_world_ambience : 0,
-
-            point_light_shader_camera_transform_inverse : 0,
-            point_light_shader_camera_view : 0,
-        
-            point_light_shader_light_color : 0,
-            point_light_shader_light_position : 0,
-            
-            instance_buffer_object : 0,
-            vertex_buffer_object : 0,
-            vertex_array_object : 0,
-            
+            backend : backend,
+
+            view_targets : Vec::new(),
+            render_targets : SparseMap::new(),
+            tilemap_caches : SparseMap::new(),
+            particle_caches : SparseMap::new(),
+            fonts : SparseMap::new(),
+            post_process : PostProcessStack::new(),
+
+            window_width : 1280,
+            window_height : 720,
+            internal_resolution : None,
+            subpixel_smoothing : false,
+
+            instance_buffer : 0,
+            occluder_vertices : Vec::new(),
+            debug_vertices : Vec::new(),
+
             material_preps : SparseMap::new(),
+
+            shader_quality : ShaderQuality::new(),
+            render_errors : Vec::new(),
         }
     }
     pub fn drop(&mut self) {
-        unsafe {
-            gl::DeleteBuffers(1, &self.instance_buffer_object);
-            gl::DeleteBuffers(1, &self.vertex_buffer_object);
-            gl::DeleteVertexArrays(1, &self.vertex_array_object);
+        for targets in self.view_targets.iter_mut() {
+            targets.free(&mut *self.backend);
+        }
+        self.view_targets.clear();
+        for tilemap_cache in self.tilemap_caches.all_iter_mut() {
+            tilemap_cache.value.drop(&mut *self.backend);
+        }
+        for particle_cache in self.particle_caches.all_iter() {
+            self.backend.delete_particle_system(particle_cache.value.system);
         }
+        self.post_process.drop(&mut *self.backend);
+        self.backend.delete_buffer(self.instance_buffer);
+        (*self.backend).drop();
+    }
+    pub fn backend_mut(&mut self) -> &mut dyn RenderBackend {
+        &mut *self.backend
     }
     pub fn prepare_material(&mut self, material_info : &MaterialInfo) {
         if !self.material_preps.contains_key(material_info.id) {
             self.material_preps.insert(material_info.id, MaterialPrepInfo{
                 material : *material_info,
+                params : Vec::new(),
                 batch : Vec::new(),
             });
         }
     }
-    pub fn init(&mut self, shader_factory : &ShaderFactory) {
-        self.geometry_shader = shader_factory.new_program(VERTEX_GEO_SHADER_SOURCE, FRAGMENT_GEO_SHADER_SOURCE);
-        self.ambience_shader = shader_factory.new_program(VERTEX_FULLSCREEN_SHADER_SOURCE, FRAGMENT_AMBIENCE_SHADER_SOURCE);
-        self.point_light_shader = shader_factory.new_program(VERTEX_FULLSCREEN_SHADER_SOURCE, FRAGMENT_POINT_LIGHT_SHADER_SOURCE);
-        
-        self.geometry_shader_camera_transform = self.geometry_shader.get_uniform_location("camera_transform");
-        self.geometry_shader_camera_view = self.geometry_shader.get_uniform_location("camera_view");
-
-        self.geometry_shader_image_size = self.geometry_shader.get_uniform_location("image_size");
-        self.geometry_shader_image_color = self.geometry_shader.get_uniform_location("image_color");
-        self.geometry_shader_image_material = self.geometry_shader.get_uniform_location("image_material");
-        self.geometry_shader_image_normal = self.geometry_shader.get_uniform_location("image_normal");
-
-        self.ambience_shader_camera_transform_inverse = self.ambience_shader.get_uniform_location("camera_transform_inverse");
-        self.ambience_shader_camera_view = self.ambience_shader.get_uniform_location("camera_view");
-
-        self.ambience_shader_world_ambience = self.ambience_shader.get_uniform_location("world_ambience");
-
-        self.point_light_shader_camera_transform_inverse = self.point_light_shader.get_uniform_location("camera_transform_inverse");
-        self.point_light_shader_camera_view = self.point_light_shader.get_uniform_location("camera_view");
-    
-        self.point_light_shader_light_color = self.point_light_shader.get_uniform_location("light_color");
-        self.point_light_shader_light_position = self.point_light_shader.get_uniform_location("light_position");
-
-        unsafe {
-            // TODO encapsulate
-            gl::Enable(gl::DEPTH_TEST);
-            gl::DepthFunc(gl::LESS);
-            
-            gl::GenBuffers(1, &mut self.instance_buffer_object);
-            gl::BindBuffer(gl::ARRAY_BUFFER, self.instance_buffer_object);
-            gl::BufferData(gl::ARRAY_BUFFER, (std::mem::size_of::<Sprite>() * MAX_GEO_INSTANCE_COUNT) as isize, 0 as *const f32 as *const c_void, gl::DYNAMIC_DRAW);
-            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
-            
-            let verts : [f32; 8] = [
-                // positions
-                0.0f32,  1.0f32,// 0.0f32, 
-                1.0f32,  1.0f32,// 0.0f32,
-                0.0f32,  0.0f32,// 0.0f32,
-                1.0f32,  0.0f32,// 0.0f32, 
-            ];
-
-            gl::GenBuffers(1, &mut self.vertex_buffer_object);
-
-            gl::GenVertexArrays(1, &mut self.vertex_array_object);
-            gl::BindVertexArray(self.vertex_array_object);
-            gl::BindBuffer(gl::ARRAY_BUFFER, self.vertex_buffer_object);
-            gl::BufferData(gl::ARRAY_BUFFER,
-                (std::mem::size_of::<f32>() * verts.len()) as isize,
-                &verts[0] as *const f32 as *const c_void,
-                gl::STATIC_DRAW);
-            gl::EnableVertexAttribArray(0);
-            gl::VertexAttribPointer(0, 2, gl::FLOAT, gl::FALSE, (std::mem::size_of::<f32>() * 2) as i32, 0 as *const c_void);
-            // instance stuff
-            gl::BindBuffer(gl::ARRAY_BUFFER, self.instance_buffer_object);
-            let stride = (std::mem::size_of::<f32>() * 19) as i32;
-            // transform
-            gl::EnableVertexAttribArray(1);
-            gl::VertexAttribPointer(1, 2, gl::FLOAT, gl::FALSE, stride, (std::mem::size_of::<f32>() * 0) as *const c_void);
-            gl::EnableVertexAttribArray(2);
-            gl::VertexAttribPointer(2, 2, gl::FLOAT, gl::FALSE, stride, (std::mem::size_of::<f32>() * 2) as *const c_void);
-            gl::EnableVertexAttribArray(3);
-            gl::VertexAttribPointer(3, 2, gl::FLOAT, gl::FALSE, stride, (std::mem::size_of::<f32>() * 4) as *const c_void);
-            // pivot
-            gl::EnableVertexAttribArray(4);
-            gl::VertexAttribPointer(4, 2, gl::FLOAT, gl::FALSE, stride, (std::mem::size_of::<f32>() * 6) as *const c_void);
-            // color
-            gl::EnableVertexAttribArray(5);
-            gl::VertexAttribPointer(5, 4, gl::FLOAT, gl::FALSE, stride, (std::mem::size_of::<f32>() * 8) as *const c_void);
-            // uv
-            gl::EnableVertexAttribArray(6);
-            gl::VertexAttribPointer(6, 4, gl::FLOAT, gl::FALSE, stride, (std::mem::size_of::<f32>() * 12) as *const c_void);
-            // z
-            gl::EnableVertexAttribArray(7);
-            gl::VertexAttribPointer(7, 1, gl::FLOAT, gl::FALSE, stride, (std::mem::size_of::<f32>() * 16) as *const c_void);
-            // height
-            gl::EnableVertexAttribArray(8);
-            gl::VertexAttribPointer(8, 1, gl::FLOAT, gl::FALSE, stride, (std::mem::size_of::<f32>() * 17) as *const c_void);
-            // id
-            gl::EnableVertexAttribArray(9);
-            gl::VertexAttribPointer(9, 1, gl::UNSIGNED_INT, gl::FALSE, stride, (std::mem::size_of::<f32>() * 18) as *const c_void);
-            
-            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
-            gl::VertexAttribDivisor(1, 1);
-            gl::VertexAttribDivisor(2, 1);
-            gl::VertexAttribDivisor(3, 1);
-            gl::VertexAttribDivisor(4, 1);
-            gl::VertexAttribDivisor(5, 1);
-            gl::VertexAttribDivisor(6, 1);
-            gl::VertexAttribDivisor(7, 1);
-            gl::VertexAttribDivisor(8, 1);
-            gl::VertexAttribDivisor(9, 1);
-
-
-            self.gbuffer.build(1280, 720);
-        }
-    }
-    pub fn render(&mut self, registry : &Registry, camera : &Camera, ambient_color : &Color) {
-        self.generate_gbuffer(registry, camera);
-        self.render_gbuffer(registry, camera, ambient_color);
+    // surface_source defines Surface surface(vec2 uv), see Engine::new_material_shader for what it can use
+    pub fn add_material_shader(&mut self, shader_factory : &ShaderFactory, surface_source : &str) -> Result<u32, ShaderError> {
+        self.backend.create_material_shader(shader_factory, surface_source)
     }
-
-    pub fn resize_geo_buffer(&mut self, width : i32, height : i32) {
-        self.gbuffer.rebuild(width, height);
+    pub fn set_material_shader(&mut self, material_id : u32, shader_id : Option<u32>) {
+        if !self.material_preps.contains_key(material_id) {
+            return;
+        }
+        self.material_preps.get_mut(material_id).material.shader_id = shader_id;
+    }
+    // the params keep their values when the material gets another shader
+    pub fn set_material_param(&mut self, material_id : u32, param_name : &str, value : UniformValue) {
+        if !self.material_preps.contains_key(material_id) {
+            return;
+        }
+        let params = &mut self.material_preps.get_mut(material_id).params;
+        match params.iter_mut().find(|(name, _)| name == param_name) {
+            Some(param) => param.1 = value,
+            None => params.push((param_name.to_string(), value)),
+        }
+    }
+    pub fn prepare_font(&mut self, font_info : FontInfo) {
+        if !self.fonts.contains_key(font_info.id) {
+            self.fonts.insert(font_info.id, font_info);
+        }
+    }
+    pub fn prepare_render_target(&mut self, material_info : &MaterialInfo) {
+        if self.render_targets.contains_key(material_info.id) {
+            return;
+        }
+        self.render_targets.insert(material_info.id, RenderTarget {
+            color : material_info.color,
+            width : material_info.size.x as i32,
+            height : material_info.size.y as i32,
+        });
+    }
+    // forgets the material, returns it so its textures can be released
+    pub fn free_render_target(&mut self, material_id : u32) -> Option<MaterialInfo> {
+        if !self.render_targets.contains_key(material_id) {
+            return None;
+        }
+        self.render_targets.remove(material_id);
+        let material_info = self.material_preps.get(material_id).material;
+        self.material_preps.remove(material_id);
+        Some(material_info)
+    }
+    // compiles the built in shaders, a file with the shader's name in the shader directory replaces its source,
+    // a shader that fails to build keeps its previous program
+    pub fn build_shaders(&mut self, shader_factory : &mut ShaderFactory) -> Vec<ShaderError> {
+        self.backend.build_shaders(shader_factory, None)
+    }
+    // rebuilds only the programs, material shaders and post effects made from the changed shader directory files
+    pub fn reload_shaders(&mut self, shader_factory : &mut ShaderFactory, changed : &[String]) -> Vec<ShaderError> {
+        self.backend.build_shaders(shader_factory, Some(changed))
+    }
+    // if any of the quality's shaders fails to build the quality stays as it was
+    pub fn set_shader_quality(&mut self, shader_factory : &mut ShaderFactory, shader_quality : ShaderQuality) -> Vec<ShaderError> {
+        if shader_quality == self.shader_quality {
+            return Vec::new();
+        }
+        let errors = self.backend.set_shader_quality(shader_factory, shader_quality);
+        if errors.is_empty() {
+            self.shader_quality = shader_quality;
+        }
+        errors
+    }
+    // the shaders that failed to build from the shader directory, the built in ones took their place
+    pub fn init(&mut self, shader_factory : &mut ShaderFactory) -> Vec<ShaderError> {
+        let errors = self.backend.init(shader_factory);
+        self.instance_buffer = self.backend.create_buffer(std::mem::size_of::<Sprite>() * MAX_GEO_INSTANCE_COUNT);
+        errors
     }
-    fn generate_gbuffer(&mut self, registry : &Registry, camera : &Camera) {
-        unsafe {
-            gl::Disable(gl::BLEND);
+    // the passes that couldn't begin are skipped and returned
+    pub fn render(&mut self, registry : &Registry, main_camera : &Camera, ambient_color : &Color, tone_mapping : &ToneMapping, bloom : &Bloom, time : f32, debug_draw : &DebugDraw) -> Vec<ResourceError> {
+        // a minimized window has nothing to draw into
+        if self.window_width <= 0 || self.window_height <= 0 {
+            return Vec::new();
+        }
+        // camera components take over from the main camera when there are any
+        let camera_map = registry.get_map::<Camera>();
+        let mut cameras : Vec<&Camera> = camera_map.all_iter().map(|camera_kv| &camera_kv.value).collect();
+        if cameras.is_empty() {
+            cameras.push(main_camera);
+        }
+        // offscreen cameras go first so sprites showing their textures get this frame's image
+        cameras.sort_by_key(|camera| (camera.render_target.is_none(), camera.priority));
+
+        let mut view_targets = std::mem::replace(&mut self.view_targets, Vec::new());
+        while view_targets.len() > cameras.len() {
+            let mut targets = view_targets.pop().unwrap();
+            targets.free(&mut *self.backend);
+        }
+        while view_targets.len() < cameras.len() {
+            view_targets.push(ViewTargets::new());
+        }
 
-            gl::ClearColor(0.0, 0.0, 0.0, 1.0);
-            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        self.update_tilemaps(registry);
+        self.simulate_particles(registry);
 
-            gl::BindFramebuffer(gl::FRAMEBUFFER, self.gbuffer.framebuffer);
-            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
-            self.geometry_shader.use_program();
+        self.backend.begin_frame(self.window_width, self.window_height);
+        // render targets start each frame transparent, cameras that don't clear draw over this frame's views only
+        let render_target_colors : Vec<u32> = self.render_targets.all_iter().map(|render_target_kv| render_target_kv.value.color).collect();
+        for color in render_target_colors {
+            self.begin_pass(&[color], &[[0f32; 4]], false);
         }
 
-        let camera_transform : Matrix3x2 = camera.transform.inverse();
-        
-        let camera_view = Vector2 { x : camera.view_size.x / 2.0f32, y : camera.view_size.y / 2.0f32 };
+        for (camera, targets) in cameras.iter().zip(view_targets.iter_mut()) {
+            let destination = match self.view_destination(camera) {
+                Some(destination) => destination,
+                None => continue,
+            };
+            let region = viewport_region(camera, &destination);
+            let width = region.2 + destination.border * 2;
+            let height = region.3 + destination.border * 2;
+            if width <= 0 || height <= 0 {
+                continue;
+            }
+            if targets.width != width || targets.height != height {
+                targets.rebuild(&mut *self.backend, width, height);
+            }
+
+            let (view_camera, subpixel_offset) = if destination.pixel_snap {
+                snap_camera(camera, region, destination.border)
+            } else {
+                ((*camera).clone(), Vector2::new())
+            };
+            self.render_view(registry, &view_camera, targets, ambient_color, tone_mapping, bloom, time, debug_draw);
+            self.composite_view(camera, targets, &destination, region, subpixel_offset);
+        }
+        self.render_overlay(registry, debug_draw, ambient_color, time);
+        self.backend.end_frame();
+
+        self.view_targets = view_targets;
+        std::mem::replace(&mut self.render_errors, Vec::new())
+    }
+    pub fn get_issued_gl_calls(&self) -> u32 {
+        self.backend.state_changes().0
+    }
+    pub fn get_skipped_gl_calls(&self) -> u32 {
+        self.backend.state_changes().1
+    }
+    // what the last frame left in the window, before the buffers are swapped
+    pub fn read_window(&mut self) -> Vec<[f32; 4]> {
+        self.backend.read_window()
+    }
+    // the draws until the next pass are skipped when it can't begin, the error goes out with the frame
+    fn begin_pass(&mut self, targets : &[u32], clear_colors : &[[f32; 4]], depth_test : bool) -> bool {
+        match self.backend.begin_pass(targets, clear_colors, depth_test) {
+            Ok(()) => true,
+            Err(error) => {
+                self.render_errors.push(error);
+                false
+            },
+        }
+    }
+    fn render_view(&mut self, registry : &Registry, camera : &Camera, targets : &ViewTargets, ambient_color : &Color, tone_mapping : &ToneMapping, bloom : &Bloom, time : f32, debug_draw : &DebugDraw) {
+        self.backend.set_frame(&BackendFrame {
+            camera_transform : camera.transform.inverse(),
+            camera_transform_inverse : camera.transform,
+            camera_view : Vector2 { x : camera.view_size.x / 2.0f32, y : camera.view_size.y / 2.0f32 },
+            resolution : (targets.width, targets.height),
+            ambient : *ambient_color,
+            time : time,
+        });
+
+        self.generate_gbuffer(registry, camera, targets);
+        let has_shadows = self.shader_quality.shadows && self.generate_occlusion(registry, camera, targets);
+        if has_shadows {
+            self.generate_shadow_maps(registry, targets);
+        }
+        self.render_gbuffer(registry, has_shadows, targets);
+        self.render_additive_particles(registry, camera, targets);
+        if bloom.enabled {
+            self.render_bloom(bloom, targets);
+        }
+        if self.post_process.has_enabled_effects() {
+            self.render_tone_mapping(tone_mapping, bloom, targets, targets.post_buffers[0]);
+            if let Err(error) = self.post_process.render(&mut *self.backend, targets.post_buffers, targets.light, &targets.backend_gbuffer(), time, targets.output) {
+                self.render_errors.push(error);
+            }
+        } else {
+            self.render_tone_mapping(tone_mapping, bloom, targets, targets.output);
+        }
+
+        // world debug drawing goes on top of the finished image
+        if self.begin_pass(&[targets.output], &[], false) {
+            self.render_debug(debug_draw, DebugSpace::World);
+        }
+    }
+
+    pub fn resize_geo_buffer(&mut self, width : i32, height : i32) {
+        // the view targets follow on the next render
+        self.window_width = width;
+        self.window_height = height;
+    }
+    pub fn set_internal_resolution(&mut self, internal_resolution : Option<(i32, i32)>, subpixel_smoothing : bool) {
+        self.internal_resolution = internal_resolution;
+        self.subpixel_smoothing = subpixel_smoothing;
+    }
+    pub fn get_internal_resolution(&self) -> Option<(i32, i32)> {
+        self.internal_resolution
+    }
+    pub fn supports_gpu_particles(&self) -> bool {
+        self.backend.supports_particle_simulation()
+    }
+    // an effect that fails to build leaves the stack as it was
+    pub fn add_post_effect(&mut self, shader_factory : &ShaderFactory, name : &str, fragment_source : &str, params : &[(&str, UniformValue)]) -> Result<(), ShaderError> {
+        self.post_process.add_effect(&mut *self.backend, shader_factory, name, fragment_source, params)
+    }
+    pub fn remove_post_effect(&mut self, name : &str) {
+        self.post_process.remove_effect(&mut *self.backend, name);
+    }
+    fn view_destination(&self, camera : &Camera) -> Option<ViewDestination> {
+        match camera.render_target {
+            Some(material_id) => {
+                if !self.render_targets.contains_key(material_id) {
+                    return None;
+                }
+                let render_target = self.render_targets.get(material_id);
+                Some(ViewDestination {
+                    color : render_target.color,
+                    canvas_width : render_target.width,
+                    canvas_height : render_target.height,
+                    scale : 1,
+                    offset_x : 0,
+                    offset_y : 0,
+                    pixel_snap : false,
+                    border : 0,
+                })
+            },
+            None => Some(self.window_destination()),
+        }
+    }
+    // the window is laid out at the internal resolution and upscaled by the largest integer factor
+    fn window_destination(&self) -> ViewDestination {
+        let (canvas_width, canvas_height) = match self.internal_resolution {
+            Some(resolution) => resolution,
+            None => (self.window_width, self.window_height),
+        };
+        let scale = (self.window_width / canvas_width.max(1)).min(self.window_height / canvas_height.max(1)).max(1);
+        ViewDestination {
+            color : 0,
+            canvas_width : canvas_width,
+            canvas_height : canvas_height,
+            scale : scale,
+            offset_x : (self.window_width - canvas_width * scale) / 2,
+            offset_y : (self.window_height - canvas_height * scale) / 2,
+            pixel_snap : self.internal_resolution.is_some(),
+            // one pixel on each side to scroll into when smoothing
+            border : if self.internal_resolution.is_some() && self.subpixel_smoothing { 1 } else { 0 },
+        }
+    }
+    fn composite_view(&mut self, camera : &Camera, targets : &ViewTargets, destination : &ViewDestination, region : (i32, i32, i32, i32), subpixel_offset : Vector2) {
+        let scale = destination.scale;
+        let border = destination.border;
+        let region_x = destination.offset_x + region.0 * scale;
+        let region_y = destination.offset_y + region.1 * scale;
+        // shift the whole image including its border by the part of the camera movement that was snapped away
+        let offset_x = if border > 0 { (-subpixel_offset.x * scale as f32).round() as i32 } else { 0 };
+        let offset_y = if border > 0 { (-subpixel_offset.y * scale as f32).round() as i32 } else { 0 };
+        let render_target = [destination.color];
+        let pass_targets : &[u32] = if destination.color == 0 { &[] } else { &render_target };
+        if !self.begin_pass(pass_targets, &[], false) {
+            return;
+        }
+        self.backend.draw_composite(targets.output,
+            (region_x - border * scale + offset_x, region_y - border * scale + offset_y, targets.width * scale, targets.height * scale),
+            (region_x, region_y, region.2 * scale, region.3 * scale),
+            !camera.clear);
+    }
+    fn generate_gbuffer(&mut self, registry : &Registry, camera : &Camera, targets : &ViewTargets) {
+        // cameras that don't clear leave a transparent background to draw over the views below
+        let clear_color : [f32; 4] = if camera.clear {
+            [camera.clear_color.r, camera.clear_color.g, camera.clear_color.b, camera.clear_color.a]
+        } else {
+            [0.0, 0.0, 0.0, 0.0]
+        };
+        let clear_normal : [f32; 4] = [0.5, 0.5, 1.0, 0.0];
+        // the background is unlit so the clear color shows as is
+        let clear_material : [f32; 4] = [0.0, 0.0, 0.0, 0.0];
+        let clear_height : [f32; 4] = [0.0, 0.0, 0.0, 0.0];
+        let gbuffer = &targets.gbuffer;
+        if !self.begin_pass(&[gbuffer.color, gbuffer.normal, gbuffer.material, gbuffer.height], &[clear_color, clear_normal, clear_material, clear_height], true) {
+            return;
+        }
 
         let sprite_map = registry.get_map::<Sprite>();
         for sprite_kv in sprite_map.all_iter() {
+            if !sprite_kv.value.has_flag(SPRITE_VISIBLE) || !layer_in_mask(camera.layer_mask, sprite_kv.value.layer) {
+                continue;
+            }
+            // a camera never samples the texture it is rendering into
+            if camera.render_target == Some(sprite_kv.value.material_id) || !self.material_preps.contains_key(sprite_kv.value.material_id) {
+                continue;
+            }
             let material_object = &sprite_kv.value.material_id;
             let material_info = self.material_preps.get_mut(*material_object);
             material_info.batch.push(sprite_kv.value.clone());
         }
-        
-        unsafe {
-            gl::UniformMatrix3x2fv(self.geometry_shader_camera_transform, 1, gl::FALSE, &camera_transform.elements[0]);
-            gl::Uniform2fv(self.geometry_shader_camera_view, 1, &camera_view.x);
-        }
-
-        for material_prep in self.material_preps.all_iter_mut() {
-            let material_info = &mut material_prep.value;
-            let color : u32 = material_info.material.color;
-            let material : u32 = material_info.material.material;
-            let normal : u32 = material_info.material.normal;
-
-            let count : i32 = material_info.batch.len() as i32;
-            let size : isize = (std::mem::size_of::<Sprite>() * material_info.batch.len()) as isize;
-            unsafe {
-                gl::NamedBufferSubData(
-                    self.instance_buffer_object,
-                    0,
-                    size,
-                    &material_info.batch[0].transform.elements[0] as *const f32 as *const c_void);
-
-                // bind texture
-                gl::ActiveTexture(gl::TEXTURE0);
-                gl::BindTexture(gl::TEXTURE_2D, color);
-                gl::ActiveTexture(gl::TEXTURE1);
-                gl::BindTexture(gl::TEXTURE_2D, normal);
-                gl::ActiveTexture(gl::TEXTURE2);
-                gl::BindTexture(gl::TEXTURE_2D, material);
-
-                gl::Uniform2fv(self.geometry_shader_image_size, 1, &material_info.material.size.x);
-                
-                // render
-                gl::BindVertexArray(self.vertex_array_object);
-                gl::DrawArraysInstanced(gl::TRIANGLE_STRIP, 0, 4, count);
-                gl::BindVertexArray(0);
+        self.batch_texts(registry, TextSpace::World, camera.layer_mask, SPRITE_VISIBLE);
+        self.batch_nine_slices(registry, camera);
+        self.batch_particles(registry, ParticleBlend::Lit, camera);
+
+        // every material can have its own shader
+        self.draw_batches(SpritePass::Geometry);
+        self.draw_gpu_particles(registry, ParticleBlend::Lit, camera);
+        for tilemap_kv in self.tilemap_caches.all_iter() {
+            let tilemap_cache = &tilemap_kv.value;
+            if !layer_in_mask(camera.layer_mask, tilemap_cache.layer) || camera.render_target == Some(tilemap_cache.material_id) {
+                continue;
+            }
+            let material = backend_material(self.material_preps.get(tilemap_cache.material_id));
+            for chunk in tilemap_cache.chunks.iter().filter(|chunk| chunk.count > 0) {
+                self.backend.draw_sprites(SpritePass::Geometry, &material, chunk.instance_buffer, chunk.count);
+            }
+        }
+    }
+    fn batch_texts(&mut self, registry : &Registry, space : TextSpace, layer_mask : u32, flags : u32) {
+        let text_map = registry.get_map::<Text>();
+        let mut glyphs : Vec<Sprite> = Vec::new();
+        for text_kv in text_map.all_iter() {
+            let text = &text_kv.value;
+            if text.space != space || !layer_in_mask(layer_mask, text.layer) || !self.fonts.contains_key(text.font_id) {
+                continue;
+            }
+            let font_info = self.fonts.get(text.font_id);
+            if !self.material_preps.contains_key(font_info.material.id) {
+                continue;
+            }
+            glyphs.clear();
+            font_info.layout(text, flags, &mut glyphs);
+            let material_info = self.material_preps.get_mut(font_info.material.id);
+            material_info.batch.extend(glyphs.drain(..));
+        }
+    }
+    fn batch_nine_slices(&mut self, registry : &Registry, camera : &Camera) {
+        let nine_slice_map = registry.get_map::<NineSlice>();
+        for nine_slice_kv in nine_slice_map.all_iter() {
+            let nine_slice = &nine_slice_kv.value;
+            if nine_slice.flags & SPRITE_VISIBLE == 0 || !layer_in_mask(camera.layer_mask, nine_slice.layer) {
+                continue;
+            }
+            if camera.render_target == Some(nine_slice.material_id) || !self.material_preps.contains_key(nine_slice.material_id) {
+                continue;
             }
-            material_info.batch.clear();
+            let material_info = self.material_preps.get_mut(nine_slice.material_id);
+            nine_slice.build_instances(material_info.material.size, &mut material_info.batch);
+        }
+    }
+    // the live particles of the emitters with the given blend into the material batches
+    fn batch_particles(&mut self, registry : &Registry, blend : ParticleBlend, camera : &Camera) {
+        let emitter_map = registry.get_map::<ParticleEmitter>();
+        for emitter_kv in emitter_map.all_iter() {
+            let emitter = &emitter_kv.value;
+            if emitter.blend != blend || !layer_in_mask(camera.layer_mask, emitter.layer) {
+                continue;
+            }
+            if camera.render_target == Some(emitter.material_id) || !self.material_preps.contains_key(emitter.material_id) {
+                continue;
+            }
+            emitter.build_instances(&mut self.material_preps.get_mut(emitter.material_id).batch);
+        }
+    }
+    fn render_additive_particles(&mut self, registry : &Registry, camera : &Camera, targets : &ViewTargets) {
+        if !self.begin_pass(&[targets.light], &[], false) {
+            return;
+        }
+        self.batch_particles(registry, ParticleBlend::Additive, camera);
+        self.draw_batches(SpritePass::Additive);
+        self.draw_gpu_particles(registry, ParticleBlend::Additive, camera);
+    }
+    // the gpu simulated emitters with the given blend, straight from the instances the simulation wrote
+    fn draw_gpu_particles(&mut self, registry : &Registry, blend : ParticleBlend, camera : &Camera) {
+        let pass = match blend {
+            ParticleBlend::Lit => SpritePass::Geometry,
+            ParticleBlend::Additive => SpritePass::Additive,
+        };
+        let emitter_map = registry.get_map::<ParticleEmitter>();
+        for emitter_kv in emitter_map.all_iter() {
+            let entity = emitter_kv.get_key();
+            let emitter = &emitter_kv.value;
+            if emitter.gpu_particles().is_none() || emitter.blend != blend || !layer_in_mask(camera.layer_mask, emitter.layer) {
+                continue;
+            }
+            if camera.render_target == Some(emitter.material_id) || !self.material_preps.contains_key(emitter.material_id) || !self.particle_caches.contains_key(entity) {
+                continue;
+            }
+            let particle_cache = self.particle_caches.get(entity);
+            let instance_buffer = self.backend.particle_instances(particle_cache.system);
+            let material = backend_material(self.material_preps.get(emitter.material_id));
+            self.backend.draw_sprites(pass, &material, instance_buffer, particle_cache.capacity);
+        }
+    }
+    // draws and empties every material batch into the current pass
+    fn draw_batches(&mut self, pass : SpritePass) {
+        let material_ids : Vec<u32> = self.material_preps.all_iter().map(|material_prep| material_prep.get_key()).collect();
+        for material_id in material_ids {
+            self.draw_batch(pass, material_id);
         }
-        unsafe {
-            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+    }
+    fn draw_batch(&mut self, pass : SpritePass, material_id : u32) {
+        let material_prep = self.material_preps.get_mut(material_id);
+        if material_prep.batch.is_empty() {
+            return;
+        }
+        let material = backend_material(material_prep);
+        // the instance buffer only fits so many at once
+        for instances in material_prep.batch.chunks(MAX_GEO_INSTANCE_COUNT) {
+            self.backend.write_buffer(self.instance_buffer, 0, sprite_bytes(instances));
+            self.backend.draw_sprites(pass, &material, self.instance_buffer, instances.len());
         }
+        material_prep.batch.clear();
     }
-    fn render_gbuffer(&self, registry : &Registry, camera : &Camera, ambient_color : &Color) {
-        unsafe {
-            gl::ActiveTexture(gl::TEXTURE0);
-            gl::BindTexture(gl::TEXTURE_2D, self.gbuffer.color);
-            gl::ActiveTexture(gl::TEXTURE1);
-            gl::BindTexture(gl::TEXTURE_2D, self.gbuffer.normal);
-            gl::ActiveTexture(gl::TEXTURE2);
-            gl::BindTexture(gl::TEXTURE_2D, self.gbuffer.material);
+    // screen space texts and debug drawing, unlit and blended over the composited window
+    fn render_overlay(&mut self, registry : &Registry, debug_draw : &DebugDraw, ambient_color : &Color, time : f32) {
+        if !self.begin_pass(&[], &[], false) {
+            return;
         }
-        self.render_ambience(camera, ambient_color);
-        self.render_lights(registry, camera);
+        let destination = self.window_destination();
+        let canvas_view = Vector2 { x : destination.canvas_width as f32 / 2f32, y : destination.canvas_height as f32 / 2f32 };
+        self.backend.set_viewport(destination.offset_x, destination.offset_y,
+            destination.canvas_width * destination.scale, destination.canvas_height * destination.scale);
+        // the origin is the bottom left of the canvas
+        self.backend.set_frame(&BackendFrame {
+            camera_transform : Matrix3x2::new_translation(Vector2 { x : -canvas_view.x, y : -canvas_view.y }),
+            camera_transform_inverse : Matrix3x2::new_translation(canvas_view),
+            camera_view : canvas_view,
+            resolution : (destination.canvas_width, destination.canvas_height),
+            ambient : *ambient_color,
+            time : time,
+        });
+        self.batch_texts(registry, TextSpace::Screen, u32::MAX, SPRITE_VISIBLE);
+        self.draw_batches(SpritePass::Overlay);
+        self.render_debug(debug_draw, DebugSpace::Screen);
     }
-    fn render_ambience(&self, camera : &Camera, ambient_color : &Color) {
-        unsafe {
-            let camera_view = Vector2 { x : camera.view_size.x / 2.0f32, y : camera.view_size.y / 2.0f32 };
-            let camera_transform = camera.transform;
+    // all the lines in one draw, then the texts, into the current pass with the current frame
+    fn render_debug(&mut self, debug_draw : &DebugDraw, space : DebugSpace) {
+        self.debug_vertices.clear();
+        for line in debug_draw.lines.iter().filter(|line| line.space == space) {
+            let color = [line.color.r, line.color.g, line.color.b, line.color.a];
+            self.debug_vertices.extend_from_slice(&[line.begin.x, line.begin.y]);
+            self.debug_vertices.extend_from_slice(&color);
+            self.debug_vertices.extend_from_slice(&[line.end.x, line.end.y]);
+            self.debug_vertices.extend_from_slice(&color);
+        }
+        if !self.debug_vertices.is_empty() {
+            self.backend.draw_lines(&self.debug_vertices);
+        }
 
-            gl::Disable(gl::BLEND);
+        if let Some(font_id) = debug_draw.font_id {
+            if self.fonts.contains_key(font_id) {
+                let font_info = self.fonts.get(font_id);
+                let mut glyphs : Vec<Sprite> = Vec::new();
+                for debug_text in debug_draw.texts.iter().filter(|text| text.space == space) {
+                    let text = Text {
+                        text : debug_text.text.clone(),
+                        transform : Matrix3x2::new_translation(debug_text.position),
+                        color : debug_text.color,
+                        scale : 1f32,
+                        align : TextAlign::Left,
+                        wrap_width : 0f32,
+                        z : 0f32,
+                        layer : 0,
+                        space : TextSpace::Screen,
+                        font_id : font_id,
+                    };
+                    font_info.layout(&text, SPRITE_VISIBLE, &mut glyphs);
+                }
+                if !glyphs.is_empty() && self.material_preps.contains_key(font_info.material.id) {
+                    self.material_preps.get_mut(font_info.material.id).batch.extend(glyphs.drain(..));
+                    self.draw_batches(SpritePass::Overlay);
+                }
+            }
+        }
+    }
+    // keeps a cache per tilemap and rebuilds the chunks that changed since the last frame
+    fn update_tilemaps(&mut self, registry : &Registry) {
+        for tilemap_cache in self.tilemap_caches.all_iter_mut() {
+            tilemap_cache.value.seen = false;
+        }
+        let tilemap_map = registry.get_map::<Tilemap>();
+        for tilemap_kv in tilemap_map.all_iter() {
+            let entity = tilemap_kv.get_key();
+            let tilemap = &tilemap_kv.value;
+            if !self.material_preps.contains_key(tilemap.material_id) {
+                continue;
+            }
+            let chunk_count = (tilemap.chunks_x() * tilemap.chunks_y()) as usize;
+            if !self.tilemap_caches.contains_key(entity) {
+                self.tilemap_caches.insert(entity, TilemapCache {
+                    chunks : Vec::new(),
+                    appearance : [0f32; 13],
+                    layer : 0,
+                    material_id : 0,
+                    seen : false,
+                });
+            }
+            let material_size = self.material_preps.get(tilemap.material_id).material.size;
+            let tilemap_cache = self.tilemap_caches.get_mut(entity);
+            tilemap_cache.seen = true;
+            tilemap_cache.layer = tilemap.layer;
+
+            let appearance = tilemap_appearance(tilemap);
+            let rebuild_all = tilemap_cache.appearance != appearance || tilemap_cache.material_id != tilemap.material_id || tilemap_cache.chunks.len() != chunk_count;
+            if tilemap_cache.chunks.len() != chunk_count {
+                tilemap_cache.drop(&mut *self.backend);
+                for _ in 0..chunk_count {
+                    tilemap_cache.chunks.push(TilemapChunk {
+                        instance_buffer : self.backend.create_buffer(std::mem::size_of::<Sprite>() * (TILEMAP_CHUNK_SIZE * TILEMAP_CHUNK_SIZE) as usize),
+                        revision : 0,
+                        count : 0,
+                    });
+                }
+            }
+            tilemap_cache.appearance = appearance;
+            tilemap_cache.material_id = tilemap.material_id;
+
+            let tile_uv = Vector2 { x : tilemap.tile_size.x / material_size.x, y : tilemap.tile_size.y / material_size.y };
+            let columns = ((material_size.x / tilemap.tile_size.x) as u32).max(1);
+            let mut instances : Vec<Sprite> = Vec::with_capacity((TILEMAP_CHUNK_SIZE * TILEMAP_CHUNK_SIZE) as usize);
+            for chunk_y in 0..tilemap.chunks_y() {
+                for chunk_x in 0..tilemap.chunks_x() {
+                    let chunk_index = tilemap.chunk_index(chunk_x, chunk_y);
+                    let chunk = &mut tilemap_cache.chunks[chunk_index];
+                    if !rebuild_all && chunk.revision == tilemap.chunk_revision(chunk_index) {
+                        continue;
+                    }
+                    chunk.revision = tilemap.chunk_revision(chunk_index);
+
+                    instances.clear();
+                    let end_x = ((chunk_x + 1) * TILEMAP_CHUNK_SIZE).min(tilemap.get_width());
+                    let end_y = ((chunk_y + 1) * TILEMAP_CHUNK_SIZE).min(tilemap.get_height());
+                    for y in chunk_y * TILEMAP_CHUNK_SIZE..end_y {
+                        for x in chunk_x * TILEMAP_CHUNK_SIZE..end_x {
+                            let tile = match tilemap.get_tile(x, y) {
+                                Some(tile) if tile != EMPTY_TILE => tile,
+                                _ => continue,
+                            };
+                            let uv_begin = Vector2 { x : (tile % columns) as f32 * tile_uv.x, y : (tile / columns) as f32 * tile_uv.y };
+                            let position = tilemap.transform.transform_point(Vector2 { x : x as f32 * tilemap.tile_size.x, y : y as f32 * tilemap.tile_size.y });
+                            let mut transform = tilemap.transform;
+                            transform.elements[4] = position.x;
+                            transform.elements[5] = position.y;
+                            instances.push(Sprite {
+                                transform : transform,
+                                pivot : Vector2::new(),
+                                color : tilemap.color,
+                                uv_rect : Rect { begin : uv_begin, end : Vector2 { x : uv_begin.x + tile_uv.x, y : uv_begin.y + tile_uv.y } },
+                                z : tilemap.z,
+                                height : tilemap.height,
+                                height_map_scale : tilemap.height_map_scale,
+                                material_id : tilemap.material_id,
+                                layer : tilemap.layer,
+                                flags : SPRITE_VISIBLE,
+                            });
+                        }
+                    }
+                    chunk.count = instances.len();
+                    if chunk.count > 0 {
+                        self.backend.write_buffer(chunk.instance_buffer, 0, sprite_bytes(&instances));
+                    }
+                }
+            }
+        }
+        // drop the caches of tilemaps that are gone
+        let stale : Vec<u32> = self.tilemap_caches.all_iter().filter(|cache_kv| !cache_kv.value.seen).map(|cache_kv| cache_kv.get_key()).collect();
+        for entity in stale {
+            self.tilemap_caches.get_mut(entity).drop(&mut *self.backend);
+            self.tilemap_caches.remove(entity);
+        }
+    }
+    // steps every gpu simulated emitter once per update, writing this update's spawns in first
+    fn simulate_particles(&mut self, registry : &Registry) {
+        for particle_cache in self.particle_caches.all_iter_mut() {
+            particle_cache.value.seen = false;
+        }
+        let emitter_map = registry.get_map::<ParticleEmitter>();
+        for emitter_kv in emitter_map.all_iter() {
+            let entity = emitter_kv.get_key();
+            let emitter = &emitter_kv.value;
+            let gpu = match emitter.gpu_particles() {
+                Some(gpu) if self.backend.supports_particle_simulation() => gpu,
+                _ => continue,
+            };
+            if self.particle_caches.contains_key(entity) {
+                let particle_cache = self.particle_caches.get_mut(entity);
+                particle_cache.seen = true;
+                if particle_cache.capacity == gpu.capacity() && particle_cache.generation == gpu.generation {
+                    if particle_cache.step == gpu.step {
+                        continue;
+                    }
+                } else {
+                    self.backend.delete_particle_system(particle_cache.system);
+                    self.particle_caches.remove(entity);
+                }
+            }
+            if !self.particle_caches.contains_key(entity) {
+                // a new cache starts from the step before, so this update's spawns go in
+                self.particle_caches.insert(entity, ParticleCache {
+                    system : self.backend.create_particle_system(gpu.capacity()),
+                    capacity : gpu.capacity(),
+                    generation : gpu.generation,
+                    step : gpu.step.wrapping_sub(1),
+                    seen : true,
+                });
+            }
 
-            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
-            self.ambience_shader.use_program();
-            
-            gl::UniformMatrix3x2fv(self.ambience_shader_camera_transform_inverse, 1, gl::FALSE, &camera_transform.elements[0]);
-            gl::Uniform2fv(self.ambience_shader_camera_view, 1, &camera_view.x);
+            let (colors, sizes) = emitter.sample_curves();
+            let particle_cache = self.particle_caches.get_mut(entity);
+            self.backend.simulate_particles(particle_cache.system, &gpu.spawns, &ParticleSimulation {
+                delta_time : gpu.delta_time,
+                gravity : emitter.gravity,
+                uv_rect : emitter.uv_rect,
+                z : emitter.z,
+                height : emitter.height,
+                material_id : emitter.material_id,
+                layer : emitter.layer,
+                colors : colors,
+                sizes : sizes,
+            });
+            particle_cache.step = gpu.step;
+        }
+        // drop the caches of emitters that are gone or back on the cpu
+        let stale : Vec<u32> = self.particle_caches.all_iter().filter(|cache_kv| !cache_kv.value.seen).map(|cache_kv| cache_kv.get_key()).collect();
+        for entity in stale {
+            self.backend.delete_particle_system(self.particle_caches.get(entity).system);
+            self.particle_caches.remove(entity);
+        }
+    }
+    fn generate_occlusion(&mut self, registry : &Registry, camera : &Camera, targets : &ViewTargets) -> bool {
+        let caster_map = registry.get_map::<ShadowCaster>();
+        if caster_map.all_iter().next().is_none() {
+            return false;
+        }
+        if !self.begin_pass(&[targets.shadow_buffer.occlusion], &[[0f32; 4]], false) {
+            return false;
+        }
 
-            gl::Uniform4fv(self.ambience_shader_world_ambience, 1, &ambient_color.r);
+        let sprite_map = registry.get_map::<Sprite>();
+        self.occluder_vertices.clear();
+        for caster_kv in caster_map.all_iter() {
+            let entity = caster_kv.get_key();
+            if sprite_map.contains_key(entity) && !sprite_map.get(entity).has_flag(SPRITE_CASTS_SHADOW) {
+                continue;
+            }
+            match &caster_kv.value.shape {
+                ShadowShape::Polygon(points) => {
+                    let transform = if sprite_map.contains_key(entity) {
+                        sprite_map.get(entity).transform
+                    } else {
+                        Matrix3x2::new()
+                    };
+                    for triangle in triangulate(points) {
+                        for index in triangle.iter() {
+                            let point = transform.transform_point(points[*index]);
+                            self.occluder_vertices.extend_from_slice(&[point.x, point.y]);
+                        }
+                    }
+                },
+                ShadowShape::SpriteAlpha => {
+                    if sprite_map.contains_key(entity) {
+                        let sprite = sprite_map.get(entity);
+                        if camera.render_target == Some(sprite.material_id) || !self.material_preps.contains_key(sprite.material_id) {
+                            continue;
+                        }
+                        self.material_preps.get_mut(sprite.material_id).batch.push(sprite.clone());
+                    }
+                },
+            }
+        }
 
-            gl::BindVertexArray(self.vertex_array_object);
-            gl::DrawArrays(gl::TRIANGLES, 0, 3);
-            gl::BindVertexArray(0);
+        self.draw_batches(SpritePass::Occluder);
+        if !self.occluder_vertices.is_empty() {
+            self.backend.draw_polygons(&self.occluder_vertices);
         }
+        true
     }
-    fn render_lights(&self, registry : &Registry, camera : &Camera) {
-        unsafe {
-            gl::Enable(gl::BLEND);
-            gl::BlendFunc(gl::ONE, gl::ONE);
-            gl::Disable(gl::DEPTH_TEST);
-        }
-        
-        let camera_view = Vector2 { x : camera.view_size.x / 2.0f32, y : camera.view_size.y / 2.0f32 };
-        let camera_transform = camera.transform;
-        
-        self.point_light_shader.use_program();
-        
-        unsafe {
-            gl::UniformMatrix3x2fv(self.point_light_shader_camera_transform_inverse, 1, gl::FALSE, &camera_transform.elements[0]);
-            gl::Uniform2fv(self.point_light_shader_camera_view, 1, &camera_view.x);
+    fn generate_shadow_maps(&mut self, registry : &Registry, targets : &ViewTargets) {
+        if !self.begin_pass(&[targets.shadow_buffer.shadow_map], &[], false) {
+            return;
         }
-        
         let light_map = registry.get_map::<PointLight>();
-        for light_kv in light_map.all_iter() {
-            unsafe {
-                gl::Uniform4fv(self.point_light_shader_light_color, 1, &light_kv.value.color.r);
-                gl::Uniform4fv(self.point_light_shader_light_position, 1, &light_kv.value.position.x);
-                
-                gl::BindVertexArray(self.vertex_array_object);
-                gl::DrawArrays(gl::TRIANGLES, 0, 3);
-                gl::BindVertexArray(0);
+        for (light_index, light_kv) in light_map.all_iter().enumerate().take(MAX_SHADOW_CASTING_LIGHTS as usize) {
+            self.backend.set_viewport(0, light_index as i32, SHADOW_MAP_RESOLUTION, 1);
+            self.backend.draw_shadow_map(targets.shadow_buffer.occlusion, &light_kv.value, SHADOW_MAP_RESOLUTION as f32);
+        }
+    }
+    // the ambient lit scene and then every light added on top, each a fullscreen draw
+    fn render_gbuffer(&mut self, registry : &Registry, has_shadows : bool, targets : &ViewTargets) {
+        if !self.begin_pass(&[targets.light], &[[0f32; 4]], false) {
+            return;
+        }
+        let gbuffer = targets.backend_gbuffer();
+        self.backend.draw_ambience(&gbuffer);
+
+        let point_light_map = registry.get_map::<PointLight>();
+        for (light_index, light_kv) in point_light_map.all_iter().enumerate() {
+            let light = &light_kv.value;
+            let light_shadow : [f32; 4] = if has_shadows && (light_index as i32) < MAX_SHADOW_CASTING_LIGHTS {
+                [
+                    (light_index as f32 + 0.5f32) / MAX_SHADOW_CASTING_LIGHTS as f32,
+                    light.height / light.range * SHADOW_SOFTNESS_SCALE,
+                    SHADOW_MAP_RESOLUTION as f32,
+                    0f32,
+                ]
+            } else {
+                [-1f32, 0f32, SHADOW_MAP_RESOLUTION as f32, 0f32]
+            };
+            self.backend.draw_point_light(&gbuffer, light, light_shadow);
+        }
+        let spot_light_map = registry.get_map::<SpotLight>();
+        for light_kv in spot_light_map.all_iter() {
+            self.backend.draw_spot_light(&gbuffer, &light_kv.value);
+        }
+        let directional_light_map = registry.get_map::<DirectionalLight>();
+        for light_kv in directional_light_map.all_iter() {
+            self.backend.draw_directional_light(&gbuffer, &light_kv.value);
+        }
+    }
+    fn render_bloom(&mut self, bloom : &Bloom, targets : &ViewTargets) {
+        let mips = &targets.bloom_mips;
+        // extract the bright parts into the first mip
+        if !self.begin_pass(&[mips[0].color], &[], false) {
+            return;
+        }
+        self.backend.draw_bloom_prefilter(targets.light, bloom.threshold);
+
+        // blur down the chain
+        for i in 1..mips.len() {
+            if !self.begin_pass(&[mips[i].color], &[], false) {
+                return;
             }
+            self.backend.draw_bloom_downsample(mips[i - 1].color);
         }
+
+        // and add each level back up into the one above it
+        for i in (1..mips.len()).rev() {
+            if !self.begin_pass(&[mips[i - 1].color], &[], false) {
+                return;
+            }
+            self.backend.draw_bloom_upsample(mips[i].color, bloom.radius);
+        }
+    }
+    fn render_tone_mapping(&mut self, tone_mapping : &ToneMapping, bloom : &Bloom, targets : &ViewTargets, target : u32) {
+        if !self.begin_pass(&[target], &[], false) {
+            return;
+        }
+        let bloom_intensity = if bloom.enabled { bloom.intensity } else { 0f32 };
+        self.backend.draw_tone_mapping(targets.light, targets.bloom_mips[0].color, tone_mapping, bloom_intensity);
+    }
+}
+
+fn new_target(backend : &mut dyn RenderBackend, width : i32, height : i32, format : TextureFormat, filter : TextureFilter, wrap : TextureWrap) -> u32 {
+    backend.create_texture(&TextureDesc {
+        width : width,
+        height : height,
+        format : format,
+        filter : filter,
+        wrap : wrap,
+    }, None)
+}
+fn backend_material(material_prep : &MaterialPrepInfo) -> BackendMaterial<'_> {
+    BackendMaterial {
+        color : material_prep.material.color,
+        normal : material_prep.material.normal,
+        material : material_prep.material.material,
+        size : material_prep.material.size,
+        shader : material_prep.material.shader_id,
+        params : &material_prep.params,
     }
 }
+// x, y, width, height of the camera in canvas pixels
+fn viewport_region(camera : &Camera, destination : &ViewDestination) -> (i32, i32, i32, i32) {
+    let canvas_width = destination.canvas_width as f32;
+    let canvas_height = destination.canvas_height as f32;
+    let begin_x = (camera.viewport.begin.x * canvas_width).round() as i32;
+    let begin_y = (camera.viewport.begin.y * canvas_height).round() as i32;
+    let end_x = (camera.viewport.end.x * canvas_width).round() as i32;
+    let end_y = (camera.viewport.end.y * canvas_height).round() as i32;
+    (begin_x, begin_y, end_x - begin_x, end_y - begin_y)
+}
+fn snap_camera(camera : &Camera, region : (i32, i32, i32, i32), border : i32) -> (Camera, Vector2) {
+    let pixel_size = Vector2 {
+        x : camera.view_size.x / region.2 as f32,
+        y : camera.view_size.y / region.3 as f32,
+    };
+    let mut snapped_camera = camera.clone();
+    let position = camera.transform.get_position();
+    let snapped = Vector2 {
+        x : (position.x / pixel_size.x).round() * pixel_size.x,
+        y : (position.y / pixel_size.y).round() * pixel_size.y,
+    };
+    snapped_camera.transform.elements[4] = snapped.x;
+    snapped_camera.transform.elements[5] = snapped.y;
+    // keep one world pixel per render pixel when the border is added
+    snapped_camera.view_size = Vector2 {
+        x : pixel_size.x * (region.2 + border * 2) as f32,
+        y : pixel_size.y * (region.3 + border * 2) as f32,
+    };
+    let subpixel_offset = Vector2 {
+        x : (position.x - snapped.x) / pixel_size.x,
+        y : (position.y - snapped.y) / pixel_size.y,
+    };
+    (snapped_camera, subpixel_offset)
+}
+fn tilemap_appearance(tilemap : &Tilemap) -> [f32; 13] {
+    let t = &tilemap.transform.elements;
+    [
+        t[0], t[1], t[2], t[3], t[4], t[5],
+        tilemap.color.r, tilemap.color.g, tilemap.color.b, tilemap.color.a,
+        tilemap.z, tilemap.height, tilemap.height_map_scale,
+    ]
+}
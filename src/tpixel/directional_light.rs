@@ -0,0 +1,18 @@
+use crate::tpixel::color::Color;
+use crate::tpixel::vector3::Vector3;
+
+pub struct DirectionalLight {
+    pub color : Color,
+    pub intensity : f32,
+    pub direction : Vector3, // direction the light travels in, z points out of the sprite plane towards the viewer
+}
+
+impl DirectionalLight {
+    pub(crate) fn clone(&self) -> DirectionalLight {
+        DirectionalLight {
+            color : self.color,
+            intensity : self.intensity,
+            direction : self.direction,
+        }
+    }
+}
@@ -4,35 +4,69 @@ use crate::tpixel::sprite_factory::SpriteFactory;
 use crate::tpixel::texture_factory::TextureFactory;
 use crate::tpixel::material_factory::MaterialFactory;
 use crate::tpixel::shader_factory::ShaderFactory;
+use crate::tpixel::font_factory::FontFactory;
+use crate::tpixel::font::FontInfo;
 use crate::tpixel::camera::Camera;
 use crate::tpixel::color::Color;
+use crate::tpixel::vector2::Vector2;
+use crate::tpixel::tone_mapping::ToneMapping;
+use crate::tpixel::bloom::Bloom;
+use crate::tpixel::uniform_value::UniformValue;
+use crate::tpixel::shader_error::ShaderError;
+use crate::tpixel::resource_error::ResourceError;
+use crate::tpixel::shader_quality::ShaderQuality;
 use crate::tpixel::input_manager::InputManager;
+use crate::tpixel::debug_draw::{DebugDraw, DebugDrawer};
+use crate::tpixel::gl_backend::GlBackend;
+use crate::tpixel::render_backend::RenderBackend;
 
 // registry inits
 use crate::tpixel::sprite::Sprite;
 use crate::tpixel::point_light::PointLight;
+use crate::tpixel::directional_light::DirectionalLight;
+use crate::tpixel::spot_light::SpotLight;
+use crate::tpixel::shadow_caster::ShadowCaster;
+use crate::tpixel::tilemap::Tilemap;
+use crate::tpixel::text::Text;
+use crate::tpixel::nine_slice::NineSlice;
+use crate::tpixel::particle_emitter::ParticleEmitter;
 
 use glfw::{Action, Key};
 use std::time::{Instant};
 
+const SHADER_POLL_INTERVAL : f32 = 0.5; // seconds between checks of the shader directory
+
 pub struct Engine {
     pub registry : Registry,
     pub camera : Camera,
     pub ambient_color : Color,
+    pub tone_mapping : ToneMapping,
+    pub bloom : Bloom,
     
     delta_time : f32,
+    elapsed_time : f32,
+    window_size : Vector2,
     last_frame_instance : Instant,
     input_manager : InputManager,
+    debug_draw : DebugDraw,
+    shader_poll_timer : f32,
+    shader_errors : Vec<ShaderError>,
+    render_errors : Vec<ResourceError>,
 
     renderer : Renderer,
     sprite_factory : SpriteFactory,
     texture_factory : TextureFactory,
     material_factory : MaterialFactory,
     shader_factory : ShaderFactory,
+    font_factory : FontFactory,
 }
 
 impl Engine {
     pub fn new() -> Engine {
+        Engine::new_with_backend(Box::new(GlBackend::new()))
+    }
+    // a CpuBackend renders without a gl context, read_window gives what it drew
+    pub fn new_with_backend(backend : Box<dyn RenderBackend>) -> Engine {
         Engine {
             registry : Registry::new(),
             camera : Camera::new(),
@@ -42,31 +76,73 @@ impl Engine {
                 b : 0f32,
                 a : 0f32,
             },
+            tone_mapping : ToneMapping::new(),
+            bloom : Bloom::new(),
             
             delta_time : 0f32,
+            elapsed_time : 0f32,
+            window_size : Vector2 { x : 1280f32, y : 720f32 },
             last_frame_instance : Instant::now(),
             input_manager : InputManager::new(),
+            debug_draw : DebugDraw::new(),
+            shader_poll_timer : 0f32,
+            shader_errors : Vec::new(),
+            render_errors : Vec::new(),
             
-            renderer : Renderer::new(),
+            renderer : Renderer::new(backend),
             sprite_factory : SpriteFactory::new(),
             texture_factory : TextureFactory::new(),
             material_factory : MaterialFactory::new(),
             shader_factory : ShaderFactory::new(),
+            font_factory : FontFactory::new(),
         }
     }
     pub fn init(&mut self) {
         self.registry.init_map::<Sprite>();
         self.registry.init_map::<PointLight>();
+        self.registry.init_map::<DirectionalLight>();
+        self.registry.init_map::<SpotLight>();
+        self.registry.init_map::<ShadowCaster>();
+        self.registry.init_map::<Camera>();
+        self.registry.init_map::<Tilemap>();
+        self.registry.init_map::<Text>();
+        self.registry.init_map::<NineSlice>();
+        self.registry.init_map::<ParticleEmitter>();
 
-        self.renderer.init(&self.shader_factory);
+        self.shader_errors = self.renderer.init(&mut self.shader_factory);
     }
     pub fn start_frame(&mut self) {
         let new_now = Instant::now();
         self.delta_time = new_now.duration_since(self.last_frame_instance).as_secs_f32();
         self.last_frame_instance = new_now;
+        self.elapsed_time += self.delta_time;
+        self.update_particles();
+        self.poll_shader_changes();
     }
     pub fn render(&mut self) {
-        self.renderer.render(&self.registry, &self.camera, &self.ambient_color);
+        self.render_errors = self.renderer.render(&self.registry, &self.camera, &self.ambient_color, &self.tone_mapping, &self.bloom, self.elapsed_time, &self.debug_draw);
+        self.debug_draw.advance(self.delta_time);
+    }
+    fn poll_shader_changes(&mut self) {
+        if !self.shader_factory.has_shader_directory() {
+            return;
+        }
+        self.shader_poll_timer += self.delta_time;
+        if self.shader_poll_timer < SHADER_POLL_INTERVAL {
+            return;
+        }
+        self.shader_poll_timer = 0f32;
+        let changed = self.shader_factory.poll_changes();
+        if !changed.is_empty() {
+            self.shader_errors = self.renderer.reload_shaders(&mut self.shader_factory, &changed);
+        }
+    }
+    fn update_particles(&mut self) {
+        let delta_time = self.delta_time;
+        let gpu_available = self.renderer.supports_gpu_particles();
+        for emitter_kv in self.registry.get_map_mut::<ParticleEmitter>().all_iter_mut() {
+            emitter_kv.value.update(delta_time, gpu_available);
+        }
     }
     pub fn process_event(&mut self, window : &mut glfw::Window, event : &glfw::WindowEvent) {
         match event {
@@ -74,10 +150,10 @@ impl Engine {
                 // make sure the viewport matches the new window dimensions; note that width and
                 // height will be significantly larger than specified on retina displays.
                 self.renderer.resize_framebuffer(*width, *height);
-                self.camera.view_size.x = (*width as f32) / 3.0;
-                self.camera.view_size.y = (*height as f32) / 3.0;
-                unsafe {
-                    gl::Viewport(0, 0, *width, *height);
+                self.window_size = Vector2 { x : *width as f32, y : *height as f32 };
+                if self.renderer.get_internal_resolution().is_none() {
+                    self.camera.view_size.x = (*width as f32) / 3.0;
+                    self.camera.view_size.y = (*height as f32) / 3.0;
                 }
             }
             glfw::WindowEvent::Key(Key::Escape, _, Action::Press, _) => {
@@ -89,6 +165,22 @@ impl Engine {
             _ => {}
         }
     }
+    // renders everything at a fixed resolution and upscales it to the window by the largest integer factor,
+    // subpixel smoothing keeps the camera movement smooth while the world stays on the pixel grid
+    pub fn set_internal_resolution(&mut self, width : i32, height : i32, subpixel_smoothing : bool) -> Result<(), ResourceError> {
+        if width <= 0 || height <= 0 {
+            return Err(ResourceError::new("internal resolution", &format!("{}x{} is not a valid size", width, height)));
+        }
+        self.camera.view_size.x = width as f32;
+        self.camera.view_size.y = height as f32;
+        self.renderer.set_internal_resolution(Some((width, height)), subpixel_smoothing);
+        Ok(())
+    }
+    pub fn clear_internal_resolution(&mut self) {
+        self.camera.view_size.x = self.window_size.x / 3.0;
+        self.camera.view_size.y = self.window_size.y / 3.0;
+        self.renderer.set_internal_resolution(None, false);
+    }
     pub fn update_input(&mut self, window : &glfw::Window) {
         self.input_manager.update_input(window);
     }
@@ -96,15 +188,138 @@ impl Engine {
     pub fn new_sprite(&mut self, material_id : u32) -> Sprite {
         self.sprite_factory.new_sprite(material_id)
     }
+    // set its borders before drawing, without them it is a stretched sprite
+    pub fn new_nine_slice(&mut self, material_id : u32, width : f32, height : f32) -> NineSlice {
+        self.sprite_factory.new_nine_slice(material_id, width, height)
+    }
+    // the material image holds r = specular, g = gloss, b = emissive and a = lit, transparent pixels are
+    // drawn at full color regardless of lights, so an image without an alpha channel is fully lit
     pub fn new_material(&mut self, color_path : &str, material_path : &str, normal_path : &str) -> u32 {
-        let material_data = self.material_factory.new_material(&mut self.texture_factory, color_path, material_path, normal_path);
+        let material_data = self.material_factory.new_material(self.renderer.backend_mut(), &mut self.texture_factory, color_path, material_path, normal_path);
         self.renderer.prepare_material(&material_data);
         material_data.id
     }
+    // geometry pass shader for materials, surface_source defines Surface surface(vec2 uv) returning
+    // vec4 color, vec2 normal (tangent space), float height_map and vec4 material for the gbuffer,
+    // Surface sample_surface(vec2 uv) reads the material's textures at uv as the default shader does,
+    // it can also use COLOR, WORLD_POSITION, image_size, the frame block (frame.time, frame.camera_view, ...) and the material's params
+    pub fn new_material_shader(&mut self, surface_source : &str) -> Result<u32, ShaderError> {
+        self.renderer.add_material_shader(&self.shader_factory, surface_source)
+    }
+    // None goes back to the default shader
+    pub fn set_material_shader(&mut self, material_id : u32, shader_id : Option<u32>) {
+        self.renderer.set_material_shader(material_id, shader_id);
+    }
+    pub fn set_material_param(&mut self, material_id : u32, param_name : &str, value : UniformValue) {
+        self.renderer.set_material_param(material_id, param_name, value);
+    }
+    pub fn load_bmfont(&mut self, font_path : &str) -> Result<u32, ResourceError> {
+        let font_info = self.font_factory.load_bmfont(self.renderer.backend_mut(), &mut self.texture_factory, &mut self.material_factory, font_path)?;
+        Ok(self.prepare_font(font_info))
+    }
+    pub fn load_ttf(&mut self, font_path : &str, size_px : f32) -> Result<u32, ResourceError> {
+        let font_info = self.font_factory.load_ttf(self.renderer.backend_mut(), &mut self.texture_factory, &mut self.material_factory, font_path, size_px)?;
+        Ok(self.prepare_font(font_info))
+    }
+    pub fn new_text(&self, font_id : u32, text : &str) -> Text {
+        self.font_factory.new_text(font_id, text)
+    }
+    // lines, shapes and texts drawn unlit on top of the lit scene, world space and one frame unless told otherwise
+    pub fn debug_draw(&mut self) -> DebugDrawer<'_> {
+        self.debug_draw.drawer()
+    }
+    pub fn set_debug_font(&mut self, font_id : u32) {
+        self.debug_draw.set_font(font_id);
+    }
+    fn prepare_font(&mut self, font_info : FontInfo) -> u32 {
+        let font_id = font_info.id;
+        self.renderer.prepare_material(&font_info.material);
+        self.renderer.prepare_font(font_info);
+        font_id
+    }
+    // material a camera can render into by setting its render_target, sprites using it show what the camera sees,
+    // the camera skips sprites with its own material so the texture is never read while it is being written
+    // a name already in use with another size is an error
+    pub fn new_render_target(&mut self, name : &str, width : i32, height : i32) -> Result<u32, ResourceError> {
+        let material_data = self.material_factory.new_render_material(self.renderer.backend_mut(), &mut self.texture_factory, name, width, height)?;
+        self.renderer.prepare_material(&material_data);
+        self.renderer.prepare_render_target(&material_data);
+        Ok(material_data.id)
+    }
+    // sprites still using the material are skipped from then on
+    pub fn free_render_target(&mut self, material_id : u32) {
+        if let Some(material_data) = self.renderer.free_render_target(material_id) {
+            let backend = self.renderer.backend_mut();
+            self.texture_factory.release_texture(backend, material_data.color);
+            self.texture_factory.release_texture(backend, material_data.material);
+            self.texture_factory.release_texture(backend, material_data.normal);
+        }
+    }
+    // fullscreen pass run in order after lighting and tone mapping, the fragment shader gets
+    // in vec2 UV, writes out vec4 out_color and can sample
+    // binding 0 image_source (previous effect), 1 image_scene (lit hdr scene),
+    // 2 image_color, 3 image_normal (world space), 4 image_material, 5 image_height (the gbuffer)
+    // along with uniform float time, uniform vec2 resolution and the given params,
+    // #include "tpixel_frame.glsl" gives the frame block with the camera and ambient as frame.camera_transform and so on
+    // files in the directory named after a built in shader, like point_light.frag or fullscreen.vert, replace it,
+    // the shaders made from a file are rebuilt when it changes and a shader that fails keeps running the old one
+    pub fn set_shader_directory(&mut self, directory : &str) {
+        self.shader_factory.set_shader_directory(Some(directory));
+        self.shader_errors = self.renderer.build_shaders(&mut self.shader_factory);
+    }
+    pub fn clear_shader_directory(&mut self) {
+        self.shader_factory.set_shader_directory(None);
+        self.shader_errors = self.renderer.build_shaders(&mut self.shader_factory);
+    }
+    // lighting shaders without shadows or specular highlights, a quality used before switches back without rebuilding
+    pub fn set_shader_quality(&mut self, shader_quality : ShaderQuality) {
+        self.shader_errors = self.renderer.set_shader_quality(&mut self.shader_factory, shader_quality);
+    }
+    // what failed to build in the last shader build, reload or quality switch, empty when everything built
+    pub fn get_shader_errors(&self) -> &[ShaderError] {
+        &self.shader_errors
+    }
+    // #include "name" in any shader pulls this in, files in the shader directory with the name win
+    pub fn add_shader_include(&mut self, name : &str, source : &str) {
+        self.shader_factory.add_include(name, source);
+    }
+    pub fn add_post_effect(&mut self, name : &str, fragment_source : &str, params : &[(&str, UniformValue)]) -> Result<(), ShaderError> {
+        self.renderer.add_post_effect(&self.shader_factory, name, fragment_source, params)
+    }
+    pub fn remove_post_effect(&mut self, name : &str) {
+        self.renderer.remove_post_effect(name);
+    }
+    pub fn set_post_effect_enabled(&mut self, name : &str, enabled : bool) {
+        self.renderer.set_post_effect_enabled(name, enabled);
+    }
+    pub fn is_post_effect_enabled(&self, name : &str) -> bool {
+        self.renderer.is_post_effect_enabled(name)
+    }
+    pub fn set_post_effect_param(&mut self, name : &str, param_name : &str, value : UniformValue) {
+        self.renderer.set_post_effect_param(name, param_name, value);
+    }
+    // the render targets that couldn't be drawn into in the last frame, their passes were skipped
+    pub fn get_render_errors(&self) -> &[ResourceError] {
+        &self.render_errors
+    }
+    // what the last frame left in the window, rows starting at the bottom, before the buffers are swapped
+    pub fn read_window(&mut self) -> Vec<[f32; 4]> {
+        self.renderer.read_window()
+    }
 
     pub fn get_dt(&self) -> f32 {
         return self.delta_time;
     }
+    pub fn get_time(&self) -> f32 {
+        self.elapsed_time
+    }
+    // gl state changes the renderer made in the last frame, and the redundant ones it skipped
+    pub fn get_issued_gl_calls(&self) -> u32 {
+        self.renderer.get_issued_gl_calls()
+    }
+    pub fn get_skipped_gl_calls(&self) -> u32 {
+        self.renderer.get_skipped_gl_calls()
+    }
     pub fn is_key_pressed(&self, key : Key) -> bool {
         self.input_manager.is_key_pressed(key)
     }
@@ -0,0 +1,135 @@
+use crate::tpixel::vector2::Vector2;
+use crate::tpixel::rect::Rect;
+use crate::tpixel::sprite::{Sprite, SPRITE_FLIP_Y, SPRITE_ALPHA_CUTOUT};
+use crate::tpixel::material_info::MaterialInfo;
+use crate::tpixel::text::{Text, TextAlign};
+
+use std::collections::HashMap;
+
+pub(crate) struct Glyph {
+    pub uv_rect : Rect, // top to bottom like the atlas image, drawn with SPRITE_FLIP_Y
+    pub offset : Vector2, // bottom left of the quad from the pen position on the baseline
+    pub size : Vector2,
+    pub advance : f32,
+}
+
+pub(crate) struct FontInfo {
+    pub id : u32,
+    pub material : MaterialInfo, // the glyph atlas
+    pub line_height : f32,
+    pub glyphs : HashMap<char, Glyph>,
+    pub kernings : HashMap<(char, char), f32>,
+    // truetype fonts look their kerning up in the font itself
+    pub ttf : Option<(fontdue::Font, f32)>,
+}
+
+impl FontInfo {
+    fn kerning(&self, left : char, right : char) -> f32 {
+        match &self.ttf {
+            Some((font, size_px)) => font.horizontal_kern(left, right, *size_px).unwrap_or(0f32),
+            None => *self.kernings.get(&(left, right)).unwrap_or(&0f32),
+        }
+    }
+    fn glyph(&self, character : char) -> Option<&Glyph> {
+        self.glyphs.get(&character).or_else(|| self.glyphs.get(&'?'))
+    }
+    fn measure(&self, line : &[char]) -> f32 {
+        let mut width = 0f32;
+        let mut previous : Option<char> = None;
+        for &character in line {
+            width += self.advance(previous, character);
+            previous = Some(character);
+        }
+        width
+    }
+    // how far the pen moves for character, with the kerning against the one before it
+    fn advance(&self, previous : Option<char>, character : char) -> f32 {
+        let kerning = match previous {
+            Some(previous) => self.kerning(previous, character),
+            None => 0f32,
+        };
+        match self.glyph(character) {
+            Some(glyph) => kerning + glyph.advance,
+            None => kerning,
+        }
+    }
+    // breaks the text at newlines and, when wrapping, at the last space that still fits
+    fn break_lines(&self, text : &Text) -> Vec<Vec<char>> {
+        let mut lines : Vec<Vec<char>> = Vec::new();
+        for paragraph in text.text.split('\n') {
+            let mut line : Vec<char> = Vec::new();
+            let mut width = 0f32;
+            let mut last_space : Option<usize> = None;
+            let mut rest_width = 0f32; // of what follows the last space, the next line if the break happens there
+            for character in paragraph.chars() {
+                width += self.advance(line.last().copied(), character);
+                if character == ' ' {
+                    last_space = Some(line.len());
+                    rest_width = 0f32;
+                } else {
+                    let previous = line.last().copied().filter(|&c| c != ' ');
+                    rest_width += self.advance(previous, character);
+                }
+                line.push(character);
+                if text.wrap_width <= 0f32 || character == ' ' || width <= text.wrap_width {
+                    continue;
+                }
+                if let Some(space) = last_space.take() {
+                    let rest = line.split_off(space + 1);
+                    line.pop();
+                    lines.push(line);
+                    line = rest;
+                    width = rest_width;
+                }
+            }
+            lines.push(line);
+        }
+        lines
+    }
+    // one sprite per visible glyph, ready to be batched with the atlas material
+    pub fn layout(&self, text : &Text, flags : u32, sprites : &mut Vec<Sprite>) {
+        let mut transform = text.transform;
+        for i in 0..4 {
+            transform.elements[i] *= text.scale;
+        }
+        let align = match text.align {
+            TextAlign::Left => 0f32,
+            TextAlign::Center => 0.5f32,
+            TextAlign::Right => 1f32,
+        };
+        for (line_index, line) in self.break_lines(text).iter().enumerate() {
+            let baseline = -(line_index as f32) * self.line_height;
+            let mut pen = -self.measure(line) * align;
+            let mut previous : Option<char> = None;
+            for &character in line {
+                if let Some(previous) = previous {
+                    pen += self.kerning(previous, character);
+                }
+                previous = Some(character);
+                let glyph = match self.glyph(character) {
+                    Some(glyph) => glyph,
+                    None => continue,
+                };
+                if glyph.size.x > 0f32 && glyph.size.y > 0f32 {
+                    let position = transform.transform_point(Vector2 { x : pen + glyph.offset.x, y : baseline + glyph.offset.y });
+                    let mut glyph_transform = transform;
+                    glyph_transform.elements[4] = position.x;
+                    glyph_transform.elements[5] = position.y;
+                    sprites.push(Sprite {
+                        transform : glyph_transform,
+                        pivot : Vector2::new(),
+                        color : text.color,
+                        uv_rect : glyph.uv_rect,
+                        z : text.z,
+                        height : 0f32,
+                        height_map_scale : 0f32,
+                        material_id : self.material.id,
+                        layer : text.layer,
+                        flags : flags | SPRITE_FLIP_Y | SPRITE_ALPHA_CUTOUT,
+                    });
+                }
+                pen += glyph.advance;
+            }
+        }
+    }
+}
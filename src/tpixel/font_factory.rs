@@ -0,0 +1,230 @@
+use crate::tpixel::vector2::Vector2;
+use crate::tpixel::color::Color;
+use crate::tpixel::rect::Rect;
+use crate::tpixel::matrix3x2::Matrix3x2;
+use crate::tpixel::font::{FontInfo, Glyph};
+use crate::tpixel::text::{Text, TextAlign, TextSpace};
+use crate::tpixel::texture_factory::TextureFactory;
+use crate::tpixel::material_factory::MaterialFactory;
+use crate::tpixel::material_info::MaterialInfo;
+use crate::tpixel::resource_error::ResourceError;
+use crate::tpixel::render_backend::RenderBackend;
+
+use std::path::Path;
+use std::collections::HashMap;
+
+const TTF_ATLAS_WIDTH : usize = 512;
+const TTF_GLYPH_PADDING : usize = 1;
+
+pub struct FontFactory {
+    next_id : u32,
+}
+
+impl FontFactory {
+    pub fn new() -> FontFactory {
+        FontFactory {
+            next_id : 0u32,
+        }
+    }
+    pub fn new_text(&self, font_id : u32, text : &str) -> Text {
+        Text {
+            text : text.to_string(),
+            transform : Matrix3x2::new(),
+            color : Color { r : 1f32, g : 1f32, b : 1f32, a : 1f32 },
+            scale : 1f32,
+            align : TextAlign::Left,
+            wrap_width : 0f32,
+            z : 0f32,
+            layer : 0,
+            space : TextSpace::World,
+            font_id : font_id,
+        }
+    }
+    // text format .fnt from BMFont and compatible tools, only the first page is used
+    pub(crate) fn load_bmfont(&mut self, backend : &mut dyn RenderBackend, texture_factory : &mut TextureFactory, material_factory : &mut MaterialFactory, font_path : &str) -> Result<FontInfo, ResourceError> {
+        let source = std::fs::read_to_string(font_path).map_err(|error| ResourceError::new(font_path, &error.to_string()))?;
+        let mut line_height = 0f32;
+        let mut base = 0f32;
+        let mut page_file = String::new();
+        let mut chars : Vec<HashMap<String, String>> = Vec::new();
+        let mut kernings : HashMap<(char, char), f32> = HashMap::new();
+        for line in source.lines() {
+            let (tag, values) = parse_bmfont_line(line);
+            let number = |key : &str| -> f32 {
+                values.get(key).and_then(|value| value.parse::<f32>().ok()).unwrap_or(0f32)
+            };
+            match tag.as_str() {
+                "common" => {
+                    line_height = number("lineHeight");
+                    base = number("base");
+                },
+                "page" => {
+                    if number("id") == 0f32 {
+                        page_file = values.get("file").cloned().unwrap_or_default();
+                    }
+                },
+                "char" => chars.push(values),
+                "kerning" => {
+                    let first = std::char::from_u32(number("first") as u32);
+                    let second = std::char::from_u32(number("second") as u32);
+                    if let (Some(first), Some(second)) = (first, second) {
+                        kernings.insert((first, second), number("amount"));
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        if page_file.is_empty() {
+            return Err(ResourceError::new(font_path, "font has no page 0"));
+        }
+        let page_path = Path::new(font_path).parent().unwrap_or(Path::new("")).join(&page_file);
+        let page_path = match page_path.to_str() {
+            Some(page_path) => page_path.to_string(),
+            _ => return Err(ResourceError::new(font_path, "font page path is not valid utf8")),
+        };
+        if !Path::new(&page_path).is_file() {
+            return Err(ResourceError::new(&page_path, "font page image not found"));
+        }
+        // decoded here so a broken image is an error instead of a panic in the texture factory
+        let page = image::open(&page_path).map_err(|error| ResourceError::new(&page_path, &error.to_string()))?.into_rgba8();
+        let texture = texture_factory.new_pixel_texture(backend, &page_path, page.width() as i32, page.height() as i32, page.as_raw())?;
+        let (texture_id, texture_size) = (texture.id, texture.size);
+
+        let mut glyphs : HashMap<char, Glyph> = HashMap::new();
+        for values in chars.iter() {
+            let number = |key : &str| -> f32 {
+                values.get(key).and_then(|value| value.parse::<f32>().ok()).unwrap_or(0f32)
+            };
+            let character = match std::char::from_u32(number("id") as u32) {
+                Some(character) => character,
+                None => continue,
+            };
+            if number("page") != 0f32 {
+                continue;
+            }
+            let size = Vector2 { x : number("width"), y : number("height") };
+            let begin = Vector2 { x : number("x") / texture_size.x, y : number("y") / texture_size.y };
+            glyphs.insert(character, Glyph {
+                uv_rect : Rect { begin : begin, end : Vector2 { x : begin.x + size.x / texture_size.x, y : begin.y + size.y / texture_size.y } },
+                // bmfont measures from the top of the line, y goes down
+                offset : Vector2 { x : number("xoffset"), y : base - number("yoffset") - size.y },
+                size : size,
+                advance : number("xadvance"),
+            });
+        }
+
+        let material = material_factory.new_color_material(backend, texture_factory, texture_id, texture_size);
+        Ok(self.new_font(material, line_height, glyphs, kernings, None))
+    }
+    // rasterizes latin-1 into an atlas at the given pixel size
+    pub(crate) fn load_ttf(&mut self, backend : &mut dyn RenderBackend, texture_factory : &mut TextureFactory, material_factory : &mut MaterialFactory, font_path : &str, size_px : f32) -> Result<FontInfo, ResourceError> {
+        let bytes = std::fs::read(font_path).map_err(|error| ResourceError::new(font_path, &error.to_string()))?;
+        let settings = fontdue::FontSettings { scale : size_px, ..fontdue::FontSettings::default() };
+        let font = fontdue::Font::from_bytes(bytes, settings).map_err(|error| ResourceError::new(font_path, error))?;
+
+        let characters : Vec<char> = (32u32..127u32).chain(160u32..256u32).filter_map(std::char::from_u32).collect();
+        let rasterized : Vec<(char, fontdue::Metrics, Vec<u8>)> = characters.iter()
+            .map(|&character| {
+                let (metrics, coverage) = font.rasterize(character, size_px);
+                (character, metrics, coverage)
+            })
+            .collect();
+
+        // shelf packing, rows of glyphs left to right
+        let mut placements : Vec<(usize, usize)> = Vec::with_capacity(rasterized.len());
+        let mut cursor_x = 0usize;
+        let mut cursor_y = 0usize;
+        let mut shelf_height = 0usize;
+        for (character, metrics, _) in rasterized.iter() {
+            if metrics.width + TTF_GLYPH_PADDING > TTF_ATLAS_WIDTH {
+                return Err(ResourceError::new(font_path, &format!("glyph '{}' is wider than the {} pixel atlas at size {}", character, TTF_ATLAS_WIDTH, size_px)));
+            }
+            if cursor_x + metrics.width + TTF_GLYPH_PADDING > TTF_ATLAS_WIDTH {
+                cursor_x = 0;
+                cursor_y += shelf_height + TTF_GLYPH_PADDING;
+                shelf_height = 0;
+            }
+            placements.push((cursor_x, cursor_y));
+            cursor_x += metrics.width + TTF_GLYPH_PADDING;
+            shelf_height = shelf_height.max(metrics.height);
+        }
+        let atlas_height = (cursor_y + shelf_height).max(1).next_power_of_two();
+
+        let mut pixels = vec![0u8; TTF_ATLAS_WIDTH * atlas_height * 4];
+        let mut glyphs : HashMap<char, Glyph> = HashMap::new();
+        let atlas_size = Vector2 { x : TTF_ATLAS_WIDTH as f32, y : atlas_height as f32 };
+        for ((character, metrics, coverage), (x, y)) in rasterized.iter().zip(placements.iter()) {
+            for row in 0..metrics.height {
+                for column in 0..metrics.width {
+                    let pixel = ((y + row) * TTF_ATLAS_WIDTH + x + column) * 4;
+                    pixels[pixel] = 255;
+                    pixels[pixel + 1] = 255;
+                    pixels[pixel + 2] = 255;
+                    pixels[pixel + 3] = coverage[row * metrics.width + column];
+                }
+            }
+            let size = Vector2 { x : metrics.width as f32, y : metrics.height as f32 };
+            let begin = Vector2 { x : *x as f32 / atlas_size.x, y : *y as f32 / atlas_size.y };
+            glyphs.insert(*character, Glyph {
+                uv_rect : Rect { begin : begin, end : Vector2 { x : begin.x + size.x / atlas_size.x, y : begin.y + size.y / atlas_size.y } },
+                offset : Vector2 { x : metrics.xmin as f32, y : metrics.ymin as f32 },
+                size : size,
+                advance : metrics.advance_width,
+            });
+        }
+
+        let line_height = match font.horizontal_line_metrics(size_px) {
+            Some(line_metrics) => line_metrics.new_line_size,
+            None => size_px,
+        };
+        let texture_name = format!("{}@{}", font_path, size_px);
+        let texture = texture_factory.new_pixel_texture(backend, &texture_name, TTF_ATLAS_WIDTH as i32, atlas_height as i32, &pixels)?;
+        let (texture_id, texture_size) = (texture.id, texture.size);
+        let material = material_factory.new_color_material(backend, texture_factory, texture_id, texture_size);
+        Ok(self.new_font(material, line_height, glyphs, HashMap::new(), Some((font, size_px))))
+    }
+    fn new_font(&mut self, material : MaterialInfo, line_height : f32, glyphs : HashMap<char, Glyph>, kernings : HashMap<(char, char), f32>, ttf : Option<(fontdue::Font, f32)>) -> FontInfo {
+        let font_info = FontInfo {
+            id : self.next_id,
+            material : material,
+            line_height : line_height,
+            glyphs : glyphs,
+            kernings : kernings,
+            ttf : ttf,
+        };
+        self.next_id += 1u32;
+        font_info
+    }
+}
+
+// "tag key=value key="quoted value"" into the tag and its values
+fn parse_bmfont_line(line : &str) -> (String, HashMap<String, String>) {
+    let mut values : HashMap<String, String> = HashMap::new();
+    let line = line.trim();
+    let (tag, mut rest) = match line.find(' ') {
+        Some(index) => (&line[..index], &line[index..]),
+        None => (line, ""),
+    };
+    loop {
+        rest = rest.trim_start();
+        let equals = match rest.find('=') {
+            Some(equals) => equals,
+            None => break,
+        };
+        let key = rest[..equals].trim().to_string();
+        rest = &rest[equals + 1..];
+        let value;
+        if rest.starts_with('"') {
+            let end = rest[1..].find('"').map(|end| end + 1).unwrap_or(rest.len());
+            value = rest[1..end].to_string();
+            rest = if end < rest.len() { &rest[end + 1..] } else { "" };
+        } else {
+            let end = rest.find(' ').unwrap_or(rest.len());
+            value = rest[..end].to_string();
+            rest = &rest[end..];
+        }
+        values.insert(key, value);
+    }
+    (tag.to_string(), values)
+}
@@ -0,0 +1,54 @@
+use crate::tpixel::matrix3x2::Matrix3x2;
+use crate::tpixel::vector2::Vector2;
+use crate::tpixel::color::Color;
+
+use std::os::raw::c_void;
+
+// uniform block binding every shader reads the frame from through #include "tpixel_frame.glsl"
+pub(crate) const FRAME_DATA_BINDING : u32 = 0;
+
+// matches the std140 layout of the FrameData block, each mat3x2 column is padded out to a vec4
+#[repr(C)]
+pub(crate) struct FrameData {
+    camera_transform : [[f32; 4]; 3], // world to view
+    camera_transform_inverse : [[f32; 4]; 3], // view to world
+    camera_view : [f32; 2], // half the view size
+    resolution : [f32; 2], // of the target in pixels
+    ambient : [f32; 4],
+    time : f32,
+    padding : [f32; 3],
+}
+
+impl FrameData {
+    // both ways are passed in since Matrix3x2::inverse doesn't handle scale
+    pub fn new(camera_transform : &Matrix3x2, camera_transform_inverse : &Matrix3x2, camera_view : Vector2, resolution : (i32, i32), ambient : &Color, time : f32) -> FrameData {
+        FrameData {
+            camera_transform : std140_columns(camera_transform),
+            camera_transform_inverse : std140_columns(camera_transform_inverse),
+            camera_view : [camera_view.x, camera_view.y],
+            resolution : [resolution.0 as f32, resolution.1 as f32],
+            ambient : [ambient.r, ambient.g, ambient.b, ambient.a],
+            time : time,
+            padding : [0f32; 3],
+        }
+    }
+    // the whole block goes up at once, the buffer stays bound to FRAME_DATA_BINDING
+    pub fn upload(&self, uniform_buffer_object : u32) {
+        unsafe {
+            gl::NamedBufferSubData(
+                uniform_buffer_object,
+                0,
+                std::mem::size_of::<FrameData>() as isize,
+                self as *const FrameData as *const c_void);
+        }
+    }
+}
+
+fn std140_columns(matrix : &Matrix3x2) -> [[f32; 4]; 3] {
+    let e = &matrix.elements;
+    [
+        [e[0], e[1], 0f32, 0f32],
+        [e[2], e[3], 0f32, 0f32],
+        [e[4], e[5], 0f32, 0f32],
+    ]
+}
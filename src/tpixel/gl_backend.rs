@@ -0,0 +1,1248 @@
+use crate::tpixel::render_backend::{RenderBackend, TextureDesc, TextureFormat, TextureFilter, TextureWrap, BackendFrame, BackendMaterial,
+    BackendGBuffer, SpritePass, ParticleSimulation};
+use crate::tpixel::gl_shaders::*;
+use crate::tpixel::frame_data::{FrameData, FRAME_DATA_BINDING};
+use crate::tpixel::gl_state::GlState;
+use crate::tpixel::shader_factory::ShaderFactory;
+use crate::tpixel::shader_program::ShaderProgram;
+use crate::tpixel::shader_error::ShaderError;
+use crate::tpixel::shader_quality::ShaderQuality;
+use crate::tpixel::resource_error::ResourceError;
+use crate::tpixel::material_shader::{MaterialShader, MaterialParam, query_param_defaults};
+use crate::tpixel::uniform_value::UniformValue;
+use crate::tpixel::sparse_map::SparseMap;
+use crate::tpixel::sprite::Sprite;
+use crate::tpixel::point_light::PointLight;
+use crate::tpixel::spot_light::SpotLight;
+use crate::tpixel::directional_light::DirectionalLight;
+use crate::tpixel::tone_mapping::{ToneMapping, ToneMappingOperator};
+use crate::tpixel::particle_emitter::{ParticleSpawn, PARTICLE_CURVE_SAMPLES};
+
+use gl::types::*;
+use std::os::raw::c_void;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::ptr;
+
+struct GlTarget {
+    framebuffer : u32,
+    renderbuffer : u32, // depth, made by the first pass with the depth test
+}
+
+// buffers of a gpu simulated particle emitter, the state ping pongs between two buffers each step
+struct GlParticleSystem {
+    state_buffer_objects : [u32; 2],
+    state_vertex_array_objects : [u32; 2], // reading the state buffer of the same index
+    instance_buffer_object : u32, // a Sprite per slot, written by the simulation
+    source : usize, // the state buffer holding the latest step
+    capacity : usize,
+}
+
+struct GlPostEffect {
+    name : String,
+    shader : ShaderProgram,
+    shader_time : i32,
+    shader_resolution : i32,
+    fragment_source : String, // kept to build it again when the shaders are reloaded
+}
+
+// the deferred renderer's passes in opengl 4.5, made before the context and set up by init once it is current
+pub struct GlBackend {
+    geometry_shader : ShaderProgram,
+    ambience_shader : ShaderProgram,
+    point_light_shader : ShaderProgram,
+    directional_light_shader : ShaderProgram,
+    spot_light_shader : ShaderProgram,
+    occluder_polygon_shader : ShaderProgram,
+    occluder_sprite_shader : ShaderProgram,
+    shadow_map_shader : ShaderProgram,
+    tone_mapping_shader : ShaderProgram,
+    bloom_prefilter_shader : ShaderProgram,
+    bloom_downsample_shader : ShaderProgram,
+    bloom_upsample_shader : ShaderProgram,
+    composite_shader : ShaderProgram,
+    overlay_shader : ShaderProgram,
+    debug_shader : ShaderProgram,
+    particle_simulation_shader : ShaderProgram, // not linked when transform feedback can't be used, emitters stay on the cpu then
+
+    material_shaders : SparseMap<MaterialShader>,
+    next_material_shader_id : u32,
+    post_effects : SparseMap<GlPostEffect>,
+    next_post_effect_id : u32,
+    particle_systems : SparseMap<GlParticleSystem>,
+    next_particle_system_id : u32,
+    uniform_locations : HashMap<u32, HashMap<String, i32>>, // of material and post effect params by program, forgotten when programs are rebuilt
+
+    shader_quality : ShaderQuality,
+    lighting_defines : Vec<&'static str>, // what the lighting shaders in use were built with
+
+    gl_state : GlState,
+    issued_gl_calls : u32, // state changes the tracker let through in the last frame
+    skipped_gl_calls : u32, // and the ones it found redundant
+    window_width : i32,
+    window_height : i32,
+    texture_sizes : HashMap<u32, (i32, i32)>,
+    targets : HashMap<Vec<u32>, GlTarget>, // framebuffers for each set of textures passes were begun with
+    instance_vertex_arrays : HashMap<u32, u32>, // quad and instances for each buffer sprites were drawn from
+
+    geometry_shader_image_size : i32,
+    geometry_shader_param_defaults : Vec<MaterialParam>,
+
+    point_light_shader_light_color : i32,
+    point_light_shader_light_position : i32,
+    point_light_shader_light_shadow : i32,
+
+    directional_light_shader_light_color : i32,
+    directional_light_shader_light_direction : i32,
+
+    spot_light_shader_light_color : i32,
+    spot_light_shader_light_position : i32,
+    spot_light_shader_light_cone : i32,
+
+    occluder_sprite_shader_image_size : i32,
+
+    shadow_map_shader_light_position : i32,
+    shadow_map_shader_shadow_map_size : i32,
+
+    tone_mapping_shader_tone_mapping_operator : i32,
+    tone_mapping_shader_exposure : i32,
+    tone_mapping_shader_auto_exposure : i32,
+    tone_mapping_shader_hdr_max_level : i32,
+    tone_mapping_shader_bloom_intensity : i32,
+
+    bloom_prefilter_shader_bloom_threshold : i32,
+    bloom_downsample_shader_source_texel_size : i32,
+    bloom_upsample_shader_source_texel_size : i32,
+    bloom_upsample_shader_bloom_radius : i32,
+
+    overlay_shader_image_size : i32,
+
+    particle_simulation_shader_delta_time : i32,
+    particle_simulation_shader_gravity : i32,
+    particle_simulation_shader_uv_rect : i32,
+    particle_simulation_shader_z : i32,
+    particle_simulation_shader_height : i32,
+    particle_simulation_shader_material_id : i32,
+    particle_simulation_shader_layer : i32,
+    particle_simulation_shader_color_over_life : i32,
+    particle_simulation_shader_size_over_life : i32,
+
+    frame_uniform_buffer_object : u32, // FrameData for the view being drawn
+    vertex_buffer_object : u32, // the quad every sprite is drawn from
+    vertex_array_object : u32, // the fullscreen passes make their triangle from gl_VertexID, but core gl wants something bound
+    occluder_vertex_buffer_object : u32,
+    occluder_vertex_array_object : u32,
+    debug_vertex_buffer_object : u32,
+    debug_vertex_array_object : u32,
+}
+
+impl GlBackend {
+    pub fn new() -> GlBackend {
+        GlBackend {
+            geometry_shader : ShaderProgram::new(),
+            ambience_shader : ShaderProgram::new(),
+            point_light_shader : ShaderProgram::new(),
+            directional_light_shader : ShaderProgram::new(),
+            spot_light_shader : ShaderProgram::new(),
+            occluder_polygon_shader : ShaderProgram::new(),
+            occluder_sprite_shader : ShaderProgram::new(),
+            shadow_map_shader : ShaderProgram::new(),
+            tone_mapping_shader : ShaderProgram::new(),
+            bloom_prefilter_shader : ShaderProgram::new(),
+            bloom_downsample_shader : ShaderProgram::new(),
+            bloom_upsample_shader : ShaderProgram::new(),
+            composite_shader : ShaderProgram::new(),
+            overlay_shader : ShaderProgram::new(),
+            debug_shader : ShaderProgram::new(),
+            particle_simulation_shader : ShaderProgram::new(),
+
+            material_shaders : SparseMap::new(),
+            next_material_shader_id : 0,
+            post_effects : SparseMap::new(),
+            next_post_effect_id : 1,
+            particle_systems : SparseMap::new(),
+            next_particle_system_id : 1,
+            uniform_locations : HashMap::new(),
+
+            shader_quality : ShaderQuality::new(),
+            lighting_defines : Vec::new(),
+
+            gl_state : GlState::new(),
+            issued_gl_calls : 0,
+            skipped_gl_calls : 0,
+            window_width : 1280,
+            window_height : 720,
+            texture_sizes : HashMap::new(),
+            targets : HashMap::new(),
+            instance_vertex_arrays : HashMap::new(),
+
+            geometry_shader_image_size : 0,
+            geometry_shader_param_defaults : Vec::new(),
+
+            point_light_shader_light_color : 0,
+            point_light_shader_light_position : 0,
+            point_light_shader_light_shadow : 0,
+
+            directional_light_shader_light_color : 0,
+            directional_light_shader_light_direction : 0,
+
+            spot_light_shader_light_color : 0,
+            spot_light_shader_light_position : 0,
+            spot_light_shader_light_cone : 0,
+
+            occluder_sprite_shader_image_size : 0,
+
+            shadow_map_shader_light_position : 0,
+            shadow_map_shader_shadow_map_size : 0,
+
+            tone_mapping_shader_tone_mapping_operator : 0,
+            tone_mapping_shader_exposure : 0,
+            tone_mapping_shader_auto_exposure : 0,
+            tone_mapping_shader_hdr_max_level : 0,
+            tone_mapping_shader_bloom_intensity : 0,
+
+            bloom_prefilter_shader_bloom_threshold : 0,
+            bloom_downsample_shader_source_texel_size : 0,
+            bloom_upsample_shader_source_texel_size : 0,
+            bloom_upsample_shader_bloom_radius : 0,
+
+            overlay_shader_image_size : 0,
+
+            particle_simulation_shader_delta_time : 0,
+            particle_simulation_shader_gravity : 0,
+            particle_simulation_shader_uv_rect : 0,
+            particle_simulation_shader_z : 0,
+            particle_simulation_shader_height : 0,
+            particle_simulation_shader_material_id : 0,
+            particle_simulation_shader_layer : 0,
+            particle_simulation_shader_color_over_life : 0,
+            particle_simulation_shader_size_over_life : 0,
+
+            frame_uniform_buffer_object : 0,
+            vertex_buffer_object : 0,
+            vertex_array_object : 0,
+            occluder_vertex_buffer_object : 0,
+            occluder_vertex_array_object : 0,
+            debug_vertex_buffer_object : 0,
+            debug_vertex_array_object : 0,
+        }
+    }
+    fn query_uniform_locations(&mut self) {
+        // programs may have been deleted and their names handed out again
+        self.uniform_locations.clear();
+
+        self.geometry_shader_image_size = self.geometry_shader.get_uniform_location("image_size");
+        self.geometry_shader_param_defaults = query_param_defaults(&self.geometry_shader);
+
+        self.overlay_shader_image_size = self.overlay_shader.get_uniform_location("image_size");
+
+        self.particle_simulation_shader_delta_time = self.particle_simulation_shader.get_uniform_location("delta_time");
+        self.particle_simulation_shader_gravity = self.particle_simulation_shader.get_uniform_location("gravity");
+        self.particle_simulation_shader_uv_rect = self.particle_simulation_shader.get_uniform_location("uv_rect");
+        self.particle_simulation_shader_z = self.particle_simulation_shader.get_uniform_location("z");
+        self.particle_simulation_shader_height = self.particle_simulation_shader.get_uniform_location("height");
+        self.particle_simulation_shader_material_id = self.particle_simulation_shader.get_uniform_location("material_id");
+        self.particle_simulation_shader_layer = self.particle_simulation_shader.get_uniform_location("layer");
+        self.particle_simulation_shader_color_over_life = self.particle_simulation_shader.get_uniform_location("color_over_life");
+        self.particle_simulation_shader_size_over_life = self.particle_simulation_shader.get_uniform_location("size_over_life");
+
+        self.point_light_shader_light_color = self.point_light_shader.get_uniform_location("light_color");
+        self.point_light_shader_light_position = self.point_light_shader.get_uniform_location("light_position");
+        self.point_light_shader_light_shadow = self.point_light_shader.get_uniform_location("light_shadow");
+
+        self.directional_light_shader_light_color = self.directional_light_shader.get_uniform_location("light_color");
+        self.directional_light_shader_light_direction = self.directional_light_shader.get_uniform_location("light_direction");
+
+        self.spot_light_shader_light_color = self.spot_light_shader.get_uniform_location("light_color");
+        self.spot_light_shader_light_position = self.spot_light_shader.get_uniform_location("light_position");
+        self.spot_light_shader_light_cone = self.spot_light_shader.get_uniform_location("light_cone");
+
+        self.occluder_sprite_shader_image_size = self.occluder_sprite_shader.get_uniform_location("image_size");
+
+        self.shadow_map_shader_light_position = self.shadow_map_shader.get_uniform_location("light_position");
+        self.shadow_map_shader_shadow_map_size = self.shadow_map_shader.get_uniform_location("shadow_map_size");
+
+        self.tone_mapping_shader_tone_mapping_operator = self.tone_mapping_shader.get_uniform_location("tone_mapping_operator");
+        self.tone_mapping_shader_exposure = self.tone_mapping_shader.get_uniform_location("exposure");
+        self.tone_mapping_shader_auto_exposure = self.tone_mapping_shader.get_uniform_location("auto_exposure");
+        self.tone_mapping_shader_hdr_max_level = self.tone_mapping_shader.get_uniform_location("hdr_max_level");
+        self.tone_mapping_shader_bloom_intensity = self.tone_mapping_shader.get_uniform_location("bloom_intensity");
+
+        self.bloom_prefilter_shader_bloom_threshold = self.bloom_prefilter_shader.get_uniform_location("bloom_threshold");
+        self.bloom_downsample_shader_source_texel_size = self.bloom_downsample_shader.get_uniform_location("source_texel_size");
+        self.bloom_upsample_shader_source_texel_size = self.bloom_upsample_shader.get_uniform_location("source_texel_size");
+        self.bloom_upsample_shader_bloom_radius = self.bloom_upsample_shader.get_uniform_location("bloom_radius");
+    }
+    // looked up once per program and name
+    fn uniform_location(&mut self, program : u32, name : &str) -> i32 {
+        if let Some(location) = self.uniform_locations.get(&program).and_then(|locations| locations.get(name)) {
+            return *location;
+        }
+        let location = match CString::new(name) {
+            Ok(c_str) => unsafe { gl::GetUniformLocation(program, c_str.as_ptr()) },
+            Err(_) => -1,
+        };
+        self.uniform_locations.entry(program).or_insert_with(HashMap::new).insert(name.to_string(), location);
+        location
+    }
+    // the framebuffer drawing into the targets, an incomplete one is deleted again so every pass using it reports it
+    fn target(&mut self, targets : &[u32], depth_test : bool) -> Result<(u32, (i32, i32)), ResourceError> {
+        let size = targets.first().and_then(|target| self.texture_sizes.get(target)).copied().unwrap_or((0, 0));
+        let mut target = match self.targets.remove(targets) {
+            Some(target) if !depth_test || target.renderbuffer != 0 => {
+                let framebuffer = target.framebuffer;
+                self.targets.insert(targets.to_vec(), target);
+                return Ok((framebuffer, size));
+            },
+            Some(target) => target,
+            None => {
+                let mut target = GlTarget {
+                    framebuffer : 0,
+                    renderbuffer : 0,
+                };
+                unsafe {
+                    gl::CreateFramebuffers(1, &mut target.framebuffer);
+                    let mut attachments : Vec<GLenum> = Vec::new();
+                    for (index, texture) in targets.iter().enumerate() {
+                        let attachment = gl::COLOR_ATTACHMENT0 + index as GLenum;
+                        gl::NamedFramebufferTexture(target.framebuffer, attachment, *texture, 0);
+                        attachments.push(attachment);
+                    }
+                    gl::NamedFramebufferDrawBuffers(target.framebuffer, attachments.len() as i32, attachments.as_ptr());
+                }
+                target
+            },
+        };
+        unsafe {
+            if depth_test {
+                gl::CreateRenderbuffers(1, &mut target.renderbuffer);
+                gl::NamedRenderbufferStorage(target.renderbuffer, gl::DEPTH_COMPONENT, size.0, size.1);
+                gl::NamedFramebufferRenderbuffer(target.framebuffer, gl::DEPTH_ATTACHMENT, gl::RENDERBUFFER, target.renderbuffer);
+            }
+            let status = gl::CheckNamedFramebufferStatus(target.framebuffer, gl::FRAMEBUFFER);
+            if status != gl::FRAMEBUFFER_COMPLETE {
+                gl::DeleteFramebuffers(1, &target.framebuffer);
+                gl::DeleteRenderbuffers(1, &target.renderbuffer);
+                return Err(ResourceError::new("framebuffer", &format!("textures {:?} did not complete, status 0x{:x}", targets, status)));
+            }
+        }
+        let framebuffer = target.framebuffer;
+        self.targets.insert(targets.to_vec(), target);
+        Ok((framebuffer, size))
+    }
+    // the quad and the buffer's Sprites, made the first time sprites are drawn from it
+    fn instance_vertex_array(&mut self, instance_buffer : u32) -> u32 {
+        if let Some(vertex_array) = self.instance_vertex_arrays.get(&instance_buffer) {
+            return *vertex_array;
+        }
+        let mut vertex_array : u32 = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut vertex_array);
+        }
+        self.gl_state.bind_vertex_array(vertex_array);
+        bind_sprite_attributes(self.vertex_buffer_object, instance_buffer);
+        self.instance_vertex_arrays.insert(instance_buffer, vertex_array);
+        vertex_array
+    }
+    // uses the material's shader, or the default one, with the material's params and returns where the image size goes
+    fn bind_geometry_shader(&mut self, material : &BackendMaterial) -> i32 {
+        let (program, image_size_location, param_defaults) = match material.shader {
+            Some(shader_id) if self.material_shaders.contains_key(shader_id) => {
+                let material_shader = self.material_shaders.get(shader_id);
+                (material_shader.shader.get_id(), material_shader.shader_image_size, &material_shader.param_defaults)
+            },
+            _ => (self.geometry_shader.get_id(), self.geometry_shader_image_size, &self.geometry_shader_param_defaults),
+        };
+        self.gl_state.use_program(program);
+        // uniforms stay set on the program, so whatever the last material set goes back to the shader's own values first
+        for param in param_defaults.iter().filter(|param| !material.params.iter().any(|(name, _)| *name == param.name)) {
+            param.value.set_uniform(param.location);
+        }
+        for (name, value) in material.params.iter() {
+            let location = self.uniform_location(program, name);
+            value.set_uniform(location);
+        }
+        image_size_location
+    }
+    fn bind_gbuffer(&mut self, gbuffer : &BackendGBuffer) {
+        self.gl_state.bind_texture(0, gbuffer.color);
+        self.gl_state.bind_texture(1, gbuffer.normal);
+        self.gl_state.bind_texture(2, gbuffer.material);
+        self.gl_state.bind_texture(3, gbuffer.shadow_map);
+        self.gl_state.bind_texture(4, gbuffer.height);
+        // every light is a fullscreen triangle
+        self.gl_state.bind_vertex_array(self.vertex_array_object);
+        self.gl_state.set_depth_test(false);
+    }
+    fn texel_size(&self, texture : u32) -> [f32; 2] {
+        let (width, height) = self.texture_sizes.get(&texture).copied().unwrap_or((1, 1));
+        [1f32 / width.max(1) as f32, 1f32 / height.max(1) as f32]
+    }
+}
+
+impl RenderBackend for GlBackend {
+    fn create_texture(&mut self, desc : &TextureDesc, pixels : Option<&[u8]>) -> u32 {
+        let internal_format = match desc.format {
+            TextureFormat::Rgba8 => gl::RGBA8,
+            TextureFormat::Rgba16F => gl::RGBA16F,
+            TextureFormat::R8 => gl::R8,
+            TextureFormat::R32F => gl::R32F,
+        };
+        let (levels, min_filter, mag_filter) = match desc.filter {
+            TextureFilter::Nearest => (1, gl::NEAREST, gl::NEAREST),
+            TextureFilter::Linear => (1, gl::LINEAR, gl::LINEAR),
+            TextureFilter::LinearMipmaps => ((desc.width.max(desc.height).max(1) as f32).log2().floor() as i32 + 1, gl::LINEAR_MIPMAP_LINEAR, gl::LINEAR),
+        };
+        let (wrap_s, wrap_t) = match desc.wrap {
+            TextureWrap::Repeat => (gl::REPEAT, gl::REPEAT),
+            TextureWrap::Clamp => (gl::CLAMP_TO_EDGE, gl::CLAMP_TO_EDGE),
+            TextureWrap::RepeatX => (gl::REPEAT, gl::CLAMP_TO_EDGE),
+        };
+        let mut texture : u32 = 0;
+        unsafe {
+            gl::CreateTextures(gl::TEXTURE_2D, 1, &mut texture);
+            gl::TextureStorage2D(texture, levels, internal_format, desc.width, desc.height);
+            gl::TextureParameteri(texture, gl::TEXTURE_WRAP_S, wrap_s as i32);
+            gl::TextureParameteri(texture, gl::TEXTURE_WRAP_T, wrap_t as i32);
+            gl::TextureParameteri(texture, gl::TEXTURE_MIN_FILTER, min_filter as i32);
+            gl::TextureParameteri(texture, gl::TEXTURE_MAG_FILTER, mag_filter as i32);
+            match pixels {
+                Some(pixels) => {
+                    gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+                    gl::TextureSubImage2D(texture, 0, 0, 0, desc.width, desc.height, gl::RGBA, gl::UNSIGNED_BYTE, pixels.as_ptr() as *const c_void);
+                },
+                None => {
+                    let zero : [f32; 4] = [0f32; 4];
+                    gl::ClearTexImage(texture, 0, gl::RGBA, gl::FLOAT, &zero[0] as *const f32 as *const c_void);
+                },
+            }
+        }
+        self.texture_sizes.insert(texture, (desc.width, desc.height));
+        texture
+    }
+    fn read_texture(&mut self, texture : u32) -> Vec<[f32; 4]> {
+        let (width, height) = match self.texture_sizes.get(&texture) {
+            Some(size) => *size,
+            None => return Vec::new(),
+        };
+        let mut texels : Vec<[f32; 4]> = vec![[0f32; 4]; (width * height) as usize];
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 4);
+            gl::GetTextureImage(texture, 0, gl::RGBA, gl::FLOAT,
+                (std::mem::size_of::<[f32; 4]>() * texels.len()) as i32,
+                texels.as_mut_ptr() as *mut c_void);
+        }
+        texels
+    }
+    fn delete_texture(&mut self, texture : u32) {
+        // framebuffers drawing into it go with it
+        let stale : Vec<Vec<u32>> = self.targets.keys().filter(|targets| targets.contains(&texture)).cloned().collect();
+        for targets in stale {
+            if let Some(target) = self.targets.remove(&targets) {
+                unsafe {
+                    gl::DeleteFramebuffers(1, &target.framebuffer);
+                    gl::DeleteRenderbuffers(1, &target.renderbuffer);
+                }
+            }
+        }
+        self.texture_sizes.remove(&texture);
+        // the name may come back for another texture while the tracker thinks it is bound
+        self.gl_state.invalidate();
+        unsafe {
+            gl::DeleteTextures(1, &texture);
+        }
+    }
+    fn create_buffer(&mut self, size : usize) -> u32 {
+        let mut buffer : u32 = 0;
+        unsafe {
+            gl::CreateBuffers(1, &mut buffer);
+            gl::NamedBufferData(buffer, size as isize, ptr::null(), gl::DYNAMIC_DRAW);
+        }
+        buffer
+    }
+    fn write_buffer(&mut self, buffer : u32, offset : usize, data : &[u8]) {
+        if data.is_empty() {
+            return;
+        }
+        unsafe {
+            gl::NamedBufferSubData(buffer, offset as isize, data.len() as isize, data.as_ptr() as *const c_void);
+        }
+    }
+    fn delete_buffer(&mut self, buffer : u32) {
+        if let Some(vertex_array) = self.instance_vertex_arrays.remove(&buffer) {
+            unsafe {
+                gl::DeleteVertexArrays(1, &vertex_array);
+            }
+        }
+        self.gl_state.invalidate();
+        unsafe {
+            gl::DeleteBuffers(1, &buffer);
+        }
+    }
+
+    fn init(&mut self, shader_factory : &mut ShaderFactory) -> Vec<ShaderError> {
+        shader_factory.add_include("tpixel_frame.glsl", GLSL_FRAME_INCLUDE);
+        shader_factory.add_include("tpixel_gbuffer.glsl", GLSL_GBUFFER_INCLUDE);
+        shader_factory.add_include("tpixel_lighting.glsl", GLSL_LIGHTING_INCLUDE);
+        let errors = self.build_shaders(shader_factory, None);
+
+        unsafe {
+            gl::DepthFunc(gl::LESS);
+
+            // bound for good, each view only updates the contents
+            gl::CreateBuffers(1, &mut self.frame_uniform_buffer_object);
+            gl::NamedBufferData(self.frame_uniform_buffer_object, std::mem::size_of::<FrameData>() as isize, ptr::null(), gl::DYNAMIC_DRAW);
+            gl::BindBufferBase(gl::UNIFORM_BUFFER, FRAME_DATA_BINDING, self.frame_uniform_buffer_object);
+
+            let verts : [f32; 8] = [
+                0.0f32, 1.0f32,
+                1.0f32, 1.0f32,
+                0.0f32, 0.0f32,
+                1.0f32, 0.0f32,
+            ];
+            gl::CreateBuffers(1, &mut self.vertex_buffer_object);
+            gl::NamedBufferData(self.vertex_buffer_object,
+                (std::mem::size_of::<f32>() * verts.len()) as isize,
+                &verts[0] as *const f32 as *const c_void,
+                gl::STATIC_DRAW);
+            gl::GenVertexArrays(1, &mut self.vertex_array_object);
+
+            gl::GenBuffers(1, &mut self.occluder_vertex_buffer_object);
+            gl::GenVertexArrays(1, &mut self.occluder_vertex_array_object);
+            gl::BindVertexArray(self.occluder_vertex_array_object);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.occluder_vertex_buffer_object);
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(0, 2, gl::FLOAT, gl::FALSE, (std::mem::size_of::<f32>() * 2) as i32, 0 as *const c_void);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl::BindVertexArray(0);
+
+            // debug lines, position then color
+            gl::GenBuffers(1, &mut self.debug_vertex_buffer_object);
+            gl::GenVertexArrays(1, &mut self.debug_vertex_array_object);
+            gl::BindVertexArray(self.debug_vertex_array_object);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.debug_vertex_buffer_object);
+            let debug_stride = (std::mem::size_of::<f32>() * 6) as i32;
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(0, 2, gl::FLOAT, gl::FALSE, debug_stride, 0 as *const c_void);
+            gl::EnableVertexAttribArray(1);
+            gl::VertexAttribPointer(1, 4, gl::FLOAT, gl::FALSE, debug_stride, (std::mem::size_of::<f32>() * 2) as *const c_void);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl::BindVertexArray(0);
+        }
+        self.gl_state.invalidate();
+        errors
+    }
+    // a shader that fails to build keeps its previous program
+    fn build_shaders(&mut self, shader_factory : &mut ShaderFactory, changed : Option<&[String]>) -> Vec<ShaderError> {
+        let mut errors : Vec<ShaderError> = Vec::new();
+        // the cached variants may be built from sources that changed since
+        shader_factory.drop_variants(changed);
+        let lighting_defines = self.shader_quality.defines();
+        rebuild_program(&mut self.geometry_shader, shader_factory, ("geometry.vert", VERTEX_GEO_SHADER_SOURCE), ("geometry.frag", &geometry_fragment_source(FRAGMENT_GEO_SHADER_DEFAULT_SURFACE)), &[], changed, &mut errors);
+        rebuild_program(&mut self.ambience_shader, shader_factory, ("fullscreen.vert", VERTEX_FULLSCREEN_SHADER_SOURCE), ("ambience.frag", FRAGMENT_AMBIENCE_SHADER_SOURCE), &lighting_defines, changed, &mut errors);
+        rebuild_program(&mut self.point_light_shader, shader_factory, ("fullscreen.vert", VERTEX_FULLSCREEN_SHADER_SOURCE), ("point_light.frag", FRAGMENT_POINT_LIGHT_SHADER_SOURCE), &lighting_defines, changed, &mut errors);
+        rebuild_program(&mut self.directional_light_shader, shader_factory, ("fullscreen.vert", VERTEX_FULLSCREEN_SHADER_SOURCE), ("directional_light.frag", FRAGMENT_DIRECTIONAL_LIGHT_SHADER_SOURCE), &lighting_defines, changed, &mut errors);
+        rebuild_program(&mut self.spot_light_shader, shader_factory, ("fullscreen.vert", VERTEX_FULLSCREEN_SHADER_SOURCE), ("spot_light.frag", FRAGMENT_SPOT_LIGHT_SHADER_SOURCE), &lighting_defines, changed, &mut errors);
+        rebuild_program(&mut self.occluder_polygon_shader, shader_factory, ("occluder_polygon.vert", VERTEX_OCCLUDER_POLYGON_SHADER_SOURCE), ("occluder_polygon.frag", FRAGMENT_OCCLUDER_POLYGON_SHADER_SOURCE), &[], changed, &mut errors);
+        rebuild_program(&mut self.occluder_sprite_shader, shader_factory, ("geometry.vert", VERTEX_GEO_SHADER_SOURCE), ("occluder_sprite.frag", FRAGMENT_OCCLUDER_SPRITE_SHADER_SOURCE), &[], changed, &mut errors);
+        rebuild_program(&mut self.shadow_map_shader, shader_factory, ("fullscreen.vert", VERTEX_FULLSCREEN_SHADER_SOURCE), ("shadow_map.frag", FRAGMENT_SHADOW_MAP_SHADER_SOURCE), &[], changed, &mut errors);
+        rebuild_program(&mut self.tone_mapping_shader, shader_factory, ("fullscreen.vert", VERTEX_FULLSCREEN_SHADER_SOURCE), ("tone_mapping.frag", FRAGMENT_TONE_MAPPING_SHADER_SOURCE), &[], changed, &mut errors);
+        rebuild_program(&mut self.bloom_prefilter_shader, shader_factory, ("fullscreen.vert", VERTEX_FULLSCREEN_SHADER_SOURCE), ("bloom_prefilter.frag", FRAGMENT_BLOOM_PREFILTER_SHADER_SOURCE), &[], changed, &mut errors);
+        rebuild_program(&mut self.bloom_downsample_shader, shader_factory, ("fullscreen.vert", VERTEX_FULLSCREEN_SHADER_SOURCE), ("bloom_downsample.frag", FRAGMENT_BLOOM_DOWNSAMPLE_SHADER_SOURCE), &[], changed, &mut errors);
+        rebuild_program(&mut self.bloom_upsample_shader, shader_factory, ("fullscreen.vert", VERTEX_FULLSCREEN_SHADER_SOURCE), ("bloom_upsample.frag", FRAGMENT_BLOOM_UPSAMPLE_SHADER_SOURCE), &[], changed, &mut errors);
+        rebuild_program(&mut self.composite_shader, shader_factory, ("fullscreen.vert", VERTEX_FULLSCREEN_SHADER_SOURCE), ("composite.frag", FRAGMENT_COMPOSITE_SHADER_SOURCE), &[], changed, &mut errors);
+        rebuild_program(&mut self.overlay_shader, shader_factory, ("geometry.vert", VERTEX_GEO_SHADER_SOURCE), ("overlay.frag", FRAGMENT_OVERLAY_SHADER_SOURCE), &[], changed, &mut errors);
+        rebuild_program(&mut self.debug_shader, shader_factory, ("debug.vert", VERTEX_DEBUG_SHADER_SOURCE), ("debug.frag", FRAGMENT_DEBUG_SHADER_SOURCE), &[], changed, &mut errors);
+        let particle_simulation_stale = match changed {
+            Some(changed) => !self.particle_simulation_shader.is_linked() || self.particle_simulation_shader.depends_on(changed),
+            None => true,
+        };
+        if particle_simulation_stale {
+            match shader_factory.new_feedback_program(("particle_simulation.vert", VERTEX_PARTICLE_SIMULATION_SHADER_SOURCE), &PARTICLE_SIMULATION_VARYINGS) {
+                Ok(new_program) => {
+                    self.particle_simulation_shader.drop();
+                    self.particle_simulation_shader = new_program;
+                },
+                // without a program emitters simulate on the cpu
+                Err(error) => errors.push(error),
+            }
+        }
+        self.lighting_defines = lighting_defines;
+
+        for material_shader in self.material_shaders.all_iter_mut() {
+            if let Err(error) = material_shader.value.rebuild(shader_factory, changed) {
+                errors.push(error);
+            }
+        }
+        for post_effect in self.post_effects.all_iter_mut() {
+            let effect = &mut post_effect.value;
+            if let Some(changed) = changed {
+                if !effect.shader.depends_on(changed) {
+                    continue;
+                }
+            }
+            match build_post_effect_program(shader_factory, &effect.name, &effect.fragment_source) {
+                Ok(shader) => {
+                    effect.shader.drop();
+                    effect.shader = shader;
+                    effect.shader_time = effect.shader.get_uniform_location("time");
+                    effect.shader_resolution = effect.shader.get_uniform_location("resolution");
+                },
+                Err(error) => errors.push(error),
+            }
+        }
+        self.query_uniform_locations();
+        errors
+    }
+    // swaps the lighting shaders for the quality's variants, building the ones that weren't used before
+    fn set_shader_quality(&mut self, shader_factory : &mut ShaderFactory, shader_quality : ShaderQuality) -> Vec<ShaderError> {
+        if shader_quality == self.shader_quality {
+            return Vec::new();
+        }
+        let defines = shader_quality.defines();
+        let fragments = [
+            ("ambience.frag", FRAGMENT_AMBIENCE_SHADER_SOURCE),
+            ("point_light.frag", FRAGMENT_POINT_LIGHT_SHADER_SOURCE),
+            ("directional_light.frag", FRAGMENT_DIRECTIONAL_LIGHT_SHADER_SOURCE),
+            ("spot_light.frag", FRAGMENT_SPOT_LIGHT_SHADER_SOURCE),
+        ];
+        let mut programs : Vec<(&str, ShaderProgram)> = Vec::new();
+        let mut errors : Vec<ShaderError> = Vec::new();
+        for fragment in fragments.iter() {
+            match shader_factory.take_variant(("fullscreen.vert", VERTEX_FULLSCREEN_SHADER_SOURCE), *fragment, &defines) {
+                Ok(program) => programs.push((fragment.0, program)),
+                Err(error) => errors.push(error),
+            }
+        }
+        if !errors.is_empty() {
+            // the ones that built are kept for the next try
+            for (fragment_name, program) in programs {
+                shader_factory.cache_variant("fullscreen.vert", fragment_name, &defines, program);
+            }
+            return errors;
+        }
+        let previous_defines = std::mem::replace(&mut self.lighting_defines, defines);
+        let mut shaders = [&mut self.ambience_shader, &mut self.point_light_shader, &mut self.directional_light_shader, &mut self.spot_light_shader];
+        for (shader, (fragment_name, program)) in shaders.iter_mut().zip(programs) {
+            let previous_program = std::mem::replace(*shader, program);
+            shader_factory.cache_variant("fullscreen.vert", fragment_name, &previous_defines, previous_program);
+        }
+        self.shader_quality = shader_quality;
+        self.query_uniform_locations();
+        errors
+    }
+    fn create_material_shader(&mut self, shader_factory : &ShaderFactory, surface_source : &str) -> Result<u32, ShaderError> {
+        let material_shader = MaterialShader::new(shader_factory, surface_source)?;
+        let shader_id = self.next_material_shader_id;
+        self.next_material_shader_id += 1;
+        self.material_shaders.insert(shader_id, material_shader);
+        Ok(shader_id)
+    }
+    fn create_post_effect(&mut self, shader_factory : &ShaderFactory, name : &str, fragment_source : &str) -> Result<u32, ShaderError> {
+        let shader = build_post_effect_program(shader_factory, name, fragment_source)?;
+        let effect_id = self.next_post_effect_id;
+        self.next_post_effect_id += 1;
+        self.post_effects.insert(effect_id, GlPostEffect {
+            name : name.to_string(),
+            shader_time : shader.get_uniform_location("time"),
+            shader_resolution : shader.get_uniform_location("resolution"),
+            shader : shader,
+            fragment_source : fragment_source.to_string(),
+        });
+        Ok(effect_id)
+    }
+    fn delete_post_effect(&mut self, effect : u32) {
+        if !self.post_effects.contains_key(effect) {
+            return;
+        }
+        let program = self.post_effects.get(effect).shader.get_id();
+        self.uniform_locations.remove(&program);
+        self.post_effects.get_mut(effect).shader.drop();
+        self.post_effects.remove(effect);
+        self.gl_state.invalidate();
+    }
+
+    fn begin_frame(&mut self, window_width : i32, window_height : i32) {
+        self.window_width = window_width;
+        self.window_height = window_height;
+        // textures and buffers were made since the last frame, none of the tracked state can be trusted
+        self.gl_state.invalidate();
+        self.gl_state.reset_counters();
+        self.gl_state.restore_defaults(window_width, window_height);
+        unsafe {
+            gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        }
+    }
+    fn end_frame(&mut self) {
+        self.gl_state.restore_defaults(self.window_width, self.window_height);
+        self.issued_gl_calls = self.gl_state.issued_calls();
+        self.skipped_gl_calls = self.gl_state.skipped_calls();
+    }
+    fn state_changes(&self) -> (u32, u32) {
+        (self.issued_gl_calls, self.skipped_gl_calls)
+    }
+    // before the buffers are swapped
+    fn read_window(&mut self) -> Vec<[f32; 4]> {
+        let mut texels : Vec<[f32; 4]> = vec![[0f32; 4]; (self.window_width.max(0) * self.window_height.max(0)) as usize];
+        if texels.is_empty() {
+            return texels;
+        }
+        self.gl_state.bind_framebuffer(0);
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 4);
+            gl::ReadPixels(0, 0, self.window_width, self.window_height, gl::RGBA, gl::FLOAT, texels.as_mut_ptr() as *mut c_void);
+        }
+        texels
+    }
+
+    fn begin_pass(&mut self, targets : &[u32], clear_colors : &[[f32; 4]], depth_test : bool) -> Result<(), ResourceError> {
+        let (framebuffer, size) = if targets.is_empty() {
+            (0, (self.window_width, self.window_height))
+        } else {
+            self.target(targets, depth_test)?
+        };
+        self.gl_state.bind_framebuffer(framebuffer);
+        self.gl_state.set_viewport(0, 0, size.0, size.1);
+        self.gl_state.set_depth_test(depth_test);
+        unsafe {
+            for (index, clear_color) in clear_colors.iter().enumerate().take(targets.len().max(1)) {
+                gl::ClearBufferfv(gl::COLOR, index as i32, &clear_color[0]);
+            }
+            if depth_test && !clear_colors.is_empty() {
+                let clear_depth : f32 = 1.0;
+                gl::ClearBufferfv(gl::DEPTH, 0, &clear_depth);
+            }
+        }
+        Ok(())
+    }
+    fn set_viewport(&mut self, x : i32, y : i32, width : i32, height : i32) {
+        self.gl_state.set_viewport(x, y, width, height);
+    }
+    fn set_frame(&mut self, frame : &BackendFrame) {
+        FrameData::new(&frame.camera_transform, &frame.camera_transform_inverse, frame.camera_view, frame.resolution, &frame.ambient, frame.time)
+            .upload(self.frame_uniform_buffer_object);
+    }
+
+    fn draw_sprites(&mut self, pass : SpritePass, material : &BackendMaterial, instance_buffer : u32, count : usize) {
+        if count == 0 {
+            return;
+        }
+        let vertex_array = self.instance_vertex_array(instance_buffer);
+        let image_size_location = match pass {
+            SpritePass::Geometry => {
+                self.gl_state.set_blend(false);
+                self.bind_geometry_shader(material)
+            },
+            SpritePass::Additive => {
+                self.gl_state.set_blend(true);
+                // the alpha of the light buffer is the coverage of the view, leave it alone
+                self.gl_state.set_blend_func_separate(gl::SRC_ALPHA, gl::ONE, gl::ZERO, gl::ONE);
+                self.gl_state.use_program(self.overlay_shader.get_id());
+                self.overlay_shader_image_size
+            },
+            SpritePass::Overlay => {
+                self.gl_state.set_blend(true);
+                self.gl_state.set_blend_func(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+                self.gl_state.use_program(self.overlay_shader.get_id());
+                self.overlay_shader_image_size
+            },
+            SpritePass::Occluder => {
+                self.gl_state.set_blend(false);
+                self.gl_state.use_program(self.occluder_sprite_shader.get_id());
+                self.occluder_sprite_shader_image_size
+            },
+        };
+        self.gl_state.bind_texture(0, material.color);
+        self.gl_state.bind_texture(1, material.normal);
+        self.gl_state.bind_texture(2, material.material);
+        self.gl_state.bind_vertex_array(vertex_array);
+        unsafe {
+            gl::Uniform2fv(image_size_location, 1, &material.size.x);
+            gl::DrawArraysInstanced(gl::TRIANGLE_STRIP, 0, 4, count as i32);
+        }
+    }
+    fn draw_polygons(&mut self, vertices : &[f32]) {
+        if vertices.is_empty() {
+            return;
+        }
+        self.gl_state.set_blend(false);
+        self.gl_state.use_program(self.occluder_polygon_shader.get_id());
+        self.gl_state.bind_vertex_array(self.occluder_vertex_array_object);
+        unsafe {
+            gl::NamedBufferData(self.occluder_vertex_buffer_object,
+                (std::mem::size_of::<f32>() * vertices.len()) as isize,
+                vertices.as_ptr() as *const c_void,
+                gl::DYNAMIC_DRAW);
+            gl::DrawArrays(gl::TRIANGLES, 0, (vertices.len() / 2) as i32);
+        }
+    }
+    fn draw_lines(&mut self, vertices : &[f32]) {
+        if vertices.is_empty() {
+            return;
+        }
+        self.gl_state.set_depth_test(false);
+        self.gl_state.set_blend(true);
+        self.gl_state.set_blend_func(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        self.gl_state.use_program(self.debug_shader.get_id());
+        self.gl_state.bind_vertex_array(self.debug_vertex_array_object);
+        unsafe {
+            gl::NamedBufferData(self.debug_vertex_buffer_object,
+                (std::mem::size_of::<f32>() * vertices.len()) as isize,
+                vertices.as_ptr() as *const c_void,
+                gl::STREAM_DRAW);
+            gl::DrawArrays(gl::LINES, 0, (vertices.len() / 6) as i32);
+        }
+    }
+    fn draw_shadow_map(&mut self, occlusion : u32, light : &PointLight, shadow_map_size : f32) {
+        let light_position : [f32; 4] = [light.position.x, light.position.y, light.height, light.range];
+        self.gl_state.set_blend(false);
+        self.gl_state.set_depth_test(false);
+        self.gl_state.use_program(self.shadow_map_shader.get_id());
+        self.gl_state.bind_texture(0, occlusion);
+        self.gl_state.bind_vertex_array(self.vertex_array_object);
+        unsafe {
+            gl::Uniform1f(self.shadow_map_shader_shadow_map_size, shadow_map_size);
+            gl::Uniform4fv(self.shadow_map_shader_light_position, 1, &light_position[0]);
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+        }
+    }
+    fn draw_ambience(&mut self, gbuffer : &BackendGBuffer) {
+        self.gl_state.set_blend(false);
+        self.bind_gbuffer(gbuffer);
+        self.gl_state.use_program(self.ambience_shader.get_id());
+        unsafe {
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+        }
+    }
+    fn draw_point_light(&mut self, gbuffer : &BackendGBuffer, light : &PointLight, shadow : [f32; 4]) {
+        self.gl_state.set_blend(true);
+        self.gl_state.set_blend_func(gl::ONE, gl::ONE);
+        self.bind_gbuffer(gbuffer);
+        self.gl_state.use_program(self.point_light_shader.get_id());
+        let light_color : [f32; 4] = [light.color.r, light.color.g, light.color.b, light.color.a];
+        let light_position : [f32; 4] = [light.position.x, light.position.y, light.height, light.range];
+        unsafe {
+            gl::Uniform4fv(self.point_light_shader_light_color, 1, &light_color[0]);
+            gl::Uniform4fv(self.point_light_shader_light_position, 1, &light_position[0]);
+            gl::Uniform4fv(self.point_light_shader_light_shadow, 1, &shadow[0]);
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+        }
+    }
+    fn draw_spot_light(&mut self, gbuffer : &BackendGBuffer, light : &SpotLight) {
+        self.gl_state.set_blend(true);
+        self.gl_state.set_blend_func(gl::ONE, gl::ONE);
+        self.bind_gbuffer(gbuffer);
+        self.gl_state.use_program(self.spot_light_shader.get_id());
+        let direction_length = (light.direction.x * light.direction.x + light.direction.y * light.direction.y).sqrt().max(f32::EPSILON);
+        let light_color : [f32; 4] = [light.color.r, light.color.g, light.color.b, light.color.a];
+        let light_position : [f32; 4] = [light.position.x, light.position.y, light.height, light.range];
+        let light_cone : [f32; 4] = [
+            light.direction.x / direction_length,
+            light.direction.y / direction_length,
+            light.inner_angle.cos(),
+            light.outer_angle.cos(),
+        ];
+        unsafe {
+            gl::Uniform4fv(self.spot_light_shader_light_color, 1, &light_color[0]);
+            gl::Uniform4fv(self.spot_light_shader_light_position, 1, &light_position[0]);
+            gl::Uniform4fv(self.spot_light_shader_light_cone, 1, &light_cone[0]);
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+        }
+    }
+    fn draw_directional_light(&mut self, gbuffer : &BackendGBuffer, light : &DirectionalLight) {
+        self.gl_state.set_blend(true);
+        self.gl_state.set_blend_func(gl::ONE, gl::ONE);
+        self.bind_gbuffer(gbuffer);
+        self.gl_state.use_program(self.directional_light_shader.get_id());
+        let direction = light.direction.normalized();
+        let light_color : [f32; 4] = [light.color.r, light.color.g, light.color.b, light.color.a];
+        let light_direction : [f32; 4] = [direction.x, direction.y, direction.z, light.intensity];
+        unsafe {
+            gl::Uniform4fv(self.directional_light_shader_light_color, 1, &light_color[0]);
+            gl::Uniform4fv(self.directional_light_shader_light_direction, 1, &light_direction[0]);
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+        }
+    }
+    fn draw_bloom_prefilter(&mut self, source : u32, threshold : f32) {
+        self.gl_state.set_blend(false);
+        self.gl_state.set_depth_test(false);
+        self.gl_state.use_program(self.bloom_prefilter_shader.get_id());
+        self.gl_state.bind_texture(0, source);
+        self.gl_state.bind_vertex_array(self.vertex_array_object);
+        unsafe {
+            gl::Uniform1f(self.bloom_prefilter_shader_bloom_threshold, threshold);
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+        }
+    }
+    fn draw_bloom_downsample(&mut self, source : u32) {
+        let texel_size = self.texel_size(source);
+        self.gl_state.set_blend(false);
+        self.gl_state.set_depth_test(false);
+        self.gl_state.use_program(self.bloom_downsample_shader.get_id());
+        self.gl_state.bind_texture(0, source);
+        self.gl_state.bind_vertex_array(self.vertex_array_object);
+        unsafe {
+            gl::Uniform2fv(self.bloom_downsample_shader_source_texel_size, 1, &texel_size[0]);
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+        }
+    }
+    fn draw_bloom_upsample(&mut self, source : u32, radius : f32) {
+        let texel_size = self.texel_size(source);
+        self.gl_state.set_blend(true);
+        self.gl_state.set_blend_func(gl::ONE, gl::ONE);
+        self.gl_state.set_depth_test(false);
+        self.gl_state.use_program(self.bloom_upsample_shader.get_id());
+        self.gl_state.bind_texture(0, source);
+        self.gl_state.bind_vertex_array(self.vertex_array_object);
+        unsafe {
+            gl::Uniform1f(self.bloom_upsample_shader_bloom_radius, radius);
+            gl::Uniform2fv(self.bloom_upsample_shader_source_texel_size, 1, &texel_size[0]);
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+        }
+    }
+    fn draw_tone_mapping(&mut self, scene : u32, bloom : u32, tone_mapping : &ToneMapping, bloom_intensity : f32) {
+        let operator : i32 = match tone_mapping.operator {
+            ToneMappingOperator::None => 0,
+            ToneMappingOperator::Reinhard => 1,
+            ToneMappingOperator::Aces => 2,
+            ToneMappingOperator::Filmic => 3,
+        };
+        let auto_exposure : [f32; 4] = [
+            if tone_mapping.auto_exposure { 1f32 } else { 0f32 },
+            tone_mapping.auto_exposure_key,
+            tone_mapping.min_exposure,
+            tone_mapping.max_exposure,
+        ];
+        let (width, height) = self.texture_sizes.get(&scene).copied().unwrap_or((1, 1));
+        let hdr_max_level = (width.max(height).max(1) as f32).log2().floor();
+        self.gl_state.set_blend(false);
+        self.gl_state.set_depth_test(false);
+        self.gl_state.bind_texture(0, scene);
+        self.gl_state.bind_texture(1, bloom);
+        self.gl_state.use_program(self.tone_mapping_shader.get_id());
+        self.gl_state.bind_vertex_array(self.vertex_array_object);
+        unsafe {
+            if tone_mapping.auto_exposure {
+                gl::GenerateTextureMipmap(scene);
+            }
+
+            gl::Uniform1i(self.tone_mapping_shader_tone_mapping_operator, operator);
+            gl::Uniform1f(self.tone_mapping_shader_exposure, tone_mapping.exposure);
+            gl::Uniform4fv(self.tone_mapping_shader_auto_exposure, 1, &auto_exposure[0]);
+            gl::Uniform1f(self.tone_mapping_shader_hdr_max_level, hdr_max_level);
+            gl::Uniform1f(self.tone_mapping_shader_bloom_intensity, bloom_intensity);
+
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+        }
+    }
+    fn draw_post_effect(&mut self, effect : u32, source : u32, scene : u32, gbuffer : &BackendGBuffer, params : &[(String, UniformValue)], time : f32) {
+        if !self.post_effects.contains_key(effect) {
+            return;
+        }
+        let (program, shader_time, shader_resolution) = {
+            let post_effect = self.post_effects.get(effect);
+            (post_effect.shader.get_id(), post_effect.shader_time, post_effect.shader_resolution)
+        };
+        let (width, height) = self.texture_sizes.get(&source).copied().unwrap_or((0, 0));
+        let resolution : [f32; 2] = [width as f32, height as f32];
+        self.gl_state.set_blend(false);
+        self.gl_state.set_depth_test(false);
+        self.gl_state.bind_texture(0, source);
+        self.gl_state.bind_texture(1, scene);
+        self.gl_state.bind_texture(2, gbuffer.color);
+        self.gl_state.bind_texture(3, gbuffer.normal);
+        self.gl_state.bind_texture(4, gbuffer.material);
+        self.gl_state.bind_texture(5, gbuffer.height);
+        self.gl_state.bind_vertex_array(self.vertex_array_object);
+        self.gl_state.use_program(program);
+        for (name, value) in params.iter() {
+            let location = self.uniform_location(program, name);
+            value.set_uniform(location);
+        }
+        unsafe {
+            gl::Uniform1f(shader_time, time);
+            gl::Uniform2fv(shader_resolution, 1, &resolution[0]);
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+        }
+    }
+    fn draw_composite(&mut self, source : u32, viewport : (i32, i32, i32, i32), scissor : (i32, i32, i32, i32), blend : bool) {
+        self.gl_state.set_depth_test(false);
+        if blend {
+            self.gl_state.set_blend(true);
+            self.gl_state.set_blend_func(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        } else {
+            self.gl_state.set_blend(false);
+        }
+        self.gl_state.set_viewport(viewport.0, viewport.1, viewport.2, viewport.3);
+        self.gl_state.use_program(self.composite_shader.get_id());
+        self.gl_state.bind_texture(0, source);
+        self.gl_state.bind_vertex_array(self.vertex_array_object);
+        unsafe {
+            gl::Enable(gl::SCISSOR_TEST);
+            gl::Scissor(scissor.0, scissor.1, scissor.2, scissor.3);
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            gl::Disable(gl::SCISSOR_TEST);
+        }
+    }
+
+    fn supports_particle_simulation(&self) -> bool {
+        self.particle_simulation_shader.is_linked()
+    }
+    fn create_particle_system(&mut self, capacity : usize) -> u32 {
+        let mut particle_system = GlParticleSystem {
+            state_buffer_objects : [0; 2],
+            state_vertex_array_objects : [0; 2],
+            instance_buffer_object : 0,
+            source : 0,
+            capacity : capacity,
+        };
+        let state_stride = std::mem::size_of::<ParticleSpawn>();
+        // zeroed slots have a lifetime of 0 and are dead
+        let zeroed_state = vec![0u8; state_stride * capacity.max(1)];
+        let zeroed_instances = vec![0u8; std::mem::size_of::<Sprite>() * capacity.max(1)];
+        unsafe {
+            gl::GenBuffers(2, particle_system.state_buffer_objects.as_mut_ptr());
+            gl::GenVertexArrays(2, particle_system.state_vertex_array_objects.as_mut_ptr());
+            for i in 0..2 {
+                gl::BindBuffer(gl::ARRAY_BUFFER, particle_system.state_buffer_objects[i]);
+                gl::BufferData(gl::ARRAY_BUFFER, zeroed_state.len() as isize, zeroed_state.as_ptr() as *const c_void, gl::DYNAMIC_COPY);
+                gl::BindVertexArray(particle_system.state_vertex_array_objects[i]);
+                // position, velocity, age, lifetime
+                gl::EnableVertexAttribArray(0);
+                gl::VertexAttribPointer(0, 2, gl::FLOAT, gl::FALSE, state_stride as i32, (std::mem::size_of::<f32>() * 0) as *const c_void);
+                gl::EnableVertexAttribArray(1);
+                gl::VertexAttribPointer(1, 2, gl::FLOAT, gl::FALSE, state_stride as i32, (std::mem::size_of::<f32>() * 2) as *const c_void);
+                gl::EnableVertexAttribArray(2);
+                gl::VertexAttribPointer(2, 1, gl::FLOAT, gl::FALSE, state_stride as i32, (std::mem::size_of::<f32>() * 4) as *const c_void);
+                gl::EnableVertexAttribArray(3);
+                gl::VertexAttribPointer(3, 1, gl::FLOAT, gl::FALSE, state_stride as i32, (std::mem::size_of::<f32>() * 5) as *const c_void);
+            }
+            gl::BindVertexArray(0);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+
+            gl::CreateBuffers(1, &mut particle_system.instance_buffer_object);
+            gl::NamedBufferData(particle_system.instance_buffer_object, zeroed_instances.len() as isize, zeroed_instances.as_ptr() as *const c_void, gl::DYNAMIC_COPY);
+        }
+        self.gl_state.invalidate();
+        let system = self.next_particle_system_id;
+        self.next_particle_system_id += 1;
+        self.particle_systems.insert(system, particle_system);
+        system
+    }
+    fn delete_particle_system(&mut self, system : u32) {
+        if !self.particle_systems.contains_key(system) {
+            return;
+        }
+        let (state_buffer_objects, state_vertex_array_objects, instance_buffer_object) = {
+            let particle_system = self.particle_systems.get(system);
+            (particle_system.state_buffer_objects, particle_system.state_vertex_array_objects, particle_system.instance_buffer_object)
+        };
+        self.particle_systems.remove(system);
+        unsafe {
+            gl::DeleteBuffers(2, state_buffer_objects.as_ptr());
+            gl::DeleteVertexArrays(2, state_vertex_array_objects.as_ptr());
+        }
+        self.delete_buffer(instance_buffer_object);
+    }
+    fn simulate_particles(&mut self, system : u32, spawns : &[(usize, ParticleSpawn)], simulation : &ParticleSimulation) {
+        if !self.particle_systems.contains_key(system) || !self.particle_simulation_shader.is_linked() {
+            return;
+        }
+        let (state_buffer_objects, state_vertex_array_objects, instance_buffer_object, source, capacity) = {
+            let particle_system = self.particle_systems.get(system);
+            (particle_system.state_buffer_objects, particle_system.state_vertex_array_objects, particle_system.instance_buffer_object,
+                particle_system.source, particle_system.capacity)
+        };
+        if capacity == 0 {
+            return;
+        }
+        let destination = 1 - source;
+        // spawns mostly land in consecutive slots, each run is one upload
+        let state_stride = std::mem::size_of::<ParticleSpawn>();
+        let mut run_start = 0;
+        for i in 1..=spawns.len() {
+            if i < spawns.len() && spawns[i].0 == spawns[i - 1].0 + 1 {
+                continue;
+            }
+            let run : Vec<ParticleSpawn> = spawns[run_start..i].iter().map(|(_, spawn)| *spawn).collect();
+            unsafe {
+                gl::NamedBufferSubData(
+                    state_buffer_objects[source],
+                    (spawns[run_start].0 * state_stride) as isize,
+                    (run.len() * state_stride) as isize,
+                    run.as_ptr() as *const c_void);
+            }
+            run_start = i;
+        }
+
+        self.gl_state.use_program(self.particle_simulation_shader.get_id());
+        self.gl_state.bind_vertex_array(state_vertex_array_objects[source]);
+        unsafe {
+            gl::Uniform1f(self.particle_simulation_shader_delta_time, simulation.delta_time);
+            gl::Uniform2f(self.particle_simulation_shader_gravity, simulation.gravity.x, simulation.gravity.y);
+            gl::Uniform4f(self.particle_simulation_shader_uv_rect, simulation.uv_rect.begin.x, simulation.uv_rect.begin.y, simulation.uv_rect.end.x, simulation.uv_rect.end.y);
+            gl::Uniform1f(self.particle_simulation_shader_z, simulation.z);
+            gl::Uniform1f(self.particle_simulation_shader_height, simulation.height);
+            gl::Uniform1ui(self.particle_simulation_shader_material_id, simulation.material_id);
+            gl::Uniform1ui(self.particle_simulation_shader_layer, simulation.layer);
+            gl::Uniform4fv(self.particle_simulation_shader_color_over_life, PARTICLE_CURVE_SAMPLES as i32, simulation.colors[0].as_ptr());
+            gl::Uniform1fv(self.particle_simulation_shader_size_over_life, PARTICLE_CURVE_SAMPLES as i32, simulation.sizes.as_ptr());
+
+            gl::BindBufferBase(gl::TRANSFORM_FEEDBACK_BUFFER, 0, state_buffer_objects[destination]);
+            gl::BindBufferBase(gl::TRANSFORM_FEEDBACK_BUFFER, 1, instance_buffer_object);
+            gl::Enable(gl::RASTERIZER_DISCARD);
+            gl::BeginTransformFeedback(gl::POINTS);
+            gl::DrawArrays(gl::POINTS, 0, capacity as i32);
+            gl::EndTransformFeedback();
+            gl::Disable(gl::RASTERIZER_DISCARD);
+            gl::BindBufferBase(gl::TRANSFORM_FEEDBACK_BUFFER, 0, 0);
+            gl::BindBufferBase(gl::TRANSFORM_FEEDBACK_BUFFER, 1, 0);
+        }
+        self.particle_systems.get_mut(system).source = destination;
+    }
+    fn particle_instances(&self, system : u32) -> u32 {
+        if !self.particle_systems.contains_key(system) {
+            return 0;
+        }
+        self.particle_systems.get(system).instance_buffer_object
+    }
+
+    fn drop(&mut self) {
+        unsafe {
+            for (_, target) in self.targets.drain() {
+                gl::DeleteFramebuffers(1, &target.framebuffer);
+                gl::DeleteRenderbuffers(1, &target.renderbuffer);
+            }
+            for (_, vertex_array) in self.instance_vertex_arrays.drain() {
+                gl::DeleteVertexArrays(1, &vertex_array);
+            }
+            for (texture, _) in self.texture_sizes.drain() {
+                gl::DeleteTextures(1, &texture);
+            }
+            gl::DeleteBuffers(1, &self.frame_uniform_buffer_object);
+            gl::DeleteBuffers(1, &self.vertex_buffer_object);
+            gl::DeleteVertexArrays(1, &self.vertex_array_object);
+            gl::DeleteBuffers(1, &self.occluder_vertex_buffer_object);
+            gl::DeleteVertexArrays(1, &self.occluder_vertex_array_object);
+            gl::DeleteBuffers(1, &self.debug_vertex_buffer_object);
+            gl::DeleteVertexArrays(1, &self.debug_vertex_array_object);
+        }
+        let systems : Vec<u32> = self.particle_systems.all_iter().map(|system_kv| system_kv.get_key()).collect();
+        for system in systems {
+            self.delete_particle_system(system);
+        }
+        for material_shader in self.material_shaders.all_iter_mut() {
+            material_shader.value.drop();
+        }
+        for post_effect in self.post_effects.all_iter_mut() {
+            post_effect.value.shader.drop();
+        }
+        for shader in [&mut self.geometry_shader, &mut self.ambience_shader, &mut self.point_light_shader, &mut self.directional_light_shader,
+            &mut self.spot_light_shader, &mut self.occluder_polygon_shader, &mut self.occluder_sprite_shader, &mut self.shadow_map_shader,
+            &mut self.tone_mapping_shader, &mut self.bloom_prefilter_shader, &mut self.bloom_downsample_shader, &mut self.bloom_upsample_shader,
+            &mut self.composite_shader, &mut self.overlay_shader, &mut self.debug_shader, &mut self.particle_simulation_shader].iter_mut() {
+            shader.drop();
+        }
+    }
+}
+
+// a program that fails to build keeps the one it had, or gets the built in sources if it had none,
+// if those fail too it stays unlinked, draws with it do nothing and the next reload tries again,
+// vertex and fragment are the file name in the shader directory and the built in source,
+// with changed it is only rebuilt if it was made from one of the changed files
+fn rebuild_program(program : &mut ShaderProgram, shader_factory : &ShaderFactory, vertex : (&str, &str), fragment : (&str, &str), defines : &[&str],
+    changed : Option<&[String]>, errors : &mut Vec<ShaderError>) {
+    if let Some(changed) = changed {
+        if program.is_linked() && !program.depends_on(changed) {
+            return;
+        }
+    }
+    match shader_factory.new_program_from_files(vertex, fragment, defines) {
+        Ok(new_program) => {
+            program.drop();
+            *program = new_program;
+        },
+        Err(error) => {
+            errors.push(error);
+            if program.is_linked() {
+                return;
+            }
+            match shader_factory.new_program_with_defines(vertex.1, fragment.1, defines) {
+                Ok(fallback) => {
+                    // still rebuilt when the broken files are fixed
+                    let mut dependencies = vec![vertex.0.to_string(), fragment.0.to_string()];
+                    dependencies.extend(fallback.dependencies().iter().cloned());
+                    *program = fallback.with_dependencies(dependencies);
+                },
+                Err(error) => errors.push(error.for_program(&format!("built in {} {}", vertex.0, fragment.0))),
+            }
+        },
+    }
+}
+// fullscreen.vert from the shader directory if there is one with the effect's own fragment source
+fn build_post_effect_program(shader_factory : &ShaderFactory, name : &str, fragment_source : &str) -> Result<ShaderProgram, ShaderError> {
+    let build = || -> Result<ShaderProgram, ShaderError> {
+        let vertex_source = shader_factory.load_source("fullscreen.vert", VERTEX_FULLSCREEN_SHADER_SOURCE)?;
+        shader_factory.new_program(&vertex_source, fragment_source)
+    };
+    let shader = build().map_err(|error| error.for_program(name))?;
+    let mut dependencies = vec!["fullscreen.vert".to_string()];
+    dependencies.extend(shader.dependencies().iter().cloned());
+    Ok(shader.with_dependencies(dependencies))
+}
+// points the bound vertex array at the quad and a buffer of Sprite instances
+fn bind_sprite_attributes(vertex_buffer_object : u32, instance_buffer_object : u32) {
+    unsafe {
+        gl::BindBuffer(gl::ARRAY_BUFFER, vertex_buffer_object);
+        gl::EnableVertexAttribArray(0);
+        gl::VertexAttribPointer(0, 2, gl::FLOAT, gl::FALSE, (std::mem::size_of::<f32>() * 2) as i32, 0 as *const c_void);
+        // instance stuff
+        gl::BindBuffer(gl::ARRAY_BUFFER, instance_buffer_object);
+        let stride = std::mem::size_of::<Sprite>() as i32;
+        // transform
+        gl::EnableVertexAttribArray(1);
+        gl::VertexAttribPointer(1, 2, gl::FLOAT, gl::FALSE, stride, (std::mem::size_of::<f32>() * 0) as *const c_void);
+        gl::EnableVertexAttribArray(2);
+        gl::VertexAttribPointer(2, 2, gl::FLOAT, gl::FALSE, stride, (std::mem::size_of::<f32>() * 2) as *const c_void);
+        gl::EnableVertexAttribArray(3);
+        gl::VertexAttribPointer(3, 2, gl::FLOAT, gl::FALSE, stride, (std::mem::size_of::<f32>() * 4) as *const c_void);
+        // pivot
+        gl::EnableVertexAttribArray(4);
+        gl::VertexAttribPointer(4, 2, gl::FLOAT, gl::FALSE, stride, (std::mem::size_of::<f32>() * 6) as *const c_void);
+        // color
+        gl::EnableVertexAttribArray(5);
+        gl::VertexAttribPointer(5, 4, gl::FLOAT, gl::FALSE, stride, (std::mem::size_of::<f32>() * 8) as *const c_void);
+        // uv
+        gl::EnableVertexAttribArray(6);
+        gl::VertexAttribPointer(6, 4, gl::FLOAT, gl::FALSE, stride, (std::mem::size_of::<f32>() * 12) as *const c_void);
+        // z
+        gl::EnableVertexAttribArray(7);
+        gl::VertexAttribPointer(7, 1, gl::FLOAT, gl::FALSE, stride, (std::mem::size_of::<f32>() * 16) as *const c_void);
+        // height
+        gl::EnableVertexAttribArray(8);
+        gl::VertexAttribPointer(8, 1, gl::FLOAT, gl::FALSE, stride, (std::mem::size_of::<f32>() * 17) as *const c_void);
+        // height map scale
+        gl::EnableVertexAttribArray(10);
+        gl::VertexAttribPointer(10, 1, gl::FLOAT, gl::FALSE, stride, (std::mem::size_of::<f32>() * 18) as *const c_void);
+        // id
+        gl::EnableVertexAttribArray(9);
+        gl::VertexAttribIPointer(9, 1, gl::UNSIGNED_INT, stride, (std::mem::size_of::<f32>() * 19) as *const c_void);
+        // flags, after the layer
+        gl::EnableVertexAttribArray(11);
+        gl::VertexAttribIPointer(11, 1, gl::UNSIGNED_INT, stride, (std::mem::size_of::<f32>() * 21) as *const c_void);
+
+        gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        gl::VertexAttribDivisor(1, 1);
+        gl::VertexAttribDivisor(2, 1);
+        gl::VertexAttribDivisor(3, 1);
+        gl::VertexAttribDivisor(4, 1);
+        gl::VertexAttribDivisor(5, 1);
+        gl::VertexAttribDivisor(6, 1);
+        gl::VertexAttribDivisor(7, 1);
+        gl::VertexAttribDivisor(8, 1);
+        gl::VertexAttribDivisor(9, 1);
+        gl::VertexAttribDivisor(10, 1);
+        gl::VertexAttribDivisor(11, 1);
+    }
+}
@@ -0,0 +1,733 @@
+// the glsl behind GlBackend, a file with a shader's name in the shader directory replaces it
+
+// what every shader knows about the frame, set once per view
+pub(crate) const GLSL_FRAME_INCLUDE : &str = r#"
+    layout(std140, binding=0) uniform FrameData {
+        mat3x2 camera_transform; // world to view
+        mat3x2 camera_transform_inverse; // view to world
+        vec2 camera_view; // half the view size in world units
+        vec2 resolution; // of the target in pixels
+        vec4 ambient; // rgb and intensity
+        float time; // seconds since start
+    } frame;
+"#;
+
+pub(crate) const VERTEX_GEO_SHADER_SOURCE : &str = r#"
+    #version 420 core
+    // vertex
+    layout (location = 0) in vec2 vertex_pos;
+    // instance
+    layout (location = 1) in mat3x2 instance_transform;
+    layout (location = 4) in vec2 instance_pivot;
+    layout (location = 5) in vec4 instance_color;
+    layout (location = 6) in vec4 instance_uv;
+    layout (location = 7) in float instance_z;
+    layout (location = 8) in float instance_height;
+    layout (location = 9) in uint instance_id;
+    layout (location = 10) in float instance_height_map_scale;
+    layout (location = 11) in uint instance_flags;
+
+    out vec2 UV;
+    out vec4 COLOR;
+    out float HEIGHT;
+    out float HEIGHT_MAP_SCALE;
+    out vec2 AXIS_X;
+    out vec2 AXIS_Y;
+    out vec2 WORLD_POSITION;
+    flat out uint FLAGS;
+
+    #include "tpixel_frame.glsl"
+
+    uniform vec2 image_size;
+
+    void main() {
+        bool flip_x = (instance_flags & 8u) != 0u;
+        bool flip_y = (instance_flags & 16u) != 0u;
+        int uv_x = flip_x ? 1 - gl_VertexID % 2 : gl_VertexID % 2;
+        int uv_y = flip_y ? 1 - gl_VertexID / 2 : gl_VertexID / 2;
+        UV.x = instance_uv[uv_x*2];//[0];
+        UV.y = instance_uv[uv_y*2+1];//[1];
+
+        vec2 quad_size = instance_uv.zw - instance_uv.xy;
+        vec2 instance_size = quad_size * image_size;
+
+        vec2 world_pos = instance_transform * vec3(vertex_pos.xy * instance_size - instance_size * instance_pivot, 1.0f);
+        vec2 view_pos = frame.camera_transform * vec3(world_pos, 1.0f);
+        view_pos /= frame.camera_view;
+        gl_Position = vec4(view_pos.x, view_pos.y, -instance_z, 1.0);
+        WORLD_POSITION = world_pos;
+        COLOR = instance_color;
+        HEIGHT = instance_height;
+        HEIGHT_MAP_SCALE = instance_height_map_scale;
+        // the sprite's rotation and mirroring in world space, used to rotate its normals
+        AXIS_X = normalize(instance_transform[0]) * (flip_x ? -1.0 : 1.0);
+        AXIS_Y = normalize(instance_transform[1]) * (flip_y ? -1.0 : 1.0);
+        FLAGS = instance_flags;
+    }
+"#;
+
+// material shaders are this header, a surface function and the main below,
+// the surface function gets the interpolated uv and returns what goes into the gbuffer
+const FRAGMENT_GEO_SHADER_HEADER : &str = r#"
+    #version 420 core
+    layout(location=0) out vec4 out_color;
+    layout(location=1) out vec4 out_normal;
+    layout(location=2) out vec4 out_material;
+    layout(location=3) out float out_height;
+
+    in vec2 UV;
+    in vec4 COLOR;
+    in float HEIGHT;
+    in float HEIGHT_MAP_SCALE;
+    in vec2 AXIS_X;
+    in vec2 AXIS_Y;
+    in vec2 WORLD_POSITION;
+    flat in uint FLAGS;
+
+    layout(binding=0) uniform sampler2D image_color;
+    layout(binding=1) uniform sampler2D image_normal;
+    layout(binding=2) uniform sampler2D image_material;
+
+    #include "tpixel_frame.glsl"
+
+    uniform vec2 image_size;
+
+    struct Surface {
+        vec4 color; // tinted by the sprite color, below 0.1 alpha is discarded for SPRITE_ALPHA_CUTOUT sprites
+        vec2 normal; // tangent space in -1 to 1, turned along with the sprite
+        float height_map; // 0 to 1, scaled by the sprite's height map scale
+        vec4 material; // r = specular, g = gloss, b = emissive, a = lit, 0 draws the color as is
+    };
+
+    Surface sample_surface(vec2 uv) {
+        Surface surface;
+        surface.color = COLOR * texture(image_color, uv);
+        vec4 nor = texture(image_normal, uv);
+        surface.normal = nor.xy * 2.0 - 1.0;
+        surface.height_map = nor.a;
+        surface.material = texture(image_material, uv);
+        return surface;
+    }
+"#;
+
+pub(crate) const FRAGMENT_GEO_SHADER_DEFAULT_SURFACE : &str = r#"
+    Surface surface(vec2 uv) {
+        return sample_surface(uv);
+    }
+"#;
+
+const FRAGMENT_GEO_SHADER_MAIN : &str = r#"
+    void main() {
+        Surface s = surface(UV);
+        if ((FLAGS & 32u) != 0u && s.color.a < 0.1f) {
+            discard;
+        }
+        out_color = s.color;
+        // the gbuffer stores world space normals, so the lighting passes don't need to know the sprite's transform
+        vec3 normal = vec3(AXIS_X * s.normal.x + AXIS_Y * s.normal.y, sqrt(max(0.0, 1.0 - dot(s.normal, s.normal))));
+        out_normal = vec4(normalize(normal) * 0.5 + 0.5, s.height_map);
+        out_material = s.material;
+        if ((FLAGS & 2u) != 0u) {
+            out_material.a = 0.0;
+        }
+        out_height = HEIGHT + s.height_map * HEIGHT_MAP_SCALE;
+    }
+"#;
+
+pub(crate) const VERTEX_FULLSCREEN_SHADER_SOURCE : &str = r#"
+    #version 420 core
+
+    out vec2 UV;
+    out vec2 WORLD_POSITION;
+
+    #include "tpixel_frame.glsl"
+
+    void main()
+    {
+        float x = -1.0 + float((gl_VertexID & 1) << 2);
+        float y = -1.0 + float((gl_VertexID & 2) << 1);
+        UV.x = (x+1.0)*0.5;
+        UV.y = (y+1.0)*0.5;
+
+        vec2 view_pos = vec2(x, y);
+        WORLD_POSITION = view_pos * frame.camera_view;
+        WORLD_POSITION = frame.camera_transform_inverse * vec3(WORLD_POSITION, 1.0);
+
+        gl_Position = vec4(x, y, 0, 1);
+    }
+"#;
+
+// shared by the lighting passes through #include
+pub(crate) const GLSL_GBUFFER_INCLUDE : &str = r#"
+    layout(binding=0) uniform sampler2D image_color;
+    layout(binding=1) uniform sampler2D image_normal;
+    layout(binding=2) uniform sampler2D image_material;
+    layout(binding=4) uniform sampler2D image_height;
+
+    struct GBufferSample {
+        vec4 color;
+        vec3 normal; // world space
+        float height;
+        float specular;
+        float shininess;
+        float emissive;
+        float unlit;
+    };
+
+    GBufferSample sample_gbuffer(vec2 uv) {
+        GBufferSample gbuffer;
+        gbuffer.color = texture(image_color, uv);
+        gbuffer.normal = normalize(texture(image_normal, uv).xyz * 2.0 - 1.0);
+        gbuffer.height = texture(image_height, uv).r;
+        // material layout: r = specular, g = gloss, b = emissive, a = lit mask, so images without alpha are lit
+        vec4 mat = texture(image_material, uv);
+        gbuffer.specular = mat.r;
+        gbuffer.shininess = exp2(mat.g * 10.0 + 1.0);
+        gbuffer.emissive = mat.b;
+        gbuffer.unlit = 1.0 - mat.a;
+        return gbuffer;
+    }
+"#;
+
+pub(crate) const GLSL_LIGHTING_INCLUDE : &str = r#"
+    #include "tpixel_gbuffer.glsl"
+
+    // blinn phong with the viewer straight above
+    float specular_highlight(GBufferSample gbuffer, vec3 dir, float theta) {
+    #ifdef TPIXEL_NO_SPECULAR
+        return 0.0;
+    #else
+        vec3 half_dir = normalize(dir + vec3(0.0, 0.0, 1.0));
+        return theta > 0.0 ? gbuffer.specular * pow(max(0.0, dot(gbuffer.normal, half_dir)), gbuffer.shininess) : 0.0;
+    #endif
+    }
+
+    // diffuse and specular light from dir, black where the gbuffer is unlit
+    vec3 shade(GBufferSample gbuffer, vec3 dir, vec3 light_color) {
+        float theta = max(0.0, dot(dir, gbuffer.normal));
+        float highlight = specular_highlight(gbuffer, dir, theta);
+        return (gbuffer.color.rgb * theta + highlight) * light_color * (1.0 - gbuffer.unlit);
+    }
+"#;
+
+pub(crate) const FRAGMENT_AMBIENCE_SHADER_SOURCE : &str = r#"
+    #version 420 core
+    out vec4 out_color;
+
+    #include "tpixel_lighting.glsl"
+
+    in vec2 UV;
+    in vec2 WORLD_POSITION;
+
+    #include "tpixel_frame.glsl"
+
+    void main() {
+        GBufferSample gbuffer = sample_gbuffer(UV);
+        vec4 col = gbuffer.color;
+
+        // ambience comes from straight above, so the half vector is the view vector
+        float highlight = specular_highlight(gbuffer, vec3(0.0, 0.0, 1.0), 1.0);
+
+        vec3 ambience = frame.ambient.rgb * frame.ambient.a;
+        vec3 lit = col.rgb * ambience + ambience * highlight;
+
+        out_color.rgba = col.rgba;
+        out_color.rgb = mix(lit, col.rgb, gbuffer.unlit) + col.rgb * gbuffer.emissive;
+    }
+"#;
+
+pub(crate) const FRAGMENT_POINT_LIGHT_SHADER_SOURCE : &str = r#"
+    #version 420 core
+    out vec4 out_color;
+
+    #include "tpixel_lighting.glsl"
+
+    layout(binding=3) uniform sampler2D shadow_map;
+
+    in vec2 UV;
+    in vec2 WORLD_POSITION;
+
+    uniform vec4 light_color;
+    uniform vec4 light_position;
+    uniform vec4 light_shadow; // x = shadow map row, y = softness in texels, z = shadow map width, row < 0 means no shadows
+
+    const float PI = 3.14159265;
+    const float SHADOW_BIAS = 0.01;
+
+    float sample_shadow(vec3 world_pos, float light_range) {
+    #ifdef TPIXEL_NO_SHADOWS
+        return 1.0;
+    #else
+        if (light_shadow.x < 0.0) {
+            return 1.0;
+        }
+        vec2 to_pixel = world_pos.xy - light_position.xy;
+        float angle = atan(to_pixel.y, to_pixel.x);
+        float u = (angle + PI) / (2.0 * PI);
+        float pixel_distance = length(to_pixel) / light_range;
+        // penumbra widens with the distance from the light
+        float blur = light_shadow.y * pixel_distance / light_shadow.z;
+        float lit = 0.0;
+        for (int i = -2; i <= 2; i++) {
+            float occluder_distance = texture(shadow_map, vec2(u + float(i) * blur, light_shadow.x)).r;
+            lit += step(pixel_distance, occluder_distance + SHADOW_BIAS);
+        }
+        return lit / 5.0;
+    #endif
+    }
+
+    void main() {
+        GBufferSample gbuffer = sample_gbuffer(UV);
+
+        float light_range = light_position.w;
+        vec3 world_pos = vec3(WORLD_POSITION.xy, gbuffer.height);
+        float linear_attenuation = (light_range - length(world_pos - light_position.xyz)) / light_range; 
+        float attenuation = pow(max(0.0, linear_attenuation), 2.0);
+        vec3 light_color = light_color.rgb * light_color.a;
+
+        vec3 dir = normalize(light_position.xyz - world_pos);
+        float shadow = sample_shadow(world_pos, light_range);
+
+        out_color.rgb = shade(gbuffer, dir, light_color) * attenuation * shadow;
+        // only the ambience pass carries coverage
+        out_color.a = 0.0;
+    }
+"#;
+
+pub(crate) const VERTEX_OCCLUDER_POLYGON_SHADER_SOURCE : &str = r#"
+    #version 420 core
+    layout (location = 0) in vec2 vertex_pos;
+
+    #include "tpixel_frame.glsl"
+
+    void main() {
+        vec2 view_pos = frame.camera_transform * vec3(vertex_pos, 1.0f);
+        view_pos /= frame.camera_view;
+        gl_Position = vec4(view_pos.x, view_pos.y, 0.0, 1.0);
+    }
+"#;
+
+pub(crate) const FRAGMENT_OCCLUDER_POLYGON_SHADER_SOURCE : &str = r#"
+    #version 420 core
+    layout(location=0) out float out_occlusion;
+
+    void main() {
+        out_occlusion = 1.0;
+    }
+"#;
+
+pub(crate) const FRAGMENT_OCCLUDER_SPRITE_SHADER_SOURCE : &str = r#"
+    #version 420 core
+    layout(location=0) out float out_occlusion;
+
+    in vec2 UV;
+    in vec4 COLOR;
+
+    layout(binding=0) uniform sampler2D image_color;
+
+    void main() {
+        vec4 color = texture(image_color, UV);
+        if (color.a * COLOR.a < 0.5f) {
+            discard;
+        }
+        out_occlusion = 1.0;
+    }
+"#;
+
+pub(crate) const FRAGMENT_SHADOW_MAP_SHADER_SOURCE : &str = r#"
+    #version 420 core
+    layout(location=0) out float out_distance;
+
+    layout(binding=0) uniform sampler2D image_occlusion;
+
+    #include "tpixel_frame.glsl"
+
+    uniform vec4 light_position;
+    uniform float shadow_map_size;
+
+    const float PI = 3.14159265;
+    const int STEPS = 256;
+
+    void main() {
+        // each texel in the row is one angle around the light, storing the distance to the closest occluder
+        float angle = (gl_FragCoord.x / shadow_map_size) * 2.0 * PI - PI;
+        vec2 dir = vec2(cos(angle), sin(angle));
+        float light_range = light_position.w;
+
+        out_distance = 1.0;
+        for (int i = 0; i < STEPS; i++) {
+            float t = float(i) / float(STEPS);
+            vec2 world_pos = light_position.xy + dir * t * light_range;
+            vec2 view_pos = (frame.camera_transform * vec3(world_pos, 1.0)) / frame.camera_view;
+            vec2 occlusion_uv = view_pos * 0.5 + 0.5;
+            if (occlusion_uv.x < 0.0 || occlusion_uv.y < 0.0 || occlusion_uv.x > 1.0 || occlusion_uv.y > 1.0) {
+                break;
+            }
+            if (texture(image_occlusion, occlusion_uv).r > 0.5) {
+                out_distance = t;
+                break;
+            }
+        }
+    }
+"#;
+
+pub(crate) const FRAGMENT_DIRECTIONAL_LIGHT_SHADER_SOURCE : &str = r#"
+    #version 420 core
+    out vec4 out_color;
+
+    #include "tpixel_lighting.glsl"
+
+    in vec2 UV;
+    in vec2 WORLD_POSITION;
+
+    uniform vec4 light_color;
+    uniform vec4 light_direction;
+
+    void main() {
+        GBufferSample gbuffer = sample_gbuffer(UV);
+
+        float light_intensity = light_direction.w;
+        vec3 light_color = light_color.rgb * light_color.a * light_intensity;
+        vec3 dir = normalize(-light_direction.xyz);
+
+        out_color.rgb = shade(gbuffer, dir, light_color);
+        out_color.a = 0.0;
+    }
+"#;
+
+pub(crate) const FRAGMENT_SPOT_LIGHT_SHADER_SOURCE : &str = r#"
+    #version 420 core
+    out vec4 out_color;
+
+    #include "tpixel_lighting.glsl"
+
+    in vec2 UV;
+    in vec2 WORLD_POSITION;
+
+    uniform vec4 light_color;
+    uniform vec4 light_position;
+    uniform vec4 light_cone;
+
+    void main() {
+        GBufferSample gbuffer = sample_gbuffer(UV);
+
+        float light_range = light_position.w;
+        vec3 world_pos = vec3(WORLD_POSITION.xy, gbuffer.height);
+        float linear_attenuation = (light_range - length(world_pos - light_position.xyz)) / light_range; 
+        float attenuation = pow(max(0.0, linear_attenuation), 2.0);
+        vec3 light_color = light_color.rgb * light_color.a;
+
+        vec2 cone_dir = light_cone.xy;
+        float cos_inner = light_cone.z;
+        float cos_outer = light_cone.w;
+        vec2 to_pixel = world_pos.xy - light_position.xy;
+        float cos_angle = length(to_pixel) > 0.0 ? dot(normalize(to_pixel), cone_dir) : 1.0;
+        // smoothstep is undefined for an empty range, equal angles make a hard edged cone
+        float cone = cos_inner > cos_outer ? smoothstep(cos_outer, cos_inner, cos_angle) : step(cos_outer, cos_angle);
+
+        vec3 dir = normalize(light_position.xyz - world_pos);
+
+        out_color.rgb = shade(gbuffer, dir, light_color) * attenuation * cone;
+        out_color.a = 0.0;
+    }
+"#;
+
+pub(crate) const FRAGMENT_TONE_MAPPING_SHADER_SOURCE : &str = r#"
+    #version 420 core
+    out vec4 out_color;
+
+    layout(binding=0) uniform sampler2D image_hdr;
+    layout(binding=1) uniform sampler2D image_bloom;
+
+    in vec2 UV;
+    in vec2 WORLD_POSITION;
+
+    uniform float bloom_intensity;
+    uniform int tone_mapping_operator; // 0 = none, 1 = reinhard, 2 = aces, 3 = filmic
+    uniform float exposure;
+    uniform vec4 auto_exposure; // x = enabled, y = key, z = min exposure, w = max exposure
+    uniform float hdr_max_level;
+
+    vec3 reinhard(vec3 color) {
+        return color / (1.0 + color);
+    }
+
+    vec3 aces(vec3 color) {
+        // Narkowicz fit of the ACES reference curve
+        const float a = 2.51;
+        const float b = 0.03;
+        const float c = 2.43;
+        const float d = 0.59;
+        const float e = 0.14;
+        return clamp((color * (a * color + b)) / (color * (c * color + d) + e), 0.0, 1.0);
+    }
+
+    vec3 hable(vec3 x) {
+        const float A = 0.15;
+        const float B = 0.50;
+        const float C = 0.10;
+        const float D = 0.20;
+        const float E = 0.02;
+        const float F = 0.30;
+        return ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F;
+    }
+
+    vec3 filmic(vec3 color) {
+        const float WHITE_POINT = 11.2;
+        return hable(color * 2.0) / hable(vec3(WHITE_POINT));
+    }
+
+    void main() {
+        vec4 hdr_texel = texelFetch(image_hdr, ivec2(gl_FragCoord.xy), 0);
+        vec3 hdr = hdr_texel.rgb;
+
+        float scene_exposure = exposure;
+        if (auto_exposure.x > 0.5) {
+            // the smallest mip level holds the average of the whole lit scene
+            vec3 average = textureLod(image_hdr, vec2(0.5), hdr_max_level).rgb;
+            float average_luminance = max(dot(average, vec3(0.2126, 0.7152, 0.0722)), 0.0001);
+            scene_exposure *= clamp(auto_exposure.y / average_luminance, auto_exposure.z, auto_exposure.w);
+        }
+        hdr += texture(image_bloom, UV).rgb * bloom_intensity;
+        hdr *= scene_exposure;
+
+        vec3 mapped;
+        if (tone_mapping_operator == 1) {
+            mapped = reinhard(hdr);
+        } else if (tone_mapping_operator == 2) {
+            mapped = aces(hdr);
+        } else if (tone_mapping_operator == 3) {
+            mapped = filmic(hdr);
+        } else {
+            mapped = clamp(hdr, 0.0, 1.0);
+        }
+        out_color = vec4(mapped, hdr_texel.a);
+    }
+"#;
+
+pub(crate) const FRAGMENT_BLOOM_PREFILTER_SHADER_SOURCE : &str = r#"
+    #version 420 core
+    out vec4 out_color;
+
+    layout(binding=0) uniform sampler2D image_source;
+
+    in vec2 UV;
+    in vec2 WORLD_POSITION;
+
+    uniform float bloom_threshold;
+
+    void main() {
+        // the light buffer only has fresh mips with auto exposure, so stay on the base level
+        vec3 color = textureLod(image_source, UV, 0.0).rgb;
+        float brightness = max(color.r, max(color.g, color.b));
+        // soft knee so the threshold doesn't pop
+        float knee = bloom_threshold * 0.5;
+        float soft = clamp(brightness - bloom_threshold + knee, 0.0, 2.0 * knee);
+        soft = soft * soft / (4.0 * knee + 0.0001);
+        float contribution = max(soft, brightness - bloom_threshold) / max(brightness, 0.0001);
+        out_color = vec4(color * contribution, 1.0);
+    }
+"#;
+
+pub(crate) const FRAGMENT_BLOOM_DOWNSAMPLE_SHADER_SOURCE : &str = r#"
+    #version 420 core
+    out vec4 out_color;
+
+    layout(binding=0) uniform sampler2D image_source;
+
+    in vec2 UV;
+    in vec2 WORLD_POSITION;
+
+    uniform vec2 source_texel_size;
+
+    void main() {
+        vec2 offset = source_texel_size;
+        vec3 color = texture(image_source, UV + vec2(-offset.x, -offset.y)).rgb;
+        color += texture(image_source, UV + vec2( offset.x, -offset.y)).rgb;
+        color += texture(image_source, UV + vec2(-offset.x,  offset.y)).rgb;
+        color += texture(image_source, UV + vec2( offset.x,  offset.y)).rgb;
+        out_color = vec4(color * 0.25, 1.0);
+    }
+"#;
+
+pub(crate) const FRAGMENT_BLOOM_UPSAMPLE_SHADER_SOURCE : &str = r#"
+    #version 420 core
+    out vec4 out_color;
+
+    layout(binding=0) uniform sampler2D image_source;
+
+    in vec2 UV;
+    in vec2 WORLD_POSITION;
+
+    uniform vec2 source_texel_size;
+    uniform float bloom_radius;
+
+    void main() {
+        // 3x3 tent filter
+        vec2 offset = source_texel_size * bloom_radius;
+        vec3 color = texture(image_source, UV).rgb * 4.0;
+        color += texture(image_source, UV + vec2(-offset.x, 0.0)).rgb * 2.0;
+        color += texture(image_source, UV + vec2( offset.x, 0.0)).rgb * 2.0;
+        color += texture(image_source, UV + vec2(0.0, -offset.y)).rgb * 2.0;
+        color += texture(image_source, UV + vec2(0.0,  offset.y)).rgb * 2.0;
+        color += texture(image_source, UV + vec2(-offset.x, -offset.y)).rgb;
+        color += texture(image_source, UV + vec2( offset.x, -offset.y)).rgb;
+        color += texture(image_source, UV + vec2(-offset.x,  offset.y)).rgb;
+        color += texture(image_source, UV + vec2( offset.x,  offset.y)).rgb;
+        out_color = vec4(color / 16.0, 1.0);
+    }
+"#;
+
+pub(crate) const FRAGMENT_COMPOSITE_SHADER_SOURCE : &str = r#"
+    #version 420 core
+    out vec4 out_color;
+
+    layout(binding=0) uniform sampler2D image_source;
+
+    in vec2 UV;
+    in vec2 WORLD_POSITION;
+
+    void main() {
+        out_color = texture(image_source, UV);
+    }
+"#;
+
+pub(crate) const FRAGMENT_OVERLAY_SHADER_SOURCE : &str = r#"
+    #version 420 core
+    out vec4 out_color;
+
+    in vec2 UV;
+    in vec4 COLOR;
+
+    layout(binding=0) uniform sampler2D image_color;
+
+    void main() {
+        out_color = COLOR * texture(image_color, UV);
+    }
+"#;
+
+// steps gpu simulated particles, captured by transform feedback into the next state and the Sprite each is drawn as,
+// the same integration as ParticleEmitter::update_cpu
+pub(crate) const VERTEX_PARTICLE_SIMULATION_SHADER_SOURCE : &str = r#"
+    #version 420 core
+    layout (location = 0) in vec2 in_position;
+    layout (location = 1) in vec2 in_velocity;
+    layout (location = 2) in float in_age;
+    layout (location = 3) in float in_lifetime;
+
+    out vec2 out_position;
+    out vec2 out_velocity;
+    out float out_age;
+    out float out_lifetime;
+
+    out vec2 out_transform_x;
+    out vec2 out_transform_y;
+    out vec2 out_transform_origin;
+    out vec2 out_pivot;
+    out vec4 out_color;
+    out vec4 out_uv_rect;
+    out float out_z;
+    out float out_height;
+    out float out_height_map_scale;
+    flat out uint out_material_id;
+    flat out uint out_layer;
+    flat out uint out_flags;
+
+    uniform float delta_time;
+    uniform vec2 gravity;
+    uniform vec4 uv_rect;
+    uniform float z;
+    uniform float height;
+    uniform uint material_id;
+    uniform uint layer;
+    // the curves sampled at evenly spaced lives, PARTICLE_CURVE_SAMPLES of them
+    uniform vec4 color_over_life[32];
+    uniform float size_over_life[32];
+
+    vec4 sample_color(float life) {
+        float x = clamp(life, 0.0, 1.0) * 31.0;
+        int i = min(int(x), 30);
+        return mix(color_over_life[i], color_over_life[i + 1], x - float(i));
+    }
+    float sample_size(float life) {
+        float x = clamp(life, 0.0, 1.0) * 31.0;
+        int i = min(int(x), 30);
+        return mix(size_over_life[i], size_over_life[i + 1], x - float(i));
+    }
+
+    void main() {
+        vec2 position = in_position;
+        vec2 velocity = in_velocity;
+        float age = in_age;
+        if (age < 0.0) {
+            // spawned since the last step, drawn where it spawned first
+            age = 0.0;
+        } else if (age < in_lifetime) {
+            age += delta_time;
+            if (age < in_lifetime) {
+                velocity += gravity * delta_time;
+                position += velocity * delta_time;
+            }
+        }
+        out_position = position;
+        out_velocity = velocity;
+        out_age = age;
+        out_lifetime = in_lifetime;
+
+        bool alive = age < in_lifetime;
+        float life = alive ? age / in_lifetime : 1.0;
+        // dead slots are drawn as a zero sized quad that covers nothing
+        float size = alive ? sample_size(life) : 0.0;
+        out_transform_x = vec2(size, 0.0);
+        out_transform_y = vec2(0.0, size);
+        out_transform_origin = position;
+        out_pivot = vec2(0.5);
+        out_color = sample_color(life);
+        out_uv_rect = uv_rect;
+        out_z = z;
+        out_height = height;
+        out_height_map_scale = 0.0;
+        out_material_id = material_id;
+        out_layer = layer;
+        out_flags = 1u;
+    }
+"#;
+
+// the state buffer gets the first four, the instance buffer the Sprite after gl_NextBuffer
+pub(crate) const PARTICLE_SIMULATION_VARYINGS : [&str; 17] = [
+    "out_position", "out_velocity", "out_age", "out_lifetime", "gl_NextBuffer",
+    "out_transform_x", "out_transform_y", "out_transform_origin", "out_pivot", "out_color", "out_uv_rect",
+    "out_z", "out_height", "out_height_map_scale", "out_material_id", "out_layer", "out_flags",
+];
+
+pub(crate) const VERTEX_DEBUG_SHADER_SOURCE : &str = r#"
+    #version 420 core
+    layout (location = 0) in vec2 vertex_pos;
+    layout (location = 1) in vec4 vertex_color;
+
+    out vec4 COLOR;
+
+    #include "tpixel_frame.glsl"
+
+    void main() {
+        vec2 view_pos = frame.camera_transform * vec3(vertex_pos, 1.0f);
+        view_pos /= frame.camera_view;
+        gl_Position = vec4(view_pos.x, view_pos.y, 0.0, 1.0);
+        COLOR = vertex_color;
+    }
+"#;
+
+pub(crate) const FRAGMENT_DEBUG_SHADER_SOURCE : &str = r#"
+    #version 420 core
+    out vec4 out_color;
+
+    in vec4 COLOR;
+
+    void main() {
+        out_color = COLOR;
+    }
+"#;
+
+pub(crate) fn geometry_fragment_source(surface_source : &str) -> String {
+    format!("{}{}{}", FRAGMENT_GEO_SHADER_HEADER, surface_source, FRAGMENT_GEO_SHADER_MAIN)
+}
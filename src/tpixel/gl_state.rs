@@ -0,0 +1,160 @@
+use gl::types::*;
+
+const TRACKED_TEXTURE_UNITS : usize = 8;
+
+// the gl state the renderer last set, None is unknown and always gets set,
+// calls that would set what is already there are skipped and counted
+pub(crate) struct GlState {
+    program : Option<u32>,
+    vertex_array : Option<u32>,
+    framebuffer : Option<u32>,
+    active_texture_unit : Option<u32>,
+    textures : [Option<u32>; TRACKED_TEXTURE_UNITS], // TEXTURE_2D on each unit
+    blend : Option<bool>,
+    blend_func : Option<(GLenum, GLenum, GLenum, GLenum)>,
+    depth_test : Option<bool>,
+    viewport : Option<(i32, i32, i32, i32)>,
+    issued_calls : u32,
+    skipped_calls : u32,
+}
+
+impl GlState {
+    pub fn new() -> GlState {
+        GlState {
+            program : None,
+            vertex_array : None,
+            framebuffer : None,
+            active_texture_unit : None,
+            textures : [None; TRACKED_TEXTURE_UNITS],
+            blend : None,
+            blend_func : None,
+            depth_test : None,
+            viewport : None,
+            issued_calls : 0,
+            skipped_calls : 0,
+        }
+    }
+    // after gl was used behind the tracker's back, like creating textures or building framebuffers
+    pub fn invalidate(&mut self) {
+        self.program = None;
+        self.vertex_array = None;
+        self.framebuffer = None;
+        self.active_texture_unit = None;
+        self.textures = [None; TRACKED_TEXTURE_UNITS];
+        self.blend = None;
+        self.blend_func = None;
+        self.depth_test = None;
+        self.viewport = None;
+    }
+    // what a pass can count on and what the rest of the engine gets back between frames:
+    // the window framebuffer, nothing bound, depth test on and blending off
+    pub fn restore_defaults(&mut self, window_width : i32, window_height : i32) {
+        self.use_program(0);
+        self.bind_vertex_array(0);
+        self.bind_framebuffer(0);
+        self.set_viewport(0, 0, window_width, window_height);
+        self.set_blend(false);
+        self.set_depth_test(true);
+    }
+    pub fn reset_counters(&mut self) {
+        self.issued_calls = 0;
+        self.skipped_calls = 0;
+    }
+    pub fn issued_calls(&self) -> u32 {
+        self.issued_calls
+    }
+    pub fn skipped_calls(&self) -> u32 {
+        self.skipped_calls
+    }
+    // true and counted as issued when the state changes
+    fn change<T : PartialEq + Copy>(current : &mut Option<T>, value : T, issued_calls : &mut u32, skipped_calls : &mut u32) -> bool {
+        if *current == Some(value) {
+            *skipped_calls += 1;
+            return false;
+        }
+        *current = Some(value);
+        *issued_calls += 1;
+        true
+    }
+    pub fn use_program(&mut self, program : u32) {
+        if GlState::change(&mut self.program, program, &mut self.issued_calls, &mut self.skipped_calls) {
+            unsafe {
+                gl::UseProgram(program);
+            }
+        }
+    }
+    pub fn bind_vertex_array(&mut self, vertex_array : u32) {
+        if GlState::change(&mut self.vertex_array, vertex_array, &mut self.issued_calls, &mut self.skipped_calls) {
+            unsafe {
+                gl::BindVertexArray(vertex_array);
+            }
+        }
+    }
+    pub fn bind_framebuffer(&mut self, framebuffer : u32) {
+        if GlState::change(&mut self.framebuffer, framebuffer, &mut self.issued_calls, &mut self.skipped_calls) {
+            unsafe {
+                gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+            }
+        }
+    }
+    // only switches the active unit when the texture isn't already bound there
+    pub fn bind_texture(&mut self, unit : u32, texture : u32) {
+        let index = unit as usize;
+        if index < TRACKED_TEXTURE_UNITS && self.textures[index] == Some(texture) {
+            self.skipped_calls += 1;
+            return;
+        }
+        if GlState::change(&mut self.active_texture_unit, unit, &mut self.issued_calls, &mut self.skipped_calls) {
+            unsafe {
+                gl::ActiveTexture(gl::TEXTURE0 + unit);
+            }
+        }
+        if index < TRACKED_TEXTURE_UNITS {
+            self.textures[index] = Some(texture);
+        }
+        self.issued_calls += 1;
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, texture);
+        }
+    }
+    pub fn set_blend(&mut self, blend : bool) {
+        if GlState::change(&mut self.blend, blend, &mut self.issued_calls, &mut self.skipped_calls) {
+            unsafe {
+                if blend {
+                    gl::Enable(gl::BLEND);
+                } else {
+                    gl::Disable(gl::BLEND);
+                }
+            }
+        }
+    }
+    pub fn set_blend_func(&mut self, source : GLenum, destination : GLenum) {
+        self.set_blend_func_separate(source, destination, source, destination);
+    }
+    pub fn set_blend_func_separate(&mut self, source_rgb : GLenum, destination_rgb : GLenum, source_alpha : GLenum, destination_alpha : GLenum) {
+        let blend_func = (source_rgb, destination_rgb, source_alpha, destination_alpha);
+        if GlState::change(&mut self.blend_func, blend_func, &mut self.issued_calls, &mut self.skipped_calls) {
+            unsafe {
+                gl::BlendFuncSeparate(source_rgb, destination_rgb, source_alpha, destination_alpha);
+            }
+        }
+    }
+    pub fn set_depth_test(&mut self, depth_test : bool) {
+        if GlState::change(&mut self.depth_test, depth_test, &mut self.issued_calls, &mut self.skipped_calls) {
+            unsafe {
+                if depth_test {
+                    gl::Enable(gl::DEPTH_TEST);
+                } else {
+                    gl::Disable(gl::DEPTH_TEST);
+                }
+            }
+        }
+    }
+    pub fn set_viewport(&mut self, x : i32, y : i32, width : i32, height : i32) {
+        if GlState::change(&mut self.viewport, (x, y, width, height), &mut self.issued_calls, &mut self.skipped_calls) {
+            unsafe {
+                gl::Viewport(x, y, width, height);
+            }
+        }
+    }
+}
@@ -1,5 +1,8 @@
 use crate::tpixel::material_info::MaterialInfo;
 use crate::tpixel::texture_factory::TextureFactory;
+use crate::tpixel::resource_error::ResourceError;
+use crate::tpixel::vector2::Vector2;
+use crate::tpixel::render_backend::RenderBackend;
 
 pub struct MaterialFactory {
     next_id : u32,
@@ -11,16 +14,47 @@ impl MaterialFactory {
             next_id : 0u32
         }
     }
-    pub fn new_material(&mut self, texture_factory : &mut TextureFactory, color_path : &str, material_path : &str, normal_path : &str) -> MaterialInfo {
-        let color = texture_factory.new_texture(color_path);
+    pub fn new_material(&mut self, backend : &mut dyn RenderBackend, texture_factory : &mut TextureFactory, color_path : &str, material_path : &str, normal_path : &str) -> MaterialInfo {
+        let color = texture_factory.new_texture(backend, color_path);
         let material_info = MaterialInfo {
             id : self.next_id,
             color : color.id,
             size : color.size,
-            material : texture_factory.new_texture(material_path).id,
-            normal : texture_factory.new_texture(normal_path).id,
+            shader_id : None,
+            material : texture_factory.new_texture(backend, material_path).id,
+            normal : texture_factory.new_texture(backend, normal_path).id,
         };
         self.next_id += 1u32;
         material_info
     }
+    // a material for a color texture that is already loaded, with flat normals and no specular
+    pub fn new_color_material(&mut self, backend : &mut dyn RenderBackend, texture_factory : &mut TextureFactory, color : u32, size : Vector2) -> MaterialInfo {
+        let material_info = MaterialInfo {
+            id : self.next_id,
+            color : color,
+            size : size,
+            shader_id : None,
+            material : texture_factory.new_solid_texture(backend, "tpixel_lit_material", [0, 0, 0, 255]).id,
+            normal : texture_factory.new_solid_texture(backend, "tpixel_flat_normal", [128, 128, 255, 0]).id,
+        };
+        self.next_id += 1u32;
+        material_info
+    }
+    // a material showing what a camera renders, the image is already lit so it is drawn unlit
+    pub fn new_render_material(&mut self, backend : &mut dyn RenderBackend, texture_factory : &mut TextureFactory, name : &str, width : i32, height : i32) -> Result<MaterialInfo, ResourceError> {
+        let (color, size) = {
+            let color = texture_factory.new_render_texture(backend, name, width, height)?;
+            (color.id, color.size)
+        };
+        let material_info = MaterialInfo {
+            id : self.next_id,
+            color : color,
+            size : size,
+            shader_id : None,
+            material : texture_factory.new_solid_texture(backend, "tpixel_unlit_material", [0, 0, 0, 0]).id,
+            normal : texture_factory.new_solid_texture(backend, "tpixel_flat_normal", [128, 128, 255, 0]).id,
+        };
+        self.next_id += 1u32;
+        Ok(material_info)
+    }
 }
@@ -4,7 +4,8 @@ use crate::tpixel::vector2::Vector2;
 pub struct MaterialInfo {
     pub id : u32,
     pub color : u32,
-    pub material : u32,
+    pub material : u32, // r = specular, g = gloss, b = emissive, a = lit mask, 0 is unlit
     pub normal : u32,
     pub size : Vector2,
+    pub shader_id : Option<u32>, // a material shader drawing it into the gbuffer instead of the default one
 }
@@ -0,0 +1,120 @@
+use crate::tpixel::shader_factory::ShaderFactory;
+use crate::tpixel::shader_program::ShaderProgram;
+use crate::tpixel::uniform_value::UniformValue;
+use crate::tpixel::vector2::Vector2;
+use crate::tpixel::color::Color;
+use crate::tpixel::shader_error::ShaderError;
+use crate::tpixel::gl_shaders::{VERTEX_GEO_SHADER_SOURCE, geometry_fragment_source};
+
+use gl::types::*;
+
+// a geometry pass shader, the template around the surface function takes care of the gbuffer outputs
+pub(crate) struct MaterialShader {
+    pub shader : ShaderProgram,
+    pub shader_image_size : i32,
+    pub param_defaults : Vec<MaterialParam>, // what the uniforms a material can set start as in this program
+    pub surface_source : String, // kept to build it again when the shaders are reloaded
+}
+
+pub(crate) struct MaterialParam {
+    pub name : String,
+    pub location : i32, // in the material's current shader
+    pub value : UniformValue,
+}
+
+impl MaterialShader {
+    pub fn new(shader_factory : &ShaderFactory, surface_source : &str) -> Result<MaterialShader, ShaderError> {
+        let mut material_shader = MaterialShader {
+            shader : build_program(shader_factory, surface_source)?,
+            shader_image_size : 0,
+            param_defaults : Vec::new(),
+            surface_source : surface_source.to_string(),
+        };
+        material_shader.query_uniform_locations();
+        Ok(material_shader)
+    }
+    // keeps the current program if the new one fails to build,
+    // with changed it is only rebuilt if geometry.vert or one of its includes changed
+    pub fn rebuild(&mut self, shader_factory : &ShaderFactory, changed : Option<&[String]>) -> Result<(), ShaderError> {
+        if let Some(changed) = changed {
+            if !self.shader.depends_on(changed) {
+                return Ok(());
+            }
+        }
+        let shader = build_program(shader_factory, &self.surface_source)?;
+        self.shader.drop();
+        self.shader = shader;
+        self.query_uniform_locations();
+        Ok(())
+    }
+    fn query_uniform_locations(&mut self) {
+        self.shader_image_size = self.shader.get_uniform_location("image_size");
+        self.param_defaults = query_param_defaults(&self.shader);
+    }
+    pub fn drop(&mut self) {
+        self.shader.drop();
+    }
+}
+
+// geometry.vert from the shader directory if there is one, the surface source is always the material's own
+fn build_program(shader_factory : &ShaderFactory, surface_source : &str) -> Result<ShaderProgram, ShaderError> {
+    let build = || -> Result<ShaderProgram, ShaderError> {
+        let vertex_source = shader_factory.load_source("geometry.vert", VERTEX_GEO_SHADER_SOURCE)?;
+        shader_factory.new_program(&vertex_source, &geometry_fragment_source(surface_source))
+    };
+    let shader = build().map_err(|error| error.for_program("material shader"))?;
+    let mut dependencies = vec!["geometry.vert".to_string()];
+    dependencies.extend(shader.dependencies().iter().cloned());
+    Ok(shader.with_dependencies(dependencies))
+}
+// the uniforms outside blocks a material param can set, with the values they have after linking,
+// the image size and samplers are set by the renderer and left out
+pub(crate) fn query_param_defaults(shader : &ShaderProgram) -> Vec<MaterialParam> {
+    let mut param_defaults : Vec<MaterialParam> = Vec::new();
+    if !shader.is_linked() {
+        return param_defaults;
+    }
+    unsafe {
+        let mut uniform_count : GLint = 0;
+        gl::GetProgramiv(shader.get_id(), gl::ACTIVE_UNIFORMS, &mut uniform_count);
+        for index in 0..uniform_count as u32 {
+            let mut name = [0u8; 256];
+            let mut length : GLsizei = 0;
+            let mut size : GLint = 0;
+            let mut uniform_type : GLenum = 0;
+            gl::GetActiveUniform(shader.get_id(), index, name.len() as GLsizei, &mut length, &mut size, &mut uniform_type, name.as_mut_ptr() as *mut GLchar);
+            let name = String::from_utf8_lossy(&name[..length as usize]).to_string();
+            let location = shader.get_uniform_location(&name);
+            if location < 0 || name == "image_size" {
+                continue;
+            }
+            let mut values = [0f32; 4];
+            let value = match uniform_type {
+                gl::INT => {
+                    let mut value : GLint = 0;
+                    gl::GetUniformiv(shader.get_id(), location, &mut value);
+                    UniformValue::Int(value)
+                },
+                gl::FLOAT => {
+                    gl::GetUniformfv(shader.get_id(), location, values.as_mut_ptr());
+                    UniformValue::Float(values[0])
+                },
+                gl::FLOAT_VEC2 => {
+                    gl::GetUniformfv(shader.get_id(), location, values.as_mut_ptr());
+                    UniformValue::Vector2(Vector2 { x : values[0], y : values[1] })
+                },
+                gl::FLOAT_VEC4 => {
+                    gl::GetUniformfv(shader.get_id(), location, values.as_mut_ptr());
+                    UniformValue::Color(Color { r : values[0], g : values[1], b : values[2], a : values[3] })
+                },
+                _ => continue,
+            };
+            param_defaults.push(MaterialParam {
+                name : name,
+                location : location,
+                value : value,
+            });
+        }
+    }
+    param_defaults
+}
@@ -50,7 +50,7 @@ impl Matrix3x2 {
         Matrix3x2 {
             elements : [
                 c, s,
-                -c, s,
+                -s, c,
                 0f32, 0f32,
             ]
         }
@@ -85,6 +85,12 @@ impl Matrix3x2 {
             y : self.elements[5],
         }
     }
+    pub fn transform_point(&self, point : Vector2) -> Vector2 {
+        Vector2 {
+            x : self.elements[0] * point.x + self.elements[2] * point.y + self.elements[4],
+            y : self.elements[1] * point.x + self.elements[3] * point.y + self.elements[5],
+        }
+    }
     fn mul(matrix_a : &Matrix3x2, matrix_b : &Matrix3x2) -> Matrix3x2 {
         let a = &matrix_a.elements;
         let b = &matrix_b.elements;
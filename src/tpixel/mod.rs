@@ -5,11 +5,29 @@ pub mod registry;
 pub mod sparse_map;
 pub mod sprite;
 pub mod point_light;
+pub mod directional_light;
+pub mod spot_light;
+pub mod shadow_caster;
+pub mod tilemap;
+pub mod nine_slice;
+pub mod particle_emitter;
+pub mod text;
+pub mod debug_draw;
 pub mod vector2;
+pub mod vector3;
 pub mod color;
 pub mod matrix3x2;
 pub mod rect;
 pub mod camera;
+pub mod tone_mapping;
+pub mod bloom;
+pub mod uniform_value;
+pub mod shader_error;
+pub mod resource_error;
+pub mod shader_quality;
+pub mod render_backend;
+pub mod cpu_backend;
+pub mod gl_backend;
 
 // unlikely to mess with
 mod sprite_factory;
@@ -18,7 +36,14 @@ mod texture_factory;
 mod texture_info;
 mod material_factory;
 mod material_info;
+mod material_shader;
+mod frame_data;
+mod gl_state;
+mod font;
+mod font_factory;
 mod renderer;
 mod deferred_renderer;
+mod gl_shaders;
 mod shader_factory;
 mod shader_program;
+mod post_process;
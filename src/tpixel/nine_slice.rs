@@ -0,0 +1,148 @@
+use crate::tpixel::vector2::Vector2;
+use crate::tpixel::color::Color;
+use crate::tpixel::matrix3x2::Matrix3x2;
+use crate::tpixel::rect::Rect;
+use crate::tpixel::sprite::{Sprite, SPRITE_FLIP_X, SPRITE_FLIP_Y};
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum NineSliceMode {
+    Stretch, // the edges and center are scaled to fit
+    Tile, // the edges and center repeat at their texel size, the last one is cut off
+}
+
+// a sprite drawn at any size, the corners keep their texel size while the rest fills the gap
+pub struct NineSlice {
+    pub transform : Matrix3x2,
+    pub size : Vector2, // in pixels before the transform
+    pub pivot : Vector2,
+    pub color : Color,
+    pub uv_rect : Rect,
+    // insets from the edges of uv_rect in texels
+    pub border_left : f32,
+    pub border_right : f32,
+    pub border_bottom : f32,
+    pub border_top : f32,
+    pub mode : NineSliceMode,
+    pub z : f32,
+    pub height : f32,
+    pub height_map_scale : f32,
+    pub layer : u32, // below MAX_LAYERS (32), like Sprite::layer
+    pub flags : u32, // the same bits as Sprite::flags, nine slices cast no shadows so SPRITE_CASTS_SHADOW does nothing
+    pub(crate) material_id : u32,
+}
+
+// a run of one row or column, position and size in pixels and the uv range it shows
+struct Segment {
+    position : f32,
+    size : f32,
+    uv_begin : f32,
+    uv_end : f32,
+}
+
+impl NineSlice {
+    pub(crate) fn clone(&self) -> NineSlice {
+        NineSlice {
+            transform : self.transform,
+            size : self.size,
+            pivot : self.pivot,
+            color : self.color,
+            uv_rect : self.uv_rect,
+            border_left : self.border_left,
+            border_right : self.border_right,
+            border_bottom : self.border_bottom,
+            border_top : self.border_top,
+            mode : self.mode,
+            z : self.z,
+            height : self.height,
+            height_map_scale : self.height_map_scale,
+            layer : self.layer,
+            flags : self.flags,
+            material_id : self.material_id,
+        }
+    }
+    pub fn set_border(&mut self, left : f32, right : f32, bottom : f32, top : f32) {
+        self.border_left = left;
+        self.border_right = right;
+        self.border_bottom = bottom;
+        self.border_top = top;
+    }
+    // one sprite per piece, image_size is the size of the material's textures
+    pub(crate) fn build_instances(&self, image_size : Vector2, sprites : &mut Vec<Sprite>) {
+        let columns = self.segments(self.size.x, image_size.x, self.uv_rect.begin.x, self.uv_rect.end.x,
+            self.border_left, self.border_right, self.flags & SPRITE_FLIP_X != 0);
+        let rows = self.segments(self.size.y, image_size.y, self.uv_rect.begin.y, self.uv_rect.end.y,
+            self.border_bottom, self.border_top, self.flags & SPRITE_FLIP_Y != 0);
+        let origin = Vector2 { x : self.size.x * self.pivot.x, y : self.size.y * self.pivot.y };
+        for row in rows.iter() {
+            for column in columns.iter() {
+                // the quad is as big as its uv range in texels, scale it to the segment
+                let texels = Vector2 {
+                    x : (column.uv_end - column.uv_begin) * image_size.x,
+                    y : (row.uv_end - row.uv_begin) * image_size.y,
+                };
+                let scale = Vector2 { x : column.size / texels.x, y : row.size / texels.y };
+                let position = self.transform.transform_point(Vector2 { x : column.position - origin.x, y : row.position - origin.y });
+                let elements = &self.transform.elements;
+                sprites.push(Sprite {
+                    transform : Matrix3x2 { elements : [
+                        elements[0] * scale.x, elements[1] * scale.x,
+                        elements[2] * scale.y, elements[3] * scale.y,
+                        position.x, position.y,
+                    ] },
+                    pivot : Vector2::new(),
+                    color : self.color,
+                    uv_rect : Rect {
+                        begin : Vector2 { x : column.uv_begin, y : row.uv_begin },
+                        end : Vector2 { x : column.uv_end, y : row.uv_end },
+                    },
+                    z : self.z,
+                    height : self.height,
+                    height_map_scale : self.height_map_scale,
+                    material_id : self.material_id,
+                    layer : self.layer,
+                    flags : self.flags,
+                });
+            }
+        }
+    }
+    // splits one axis into the two borders and the middle, mirrored when the axis is flipped
+    fn segments(&self, size : f32, image_size : f32, uv_begin : f32, uv_end : f32, border_begin : f32, border_end : f32, flip : bool) -> Vec<Segment> {
+        let mut segments : Vec<Segment> = Vec::new();
+        if size <= 0f32 || image_size <= 0f32 {
+            return segments;
+        }
+        // borders that don't fit shrink together
+        let fit = if border_begin + border_end > size { size / (border_begin + border_end) } else { 1f32 };
+        let (begin_size, end_size) = (border_begin * fit, border_end * fit);
+        let middle_uv_begin = uv_begin + border_begin / image_size;
+        let middle_uv_end = uv_end - border_end / image_size;
+        let middle_size = size - begin_size - end_size;
+
+        segments.push(Segment { position : 0f32, size : begin_size, uv_begin : uv_begin, uv_end : middle_uv_begin });
+        let middle_texels = (middle_uv_end - middle_uv_begin) * image_size;
+        if self.mode == NineSliceMode::Tile && middle_texels > 0f32 {
+            let mut position = 0f32;
+            while position < middle_size {
+                let tile_size = middle_texels.min(middle_size - position);
+                segments.push(Segment {
+                    position : begin_size + position,
+                    size : tile_size,
+                    uv_begin : middle_uv_begin,
+                    uv_end : middle_uv_begin + tile_size / image_size,
+                });
+                position += tile_size;
+            }
+        } else {
+            segments.push(Segment { position : begin_size, size : middle_size, uv_begin : middle_uv_begin, uv_end : middle_uv_end });
+        }
+        segments.push(Segment { position : size - end_size, size : end_size, uv_begin : middle_uv_end, uv_end : uv_end });
+
+        segments.retain(|segment| segment.size > 0f32 && segment.uv_end > segment.uv_begin);
+        if flip {
+            for segment in segments.iter_mut() {
+                segment.position = size - segment.position - segment.size;
+            }
+        }
+        segments
+    }
+}
@@ -0,0 +1,319 @@
+use crate::tpixel::vector2::Vector2;
+use crate::tpixel::color::Color;
+use crate::tpixel::rect::Rect;
+use crate::tpixel::matrix3x2::Matrix3x2;
+use crate::tpixel::sprite::{Sprite, SPRITE_VISIBLE};
+
+use rand::Rng;
+use rand::rngs::ThreadRng;
+
+pub const PARTICLE_CURVE_SAMPLES : usize = 32; // same as in the particle simulation shader
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum ParticleBlend {
+    Lit, // written to the gbuffer like sprites
+    Additive, // added to the lit scene unlit, so it also feeds bloom
+}
+
+pub struct ParticleEmitter {
+    pub position : Vector2,
+    pub emitting : bool,
+    pub spawn_rate : f32, // particles per second while emitting
+    pub max_particles : usize,
+    pub lifetime_min : f32,
+    pub lifetime_max : f32,
+    pub velocity_min : Vector2,
+    pub velocity_max : Vector2,
+    pub gravity : Vector2,
+    // (life 0 to 1, value) keys sorted by life, linearly interpolated, empty means white and 1
+    pub color_over_life : Vec<(f32, Color)>,
+    pub size_over_life : Vec<(f32, f32)>,
+    pub uv_rect : Rect,
+    pub z : f32,
+    pub height : f32,
+    pub layer : u32, // below MAX_LAYERS (32), like Sprite::layer
+    pub blend : ParticleBlend,
+    // simulates on the gpu through transform feedback, the cpu still spawns them,
+    // falls back to the cpu where the renderer can't, switching either way clears the particles
+    pub gpu_simulation : bool,
+    pub(crate) material_id : u32,
+
+    // one entry per live particle in each, empty while simulated on the gpu
+    positions : Vec<Vector2>,
+    velocities : Vec<Vector2>,
+    ages : Vec<f32>,
+    lifetimes : Vec<f32>,
+    spawn_accumulator : f32,
+    pending_burst : usize,
+    gpu : Option<GpuParticles>,
+}
+
+// a particle written into a slot of the gpu state buffer, laid out like the simulation shader's inputs
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct ParticleSpawn {
+    pub position : Vector2,
+    pub velocity : Vector2,
+    pub age : f32, // negative for a new particle, it stays where it spawned for its first frame
+    pub lifetime : f32,
+}
+
+// what the cpu keeps of a gpu simulated emitter, the renderer simulates once per step
+pub(crate) struct GpuParticles {
+    pub spawns : Vec<(usize, ParticleSpawn)>, // slot and particle, from the last update
+    pub delta_time : f32, // of the last update
+    pub step : u32,
+    pub generation : u32, // bumped when the slots are emptied, the renderer zeroes its buffers then
+    death_times : Vec<f32>, // one per slot, on the clock below
+    clock : f32,
+    next_slot : usize,
+}
+
+impl GpuParticles {
+    fn new() -> GpuParticles {
+        GpuParticles {
+            spawns : Vec::new(),
+            delta_time : 0f32,
+            step : 0,
+            generation : 0,
+            death_times : Vec::new(),
+            clock : 0f32,
+            next_slot : 0,
+        }
+    }
+    pub fn capacity(&self) -> usize {
+        self.death_times.len()
+    }
+    fn reset(&mut self, capacity : usize) {
+        self.spawns.clear();
+        self.death_times = vec![0f32; capacity];
+        self.clock = 0f32;
+        self.next_slot = 0;
+        self.generation = self.generation.wrapping_add(1);
+    }
+    // the first free slot from the one after the last spawn, slots are reused round robin
+    fn free_slot(&self) -> Option<usize> {
+        let capacity = self.capacity();
+        (0..capacity).map(|i| (self.next_slot + i) % capacity).find(|&slot| self.death_times[slot] <= self.clock)
+    }
+}
+
+impl ParticleEmitter {
+    pub fn new(material_id : u32) -> ParticleEmitter {
+        ParticleEmitter {
+            position : Vector2::new(),
+            emitting : true,
+            spawn_rate : 10f32,
+            max_particles : 1024,
+            lifetime_min : 1f32,
+            lifetime_max : 1f32,
+            velocity_min : Vector2 { x : -16f32, y : -16f32 },
+            velocity_max : Vector2 { x : 16f32, y : 16f32 },
+            gravity : Vector2::new(),
+            color_over_life : Vec::new(),
+            size_over_life : Vec::new(),
+            uv_rect : Rect::new_uv(),
+            z : 0f32,
+            height : 0f32,
+            layer : 0,
+            blend : ParticleBlend::Lit,
+            gpu_simulation : false,
+            material_id : material_id,
+            positions : Vec::new(),
+            velocities : Vec::new(),
+            ages : Vec::new(),
+            lifetimes : Vec::new(),
+            spawn_accumulator : 0f32,
+            pending_burst : 0,
+            gpu : None,
+        }
+    }
+    // spawns the particles on the next update, even if the emitter isn't emitting
+    pub fn burst(&mut self, count : usize) {
+        self.pending_burst += count;
+    }
+    pub fn particle_count(&self) -> usize {
+        match &self.gpu {
+            Some(gpu) => gpu.death_times.iter().filter(|&&death_time| death_time > gpu.clock).count(),
+            None => self.positions.len(),
+        }
+    }
+    pub fn clear(&mut self) {
+        self.positions.clear();
+        self.velocities.clear();
+        self.ages.clear();
+        self.lifetimes.clear();
+        if let Some(gpu) = self.gpu.as_mut() {
+            gpu.reset(self.max_particles);
+        }
+    }
+    // gpu_available is whether the renderer can simulate on the gpu, see Engine
+    pub fn update(&mut self, delta_time : f32, gpu_available : bool) {
+        let use_gpu = self.gpu_simulation && gpu_available;
+        if use_gpu != self.gpu.is_some() {
+            self.gpu = if use_gpu { Some(GpuParticles::new()) } else { None };
+            self.clear();
+        }
+        match self.gpu.take() {
+            Some(mut gpu) => {
+                self.update_gpu(&mut gpu, delta_time);
+                self.gpu = Some(gpu);
+            },
+            None => self.update_cpu(delta_time),
+        }
+    }
+    pub(crate) fn gpu_particles(&self) -> Option<&GpuParticles> {
+        self.gpu.as_ref()
+    }
+    fn update_cpu(&mut self, delta_time : f32) {
+        // age and remove the dead ones, swap remove keeps the arrays packed
+        let mut i = 0;
+        while i < self.ages.len() {
+            self.ages[i] += delta_time;
+            if self.ages[i] >= self.lifetimes[i] {
+                self.positions.swap_remove(i);
+                self.velocities.swap_remove(i);
+                self.ages.swap_remove(i);
+                self.lifetimes.swap_remove(i);
+            } else {
+                i += 1;
+            }
+        }
+        for (position, velocity) in self.positions.iter_mut().zip(self.velocities.iter_mut()) {
+            velocity.x += self.gravity.x * delta_time;
+            velocity.y += self.gravity.y * delta_time;
+            position.x += velocity.x * delta_time;
+            position.y += velocity.y * delta_time;
+        }
+
+        let spawn_count = self.take_spawn_count(delta_time);
+        let mut rng = rand::thread_rng();
+        for _ in 0..spawn_count {
+            if self.positions.len() >= self.max_particles {
+                break;
+            }
+            self.positions.push(self.position);
+            self.velocities.push(self.random_velocity(&mut rng));
+            self.ages.push(0f32);
+            self.lifetimes.push(self.random_lifetime(&mut rng));
+        }
+    }
+    // only keeps track of which slots are alive, the renderer moves the particles
+    fn update_gpu(&mut self, gpu : &mut GpuParticles, delta_time : f32) {
+        if gpu.capacity() != self.max_particles {
+            gpu.reset(self.max_particles);
+        }
+        gpu.spawns.clear();
+        gpu.clock += delta_time;
+        gpu.delta_time = delta_time;
+        gpu.step = gpu.step.wrapping_add(1);
+
+        let spawn_count = self.take_spawn_count(delta_time);
+        let mut rng = rand::thread_rng();
+        for _ in 0..spawn_count {
+            let slot = match gpu.free_slot() {
+                Some(slot) => slot,
+                None => break,
+            };
+            let lifetime = self.random_lifetime(&mut rng);
+            gpu.death_times[slot] = gpu.clock + lifetime;
+            gpu.next_slot = (slot + 1) % gpu.capacity();
+            gpu.spawns.push((slot, ParticleSpawn {
+                position : self.position,
+                velocity : self.random_velocity(&mut rng),
+                age : -1f32,
+                lifetime : lifetime,
+            }));
+        }
+    }
+    fn take_spawn_count(&mut self, delta_time : f32) -> usize {
+        let mut spawn_count = self.pending_burst;
+        self.pending_burst = 0;
+        if self.emitting {
+            self.spawn_accumulator += self.spawn_rate * delta_time;
+            spawn_count += self.spawn_accumulator as usize;
+            self.spawn_accumulator = self.spawn_accumulator.fract();
+        }
+        spawn_count
+    }
+    fn random_velocity(&self, rng : &mut ThreadRng) -> Vector2 {
+        Vector2 {
+            x : lerp(self.velocity_min.x, self.velocity_max.x, rng.gen::<f32>()),
+            y : lerp(self.velocity_min.y, self.velocity_max.y, rng.gen::<f32>()),
+        }
+    }
+    fn random_lifetime(&self, rng : &mut ThreadRng) -> f32 {
+        lerp(self.lifetime_min, self.lifetime_max, rng.gen::<f32>()).max(0.001f32)
+    }
+    // the curves at PARTICLE_CURVE_SAMPLES evenly spaced lives, for the simulation shader
+    pub(crate) fn sample_curves(&self) -> ([[f32; 4]; PARTICLE_CURVE_SAMPLES], [f32; PARTICLE_CURVE_SAMPLES]) {
+        let mut colors = [[0f32; 4]; PARTICLE_CURVE_SAMPLES];
+        let mut sizes = [0f32; PARTICLE_CURVE_SAMPLES];
+        for i in 0..PARTICLE_CURVE_SAMPLES {
+            let life = i as f32 / (PARTICLE_CURVE_SAMPLES - 1) as f32;
+            let color = sample_color(&self.color_over_life, life);
+            colors[i] = [color.r, color.g, color.b, color.a];
+            sizes[i] = sample_size(&self.size_over_life, life);
+        }
+        (colors, sizes)
+    }
+    pub(crate) fn build_instances(&self, sprites : &mut Vec<Sprite>) {
+        for i in 0..self.positions.len() {
+            let life = self.ages[i] / self.lifetimes[i];
+            let size = sample_size(&self.size_over_life, life);
+            let position = self.positions[i];
+            sprites.push(Sprite {
+                transform : Matrix3x2 { elements : [size, 0f32, 0f32, size, position.x, position.y] },
+                pivot : Vector2 { x : 0.5f32, y : 0.5f32 },
+                color : sample_color(&self.color_over_life, life),
+                uv_rect : self.uv_rect,
+                z : self.z,
+                height : self.height,
+                height_map_scale : 0f32,
+                material_id : self.material_id,
+                layer : self.layer,
+                flags : SPRITE_VISIBLE,
+            });
+        }
+    }
+}
+
+fn lerp(a : f32, b : f32, t : f32) -> f32 {
+    a + (b - a) * t
+}
+// index of the key at or after life and how far between it and the previous one life is
+fn find_key<T>(keys : &[(f32, T)], life : f32) -> (usize, f32) {
+    let next = keys.iter().position(|key| key.0 >= life).unwrap_or(keys.len() - 1);
+    if next == 0 || keys[next].0 < life {
+        return (next, 1f32);
+    }
+    let (previous_life, next_life) = (keys[next - 1].0, keys[next].0);
+    let t = if next_life > previous_life { (life - previous_life) / (next_life - previous_life) } else { 1f32 };
+    (next, t)
+}
+fn sample_size(keys : &[(f32, f32)], life : f32) -> f32 {
+    if keys.is_empty() {
+        return 1f32;
+    }
+    let (next, t) = find_key(keys, life);
+    if next == 0 {
+        return keys[0].1;
+    }
+    lerp(keys[next - 1].1, keys[next].1, t)
+}
+fn sample_color(keys : &[(f32, Color)], life : f32) -> Color {
+    if keys.is_empty() {
+        return Color { r : 1f32, g : 1f32, b : 1f32, a : 1f32 };
+    }
+    let (next, t) = find_key(keys, life);
+    if next == 0 {
+        return keys[0].1;
+    }
+    let (a, b) = (keys[next - 1].1, keys[next].1);
+    Color {
+        r : lerp(a.r, b.r, t),
+        g : lerp(a.g, b.g, t),
+        b : lerp(a.b, b.b, t),
+        a : lerp(a.a, b.a, t),
+    }
+}
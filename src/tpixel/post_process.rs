@@ -0,0 +1,81 @@
+use crate::tpixel::render_backend::{RenderBackend, BackendGBuffer};
+use crate::tpixel::shader_factory::ShaderFactory;
+use crate::tpixel::uniform_value::UniformValue;
+use crate::tpixel::shader_error::ShaderError;
+use crate::tpixel::resource_error::ResourceError;
+
+struct PostEffect {
+    name : String,
+    enabled : bool,
+    effect : u32, // the backend's, rebuilt by it when the shaders are reloaded
+    params : Vec<(String, UniformValue)>,
+}
+
+pub(crate) struct PostProcessStack {
+    effects : Vec<PostEffect>,
+}
+
+impl PostProcessStack {
+    pub fn new() -> PostProcessStack {
+        PostProcessStack {
+            effects : Vec::new(),
+        }
+    }
+    pub fn drop(&mut self, backend : &mut dyn RenderBackend) {
+        for effect in self.effects.iter() {
+            backend.delete_post_effect(effect.effect);
+        }
+        self.effects.clear();
+    }
+
+    // an effect that fails to build leaves the stack as it was
+    pub fn add_effect(&mut self, backend : &mut dyn RenderBackend, shader_factory : &ShaderFactory, name : &str, fragment_source : &str, params : &[(&str, UniformValue)]) -> Result<(), ShaderError> {
+        let effect = backend.create_post_effect(shader_factory, name, fragment_source)?;
+        self.remove_effect(backend, name);
+        self.effects.push(PostEffect {
+            name : name.to_string(),
+            enabled : true,
+            effect : effect,
+            params : params.iter().map(|(param_name, value)| (param_name.to_string(), *value)).collect(),
+        });
+        Ok(())
+    }
+    pub fn remove_effect(&mut self, backend : &mut dyn RenderBackend, name : &str) {
+        if let Some(index) = self.effects.iter().position(|effect| effect.name == name) {
+            let effect = self.effects.remove(index);
+            backend.delete_post_effect(effect.effect);
+        }
+    }
+    pub fn set_effect_enabled(&mut self, name : &str, enabled : bool) {
+        for effect in self.effects.iter_mut().filter(|effect| effect.name == name) {
+            effect.enabled = enabled;
+        }
+    }
+    pub fn is_effect_enabled(&self, name : &str) -> bool {
+        self.effects.iter().any(|effect| effect.name == name && effect.enabled)
+    }
+    pub fn set_effect_param(&mut self, name : &str, param_name : &str, value : UniformValue) {
+        for effect in self.effects.iter_mut().filter(|effect| effect.name == name) {
+            match effect.params.iter_mut().find(|(own_name, _)| own_name == param_name) {
+                Some(param) => param.1 = value,
+                None => effect.params.push((param_name.to_string(), value)),
+            }
+        }
+    }
+    pub fn has_enabled_effects(&self) -> bool {
+        self.effects.iter().any(|effect| effect.enabled)
+    }
+    // the scene should be tone mapped into the first buffer before calling render
+    pub fn render(&self, backend : &mut dyn RenderBackend, buffers : [u32; 2], scene : u32, gbuffer : &BackendGBuffer, time : f32, output : u32) -> Result<(), ResourceError> {
+        let enabled_count = self.effects.iter().filter(|effect| effect.enabled).count();
+        let mut source = 0usize;
+        for (index, effect) in self.effects.iter().filter(|effect| effect.enabled).enumerate() {
+            // ping pong between the buffers, the last effect goes straight to the output
+            let target = if index + 1 == enabled_count { output } else { buffers[1 - source] };
+            backend.begin_pass(&[target], &[], false)?;
+            backend.draw_post_effect(effect.effect, buffers[source], scene, gbuffer, &effect.params, time);
+            source = 1 - source;
+        }
+        Ok(())
+    }
+}
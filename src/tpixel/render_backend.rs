@@ -0,0 +1,190 @@
+use crate::tpixel::shader_factory::ShaderFactory;
+use crate::tpixel::shader_error::ShaderError;
+use crate::tpixel::shader_quality::ShaderQuality;
+use crate::tpixel::resource_error::ResourceError;
+use crate::tpixel::uniform_value::UniformValue;
+use crate::tpixel::point_light::PointLight;
+use crate::tpixel::spot_light::SpotLight;
+use crate::tpixel::directional_light::DirectionalLight;
+use crate::tpixel::tone_mapping::ToneMapping;
+use crate::tpixel::particle_emitter::{ParticleSpawn, PARTICLE_CURVE_SAMPLES};
+use crate::tpixel::sprite::Sprite;
+use crate::tpixel::color::Color;
+use crate::tpixel::vector2::Vector2;
+use crate::tpixel::matrix3x2::Matrix3x2;
+use crate::tpixel::rect::Rect;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum TextureFormat {
+    Rgba8,
+    Rgba16F,
+    R8, // reads back as r, 0, 0, 1
+    R32F, // same
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum TextureFilter {
+    Nearest,
+    Linear,
+    LinearMipmaps, // the smaller levels are only made by the passes that read them
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum TextureWrap {
+    Repeat,
+    Clamp,
+    RepeatX, // around horizontally, clamped vertically
+}
+
+#[derive(Copy, Clone)]
+pub struct TextureDesc {
+    pub width : i32,
+    pub height : i32,
+    pub format : TextureFormat,
+    pub filter : TextureFilter,
+    pub wrap : TextureWrap,
+}
+
+// what tpixel_frame.glsl holds, set once per view
+#[derive(Copy, Clone)]
+pub struct BackendFrame {
+    pub camera_transform : Matrix3x2, // world to view
+    pub camera_transform_inverse : Matrix3x2, // view to world
+    pub camera_view : Vector2, // half the view size in world units
+    pub resolution : (i32, i32),
+    pub ambient : Color,
+    pub time : f32,
+}
+
+// textures and shader a sprite batch is drawn with
+#[derive(Copy, Clone)]
+pub struct BackendMaterial<'a> {
+    pub color : u32,
+    pub normal : u32,
+    pub material : u32, // r = specular, g = gloss, b = emissive, a = lit mask, 0 is unlit
+    pub size : Vector2,
+    pub shader : Option<u32>, // from create_material_shader, None is the built in surface
+    pub params : &'a [(String, UniformValue)], // set on the shader, the ones left out go back to the shader's defaults
+}
+
+// the textures the geometry pass writes and the lighting passes read
+#[derive(Copy, Clone)]
+pub struct BackendGBuffer {
+    pub buffer_width : i32,
+    pub buffer_height : i32,
+    pub color : u32,
+    pub normal : u32,
+    pub material : u32,
+    pub height : u32,
+    pub shadow_map : u32, // a row per shadow casting point light
+}
+
+// how a batch of sprites is drawn into the current pass
+#[derive(Copy, Clone, PartialEq)]
+pub enum SpritePass {
+    Geometry, // into the color, normal, material and height targets with the depth test
+    Additive, // added to an hdr target, its alpha is left alone
+    Overlay, // blended over the target by alpha
+    Occluder, // marks the pixels the color image covers in an occlusion target
+}
+
+// one step of a gpu simulated emitter, see ParticleEmitter for what they mean
+pub struct ParticleSimulation {
+    pub delta_time : f32,
+    pub gravity : Vector2,
+    pub uv_rect : Rect,
+    pub z : f32,
+    pub height : f32,
+    pub material_id : u32,
+    pub layer : u32,
+    pub colors : [[f32; 4]; PARTICLE_CURVE_SAMPLES],
+    pub sizes : [f32; PARTICLE_CURVE_SAMPLES],
+}
+
+// what the deferred renderer needs from a graphics api, ids are the backend's own and 0 is none
+pub trait RenderBackend {
+    // pixels are rgba8 rows starting at v = 0, None leaves the texture zeroed
+    fn create_texture(&mut self, desc : &TextureDesc, pixels : Option<&[u8]>) -> u32;
+    fn read_texture(&mut self, texture : u32) -> Vec<[f32; 4]>;
+    fn delete_texture(&mut self, texture : u32);
+    fn create_buffer(&mut self, size : usize) -> u32;
+    fn write_buffer(&mut self, buffer : u32, offset : usize, data : &[u8]);
+    fn delete_buffer(&mut self, buffer : u32);
+
+    // adds the built in includes and builds the built in shaders, the ones that failed from the shader directory
+    fn init(&mut self, shader_factory : &mut ShaderFactory) -> Vec<ShaderError>;
+    // everything when changed is None, otherwise only what was made from the changed files
+    fn build_shaders(&mut self, shader_factory : &mut ShaderFactory, changed : Option<&[String]>) -> Vec<ShaderError>;
+    // the quality stays as it was when anything fails to build
+    fn set_shader_quality(&mut self, shader_factory : &mut ShaderFactory, shader_quality : ShaderQuality) -> Vec<ShaderError>;
+    fn create_material_shader(&mut self, shader_factory : &ShaderFactory, surface_source : &str) -> Result<u32, ShaderError>;
+    fn create_post_effect(&mut self, shader_factory : &ShaderFactory, name : &str, fragment_source : &str) -> Result<u32, ShaderError>;
+    fn delete_post_effect(&mut self, effect : u32);
+
+    // the window is cleared to opaque black and the state change counters start over
+    fn begin_frame(&mut self, window_width : i32, window_height : i32);
+    fn end_frame(&mut self);
+    // the state changes made in the last frame and the redundant ones that were skipped
+    fn state_changes(&self) -> (u32, u32);
+    // what the last frame left in the window, rows starting at the bottom
+    fn read_window(&mut self) -> Vec<[f32; 4]>;
+
+    // draws go into the targets until the next pass, no targets is the window, each target with a clear color
+    // is cleared, the depth buffer is cleared along with them when depth_test is on, the viewport covers the first target
+    fn begin_pass(&mut self, targets : &[u32], clear_colors : &[[f32; 4]], depth_test : bool) -> Result<(), ResourceError>;
+    fn set_viewport(&mut self, x : i32, y : i32, width : i32, height : i32);
+    fn set_frame(&mut self, frame : &BackendFrame);
+
+    // count Sprites read from the start of the instance buffer
+    fn draw_sprites(&mut self, pass : SpritePass, material : &BackendMaterial, instance_buffer : u32, count : usize);
+    // world space triangles, 2 floats a vertex, into an occlusion target
+    fn draw_polygons(&mut self, vertices : &[f32]);
+    // world space lines, a position and an rgba color a vertex, blended over the target
+    fn draw_lines(&mut self, vertices : &[f32]);
+    // the distance to the closest occluder around the light into the viewport's row
+    fn draw_shadow_map(&mut self, occlusion : u32, light : &PointLight, shadow_map_size : f32);
+    // replaces the target with the ambient lit gbuffer
+    fn draw_ambience(&mut self, gbuffer : &BackendGBuffer);
+    // the lights add to the target, shadow is the light's row in the shadow map, the softness in texels
+    // and the shadow map width, a row below 0 is unshadowed
+    fn draw_point_light(&mut self, gbuffer : &BackendGBuffer, light : &PointLight, shadow : [f32; 4]);
+    fn draw_spot_light(&mut self, gbuffer : &BackendGBuffer, light : &SpotLight);
+    fn draw_directional_light(&mut self, gbuffer : &BackendGBuffer, light : &DirectionalLight);
+    fn draw_bloom_prefilter(&mut self, source : u32, threshold : f32);
+    fn draw_bloom_downsample(&mut self, source : u32);
+    // adds to the target
+    fn draw_bloom_upsample(&mut self, source : u32, radius : f32);
+    fn draw_tone_mapping(&mut self, scene : u32, bloom : u32, tone_mapping : &ToneMapping, bloom_intensity : f32);
+    fn draw_post_effect(&mut self, effect : u32, source : u32, scene : u32, gbuffer : &BackendGBuffer, params : &[(String, UniformValue)], time : f32);
+    // stretches the source over the viewport, only writing inside the scissor rect, blended by alpha or replacing
+    fn draw_composite(&mut self, source : u32, viewport : (i32, i32, i32, i32), scissor : (i32, i32, i32, i32), blend : bool);
+
+    // without it emitters simulate on the cpu
+    fn supports_particle_simulation(&self) -> bool;
+    fn create_particle_system(&mut self, capacity : usize) -> u32;
+    fn delete_particle_system(&mut self, system : u32);
+    // writes the spawns into their slots and steps every slot once
+    fn simulate_particles(&mut self, system : u32, spawns : &[(usize, ParticleSpawn)], simulation : &ParticleSimulation);
+    // a Sprite per slot as of the last step, for draw_sprites
+    fn particle_instances(&self, system : u32) -> u32;
+
+    // frees everything the backend made
+    fn drop(&mut self);
+}
+
+// the largest difference of any channel, for comparing what two backends read back
+pub fn max_difference(a : &[[f32; 4]], b : &[[f32; 4]]) -> f32 {
+    if a.len() != b.len() {
+        return f32::INFINITY;
+    }
+    a.iter().zip(b.iter())
+        .flat_map(|(texel_a, texel_b)| texel_a.iter().zip(texel_b.iter()).map(|(x, y)| (x - y).abs()))
+        .fold(0f32, f32::max)
+}
+
+// Sprite is repr(C) and all 4 byte fields, so it goes to the backends as is
+pub(crate) fn sprite_bytes(sprites : &[Sprite]) -> &[u8] {
+    unsafe {
+        std::slice::from_raw_parts(sprites.as_ptr() as *const u8, std::mem::size_of::<Sprite>() * sprites.len())
+    }
+}
@@ -2,29 +2,104 @@ use crate::tpixel::registry::Registry;
 use crate::tpixel::material_info::MaterialInfo;
 use crate::tpixel::camera::Camera;
 use crate::tpixel::color::Color;
+use crate::tpixel::tone_mapping::ToneMapping;
+use crate::tpixel::bloom::Bloom;
+use crate::tpixel::uniform_value::UniformValue;
+use crate::tpixel::shader_error::ShaderError;
+use crate::tpixel::shader_quality::ShaderQuality;
 use crate::tpixel::deferred_renderer::DeferredRenderer;
 use crate::tpixel::shader_factory::ShaderFactory;
+use crate::tpixel::font::FontInfo;
+use crate::tpixel::debug_draw::DebugDraw;
+use crate::tpixel::resource_error::ResourceError;
+use crate::tpixel::render_backend::RenderBackend;
 
 pub struct Renderer {
     deferred_renderer : DeferredRenderer,
 }
 
 impl Renderer {
-    pub fn new() -> Renderer {
+    pub fn new(backend : Box<dyn RenderBackend>) -> Renderer {
         Renderer {
-            deferred_renderer : DeferredRenderer::new(),
+            deferred_renderer : DeferredRenderer::new(backend),
         }
     }
+    pub fn drop(&mut self) {
+        self.deferred_renderer.drop();
+    }
+    pub fn backend_mut(&mut self) -> &mut dyn RenderBackend {
+        self.deferred_renderer.backend_mut()
+    }
     pub fn prepare_material(&mut self, material_info : &MaterialInfo) {
         self.deferred_renderer.prepare_material(material_info);
     }
-    pub fn init(&mut self, shader_factory : &ShaderFactory) {
-        self.deferred_renderer.init(shader_factory);
+    pub fn add_material_shader(&mut self, shader_factory : &ShaderFactory, surface_source : &str) -> Result<u32, ShaderError> {
+        self.deferred_renderer.add_material_shader(shader_factory, surface_source)
+    }
+    pub fn set_material_shader(&mut self, material_id : u32, shader_id : Option<u32>) {
+        self.deferred_renderer.set_material_shader(material_id, shader_id);
+    }
+    pub fn set_material_param(&mut self, material_id : u32, param_name : &str, value : UniformValue) {
+        self.deferred_renderer.set_material_param(material_id, param_name, value);
+    }
+    pub fn prepare_font(&mut self, font_info : FontInfo) {
+        self.deferred_renderer.prepare_font(font_info);
+    }
+    pub fn prepare_render_target(&mut self, material_info : &MaterialInfo) {
+        self.deferred_renderer.prepare_render_target(material_info);
+    }
+    pub fn free_render_target(&mut self, material_id : u32) -> Option<MaterialInfo> {
+        self.deferred_renderer.free_render_target(material_id)
+    }
+    pub fn init(&mut self, shader_factory : &mut ShaderFactory) -> Vec<ShaderError> {
+        self.deferred_renderer.init(shader_factory)
+    }
+    pub fn build_shaders(&mut self, shader_factory : &mut ShaderFactory) -> Vec<ShaderError> {
+        self.deferred_renderer.build_shaders(shader_factory)
+    }
+    pub fn reload_shaders(&mut self, shader_factory : &mut ShaderFactory, changed : &[String]) -> Vec<ShaderError> {
+        self.deferred_renderer.reload_shaders(shader_factory, changed)
+    }
+    pub fn set_shader_quality(&mut self, shader_factory : &mut ShaderFactory, shader_quality : ShaderQuality) -> Vec<ShaderError> {
+        self.deferred_renderer.set_shader_quality(shader_factory, shader_quality)
     }
     pub fn resize_framebuffer(&mut self, width : i32, height : i32) {
         self.deferred_renderer.resize_geo_buffer(width, height);
     }
-    pub fn render(&mut self, registry : &Registry, camera : &Camera, ambient_color : &Color) {
-        self.deferred_renderer.render(registry, camera, ambient_color);
+    pub fn set_internal_resolution(&mut self, internal_resolution : Option<(i32, i32)>, subpixel_smoothing : bool) {
+        self.deferred_renderer.set_internal_resolution(internal_resolution, subpixel_smoothing);
+    }
+    pub fn get_internal_resolution(&self) -> Option<(i32, i32)> {
+        self.deferred_renderer.get_internal_resolution()
+    }
+    pub fn supports_gpu_particles(&self) -> bool {
+        self.deferred_renderer.supports_gpu_particles()
+    }
+    pub fn render(&mut self, registry : &Registry, camera : &Camera, ambient_color : &Color, tone_mapping : &ToneMapping, bloom : &Bloom, time : f32, debug_draw : &DebugDraw) -> Vec<ResourceError> {
+        self.deferred_renderer.render(registry, camera, ambient_color, tone_mapping, bloom, time, debug_draw)
+    }
+    pub fn read_window(&mut self) -> Vec<[f32; 4]> {
+        self.deferred_renderer.read_window()
+    }
+    pub fn get_issued_gl_calls(&self) -> u32 {
+        self.deferred_renderer.get_issued_gl_calls()
+    }
+    pub fn get_skipped_gl_calls(&self) -> u32 {
+        self.deferred_renderer.get_skipped_gl_calls()
+    }
+    pub fn add_post_effect(&mut self, shader_factory : &ShaderFactory, name : &str, fragment_source : &str, params : &[(&str, UniformValue)]) -> Result<(), ShaderError> {
+        self.deferred_renderer.add_post_effect(shader_factory, name, fragment_source, params)
+    }
+    pub fn remove_post_effect(&mut self, name : &str) {
+        self.deferred_renderer.remove_post_effect(name);
+    }
+    pub fn set_post_effect_enabled(&mut self, name : &str, enabled : bool) {
+        self.deferred_renderer.post_process.set_effect_enabled(name, enabled);
+    }
+    pub fn is_post_effect_enabled(&self, name : &str) -> bool {
+        self.deferred_renderer.post_process.is_effect_enabled(name)
+    }
+    pub fn set_post_effect_param(&mut self, name : &str, param_name : &str, value : UniformValue) {
+        self.deferred_renderer.post_process.set_effect_param(name, param_name, value);
     }
 }
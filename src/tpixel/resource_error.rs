@@ -0,0 +1,25 @@
+use std::fmt;
+
+// a texture, font or render target that could not be made, shaders report through ShaderError
+#[derive(Clone, Debug)]
+pub struct ResourceError {
+    pub name : String, // the file path, or the name it was asked for by
+    pub message : String,
+}
+
+impl ResourceError {
+    pub(crate) fn new(name : &str, message : &str) -> ResourceError {
+        ResourceError {
+            name : name.to_string(),
+            message : message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ResourceError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.message)
+    }
+}
+
+impl std::error::Error for ResourceError {}
@@ -0,0 +1,151 @@
+use std::fmt;
+
+const SHADER_ERROR_CONTEXT_LINES : usize = 2; // shown before and after the offending line
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ShaderStage {
+    Preprocess,
+    Vertex,
+    Fragment,
+    Link,
+}
+
+// one message from the info log that points at a line of the source
+#[derive(Clone, Debug)]
+pub struct ShaderErrorLine {
+    pub file : String, // the include it is in, or <source> for the shader itself
+    pub line : usize, // 1 based like the driver reports it
+    pub message : String,
+    pub context : String, // the surrounding source with line numbers, the offending one marked with >
+}
+
+#[derive(Clone, Debug)]
+pub struct ShaderError {
+    pub program : String, // the files it was built from, like "fullscreen.vert point_light.frag", empty for sources given directly
+    pub stage : ShaderStage,
+    pub log : String, // the whole info log as the driver wrote it
+    pub lines : Vec<ShaderErrorLine>,
+}
+
+impl ShaderError {
+    // files are the (name, source) pairs the shader was put together from, in source string order
+    pub(crate) fn new(stage : ShaderStage, log : &str, files : &[(String, String)]) -> ShaderError {
+        let lines = log.lines()
+            .filter_map(|log_line| {
+                let (file_index, line) = parse_log_line(log_line)?;
+                let (file, source) = files.get(file_index)?;
+                Some(ShaderErrorLine {
+                    file : file.clone(),
+                    line : line,
+                    message : log_line.trim().to_string(),
+                    context : annotate_source(source, line),
+                })
+            })
+            .collect();
+        ShaderError {
+            program : String::new(),
+            stage : stage,
+            log : log.to_string(),
+            lines : lines,
+        }
+    }
+    pub(crate) fn new_preprocess(message : &str, file : &(String, String), line : usize) -> ShaderError {
+        ShaderError {
+            program : String::new(),
+            stage : ShaderStage::Preprocess,
+            log : format!("{}:{}: {}", file.0, line, message),
+            lines : vec![ShaderErrorLine {
+                file : file.0.clone(),
+                line : line,
+                message : message.to_string(),
+                context : annotate_source(&file.1, line),
+            }],
+        }
+    }
+    // a file in the shader directory that is there but can't be read
+    pub(crate) fn new_read(file : &str, message : &str) -> ShaderError {
+        ShaderError {
+            program : String::new(),
+            stage : ShaderStage::Preprocess,
+            log : format!("{}: {}", file, message),
+            lines : Vec::new(),
+        }
+    }
+    pub(crate) fn for_program(mut self, program : &str) -> ShaderError {
+        self.program = program.to_string();
+        self
+    }
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        let stage = match self.stage {
+            ShaderStage::Preprocess => "shader failed to preprocess",
+            ShaderStage::Vertex => "vertex shader failed to compile",
+            ShaderStage::Fragment => "fragment shader failed to compile",
+            ShaderStage::Link => "shader program failed to link",
+        };
+        if self.program.is_empty() {
+            writeln!(f, "{}", stage)?;
+        } else {
+            writeln!(f, "{} ({})", stage, self.program)?;
+        }
+        if self.lines.is_empty() {
+            return write!(f, "{}", self.log.trim_end());
+        }
+        for error_line in self.lines.iter() {
+            writeln!(f, "{} line {}: {}", error_line.file, error_line.line, error_line.message)?;
+            write!(f, "{}", error_line.context)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+// the source string and line a log line is about, drivers disagree on the format:
+// nvidia "0(12) : error C0000: ...", mesa "0:12(5): error: ...",
+// amd, intel and apple "ERROR: 0:12: ..."
+fn parse_log_line(log_line : &str) -> Option<(usize, usize)> {
+    let mut rest = log_line.trim_start();
+    for prefix in ["ERROR:", "WARNING:", "error:", "warning:"].iter() {
+        if let Some(stripped) = rest.strip_prefix(prefix) {
+            rest = stripped.trim_start();
+            break;
+        }
+    }
+    let (file_index, rest) = split_number(rest)?;
+    let rest = if let Some(rest) = rest.strip_prefix('(') {
+        let (line, rest) = split_number(rest)?;
+        return if rest.starts_with(')') { Some((file_index, line)) } else { None };
+    } else {
+        rest.strip_prefix(':')?
+    };
+    let (line, rest) = split_number(rest)?;
+    if rest.starts_with(':') || rest.starts_with('(') {
+        Some((file_index, line))
+    } else {
+        None
+    }
+}
+
+fn split_number(text : &str) -> Option<(usize, &str)> {
+    let end = text.find(|c : char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let number = text[..end].parse::<usize>().ok()?;
+    Some((number, &text[end..]))
+}
+
+fn annotate_source(source : &str, line : usize) -> String {
+    let source_lines : Vec<&str> = source.lines().collect();
+    let mut context = String::new();
+    if line == 0 || line > source_lines.len() {
+        return context;
+    }
+    let first = line.saturating_sub(SHADER_ERROR_CONTEXT_LINES).max(1);
+    let last = (line + SHADER_ERROR_CONTEXT_LINES).min(source_lines.len());
+    for number in first..=last {
+        let marker = if number == line { '>' } else { ' ' };
+        context.push_str(&format!("{} {:>4} | {}\n", marker, number, source_lines[number - 1]));
+    }
+    context
+}
@@ -1,67 +1,300 @@
 use crate::tpixel::shader_program::ShaderProgram;
+use crate::tpixel::shader_error::{ShaderError, ShaderStage};
 
 use gl::types::*;
 use std::ffi::CString;
-use std::str;
 use std::ptr;
+use std::path::PathBuf;
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+const SHADER_SOURCE_NAME : &str = "<source>";
 
 pub struct ShaderFactory {
+    // files in here with the name of a built in shader or include are used instead of it
+    shader_directory : Option<PathBuf>,
+    modified_times : HashMap<PathBuf, SystemTime>,
+    includes : HashMap<String, String>,
+    // programs built with new_program_from_files for a define set not in use, keyed by variant_key
+    variants : HashMap<String, ShaderProgram>,
+}
 
+// a source after #include and #define, with the files it was put together from in source string order
+struct PreprocessedSource {
+    text : String,
+    files : Vec<(String, String)>,
 }
 
 impl ShaderFactory {
     pub fn new() -> ShaderFactory {
-        ShaderFactory {}
-    }
-    pub fn new_program(&self, vertex_shader_source : &str, fragment_shader_source : &str) -> ShaderProgram {
-        let vertex_shader;
-        let fragment_shader;
-        let shader_program;
-        unsafe {
-            vertex_shader = gl::CreateShader(gl::VERTEX_SHADER);
-            let c_str_vert = CString::new(vertex_shader_source.as_bytes()).unwrap();
-            gl::ShaderSource(vertex_shader, 1, &c_str_vert.as_ptr(), ptr::null());
-            gl::CompileShader(vertex_shader);
-            
-            let mut success = gl::FALSE as GLint;
-            let mut info_log = Vec::with_capacity(2048);
-            info_log.set_len(2048 - 1); // subtract 1 to skip the trailing null character
-            gl::GetShaderiv(vertex_shader, gl::COMPILE_STATUS, &mut success);
-            if success != gl::TRUE as GLint {
-                gl::GetShaderInfoLog(vertex_shader, 2000, ptr::null_mut(), info_log.as_mut_ptr() as *mut GLchar);
-                println!("ERROR::SHADER::VERTEX::COMPILATION_FAILED\n{}", str::from_utf8(&info_log).unwrap());
+        ShaderFactory {
+            shader_directory : None,
+            modified_times : HashMap::new(),
+            includes : HashMap::new(),
+            variants : HashMap::new(),
+        }
+    }
+    pub fn drop(&mut self) {
+        self.drop_variants(None);
+    }
+    // what #include "name" pulls in, unless the shader directory has a file with that name
+    pub fn add_include(&mut self, name : &str, source : &str) {
+        self.includes.insert(name.to_string(), source.to_string());
+    }
+    pub fn set_shader_directory(&mut self, directory : Option<&str>) {
+        self.shader_directory = directory.map(PathBuf::from);
+        self.modified_times = self.scan_directory();
+    }
+    pub fn has_shader_directory(&self) -> bool {
+        self.shader_directory.is_some()
+    }
+    // the file with that name in the shader directory if there is one, otherwise the built in source
+    pub fn load_source(&self, name : &str, default_source : &str) -> Result<String, ShaderError> {
+        Ok(self.load_file(name)?.unwrap_or_else(|| default_source.to_string()))
+    }
+    fn load_include(&self, name : &str) -> Result<Option<String>, ShaderError> {
+        Ok(self.load_file(name)?.or_else(|| self.includes.get(name).cloned()))
+    }
+    // None when there is no such file, an error when there is one that can't be read
+    fn load_file(&self, name : &str) -> Result<Option<String>, ShaderError> {
+        let path = match self.shader_directory.as_ref() {
+            Some(directory) => directory.join(name),
+            None => return Ok(None),
+        };
+        if !path.is_file() {
+            return Ok(None);
+        }
+        match std::fs::read_to_string(&path) {
+            Ok(source) => Ok(Some(source)),
+            Err(error) => Err(ShaderError::new_read(&path.display().to_string(), &error.to_string())),
+        }
+    }
+    // the names of the files in the shader directory added, changed or removed since the last poll
+    pub fn poll_changes(&mut self) -> Vec<String> {
+        let modified_times = self.scan_directory();
+        let mut changed : Vec<String> = modified_times.iter()
+            .filter(|(path, modified)| self.modified_times.get(*path) != Some(*modified))
+            .chain(self.modified_times.iter().filter(|(path, _)| !modified_times.contains_key(*path)))
+            .filter_map(|(path, _)| path.file_name().map(|name| name.to_string_lossy().to_string()))
+            .collect();
+        changed.sort_unstable();
+        changed.dedup();
+        self.modified_times = modified_times;
+        changed
+    }
+    fn scan_directory(&self) -> HashMap<PathBuf, SystemTime> {
+        let mut modified_times : HashMap<PathBuf, SystemTime> = HashMap::new();
+        let entries = match self.shader_directory.as_ref().and_then(|directory| std::fs::read_dir(directory).ok()) {
+            Some(entries) => entries,
+            None => return modified_times,
+        };
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            if let Ok(modified) = entry.metadata().and_then(|metadata| metadata.modified()) {
+                modified_times.insert(entry.path(), modified);
             }
-
-
-            fragment_shader = gl::CreateShader(gl::FRAGMENT_SHADER);
-            let c_str_frag = CString::new(fragment_shader_source.as_bytes()).unwrap();
-            gl::ShaderSource(fragment_shader, 1, &c_str_frag.as_ptr(), ptr::null());
-            gl::CompileShader(fragment_shader);
-            
-            let mut success = gl::FALSE as GLint;
-            let mut info_log = Vec::with_capacity(2048);
-            info_log.set_len(2048 - 1); // subtract 1 to skip the trailing null character
-            gl::GetShaderiv(fragment_shader, gl::COMPILE_STATUS, &mut success);
-            if success != gl::TRUE as GLint {
-                gl::GetShaderInfoLog(fragment_shader, 2000, ptr::null_mut(), info_log.as_mut_ptr() as *mut GLchar);
-                println!("ERROR::SHADER::FRAGMENT::COMPILATION_FAILED\n{}", str::from_utf8(&info_log).unwrap());
+        }
+        modified_times
+    }
+    pub fn new_program(&self, vertex_shader_source : &str, fragment_shader_source : &str) -> Result<ShaderProgram, ShaderError> {
+        self.new_program_with_defines(vertex_shader_source, fragment_shader_source, &[])
+    }
+    // defines are written after #version as #define, like "SHADOWS" or "SAMPLE_COUNT 4"
+    pub fn new_program_with_defines(&self, vertex_shader_source : &str, fragment_shader_source : &str, defines : &[&str]) -> Result<ShaderProgram, ShaderError> {
+        let vertex_source = self.preprocess(vertex_shader_source, defines)?;
+        let fragment_source = self.preprocess(fragment_shader_source, defines)?;
+        let vertex_shader = compile_shader(gl::VERTEX_SHADER, &vertex_source.text)
+            .map_err(|log| ShaderError::new(ShaderStage::Vertex, &log, &vertex_source.files))?;
+        let fragment_shader = match compile_shader(gl::FRAGMENT_SHADER, &fragment_source.text) {
+            Ok(fragment_shader) => fragment_shader,
+            Err(log) => {
+                unsafe {
+                    gl::DeleteShader(vertex_shader);
+                }
+                return Err(ShaderError::new(ShaderStage::Fragment, &log, &fragment_source.files));
+            },
+        };
+        let shader_program = link_program(&[vertex_shader, fragment_shader], &[])?;
+        let dependencies = include_names(&[&vertex_source, &fragment_source]);
+        Ok(ShaderProgram::new_init(vertex_shader, fragment_shader, shader_program).with_dependencies(dependencies))
+    }
+    // vertex and fragment are the file name in the shader directory and the built in source used without one,
+    // the program depends on both names whichever was used
+    pub fn new_program_from_files(&self, vertex : (&str, &str), fragment : (&str, &str), defines : &[&str]) -> Result<ShaderProgram, ShaderError> {
+        let program_name = format!("{} {}", vertex.0, fragment.0);
+        let build = || -> Result<ShaderProgram, ShaderError> {
+            let vertex_source = self.load_source(vertex.0, vertex.1)?;
+            let fragment_source = self.load_source(fragment.0, fragment.1)?;
+            self.new_program_with_defines(&vertex_source, &fragment_source, defines)
+        };
+        let program = build().map_err(|error| error.for_program(&program_name))?;
+        let mut dependencies = vec![vertex.0.to_string(), fragment.0.to_string()];
+        dependencies.extend(program.dependencies().iter().cloned());
+        Ok(program.with_dependencies(dependencies))
+    }
+    // the program for these files and defines, handed back from the variant cache if it was built before
+    pub(crate) fn take_variant(&mut self, vertex : (&str, &str), fragment : (&str, &str), defines : &[&str]) -> Result<ShaderProgram, ShaderError> {
+        match self.variants.remove(&variant_key(vertex.0, fragment.0, defines)) {
+            Some(program) => Ok(program),
+            None => self.new_program_from_files(vertex, fragment, defines),
+        }
+    }
+    // keeps a program built from these files and defines for take_variant
+    pub(crate) fn cache_variant(&mut self, vertex_name : &str, fragment_name : &str, defines : &[&str], program : ShaderProgram) {
+        if !program.is_linked() {
+            return;
+        }
+        if let Some(mut replaced) = self.variants.insert(variant_key(vertex_name, fragment_name, defines), program) {
+            replaced.drop();
+        }
+    }
+    // frees the cached variants built from any of the changed files, all of them without changed
+    pub(crate) fn drop_variants(&mut self, changed : Option<&[String]>) {
+        let stale : Vec<String> = self.variants.iter()
+            .filter(|(_, program)| changed.map_or(true, |changed| program.depends_on(changed)))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in stale {
+            if let Some(mut program) = self.variants.remove(&key) {
+                program.drop();
             }
+        }
+    }
+    // a vertex shader only program whose varyings are captured interleaved by transform feedback,
+    // "gl_NextBuffer" among them moves on to the next buffer binding, vertex is as for new_program_from_files
+    pub fn new_feedback_program(&self, vertex : (&str, &str), varyings : &[&str]) -> Result<ShaderProgram, ShaderError> {
+        let build = || -> Result<ShaderProgram, ShaderError> {
+            let vertex_source = self.preprocess(&self.load_source(vertex.0, vertex.1)?, &[])?;
+            let vertex_shader = compile_shader(gl::VERTEX_SHADER, &vertex_source.text)
+                .map_err(|log| ShaderError::new(ShaderStage::Vertex, &log, &vertex_source.files))?;
+            let shader_program = link_program(&[vertex_shader], varyings)?;
+            let mut dependencies = vec![vertex.0.to_string()];
+            dependencies.extend(include_names(&[&vertex_source]));
+            Ok(ShaderProgram::new_init(vertex_shader, 0, shader_program).with_dependencies(dependencies))
+        };
+        build().map_err(|error| error.for_program(vertex.0))
+    }
+    // every file gets its own source string number in #line so the driver's errors point back into it
+    fn preprocess(&self, source : &str, defines : &[&str]) -> Result<PreprocessedSource, ShaderError> {
+        let mut preprocessed = PreprocessedSource {
+            text : String::new(),
+            files : vec![(SHADER_SOURCE_NAME.to_string(), source.to_string())],
+        };
+        let define_lines : String = defines.iter().map(|define| format!("#define {}\n", define)).collect();
+        if source.lines().any(|line| line.trim_start().starts_with("#version")) {
+            self.expand(0, Some(&define_lines), &mut preprocessed)?;
+        } else {
+            preprocessed.text.push_str(&define_lines);
+            preprocessed.text.push_str("#line 1 0\n");
+            self.expand(0, None, &mut preprocessed)?;
+        }
+        Ok(preprocessed)
+    }
+    // each include is only pulled in once, later ones are left as empty lines
+    fn expand(&self, file_index : usize, define_lines : Option<&str>, preprocessed : &mut PreprocessedSource) -> Result<(), ShaderError> {
+        let source = preprocessed.files[file_index].1.clone();
+        for (index, line) in source.lines().enumerate() {
+            let number = index + 1;
+            let trimmed = line.trim_start();
+            let include = match trimmed.strip_prefix("#include") {
+                Some(include) => include.trim().trim_matches(|c| c == '"' || c == '<' || c == '>'),
+                None => {
+                    preprocessed.text.push_str(line);
+                    preprocessed.text.push('\n');
+                    if let (true, Some(define_lines)) = (trimmed.starts_with("#version"), define_lines) {
+                        preprocessed.text.push_str(define_lines);
+                        preprocessed.text.push_str(&format!("#line {} {}\n", number + 1, file_index));
+                    }
+                    continue;
+                },
+            };
+            if preprocessed.files.iter().any(|(name, _)| name == include) {
+                preprocessed.text.push('\n');
+                continue;
+            }
+            let include_source = match self.load_include(include)? {
+                Some(include_source) => include_source,
+                None => return Err(ShaderError::new_preprocess(&format!("include \"{}\" was not found", include), &preprocessed.files[file_index], number)),
+            };
+            let include_index = preprocessed.files.len();
+            preprocessed.files.push((include.to_string(), include_source));
+            preprocessed.text.push_str(&format!("#line 1 {}\n", include_index));
+            self.expand(include_index, None, preprocessed)?;
+            preprocessed.text.push_str(&format!("#line {} {}\n", number + 1, file_index));
+        }
+        Ok(())
+    }
+}
 
+// the order defines are given in doesn't make a different variant
+fn variant_key(vertex_name : &str, fragment_name : &str, defines : &[&str]) -> String {
+    let mut defines = defines.to_vec();
+    defines.sort_unstable();
+    defines.dedup();
+    format!("{}|{}|{}", vertex_name, fragment_name, defines.join(","))
+}
+// every include the sources pulled in, once
+fn include_names(sources : &[&PreprocessedSource]) -> Vec<String> {
+    let mut names : Vec<String> = Vec::new();
+    for source in sources.iter() {
+        for (name, _) in source.files.iter().skip(1) {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+    }
+    names
+}
+// the linked program, the shaders are deleted if it fails to link
+fn link_program(shaders : &[u32], varyings : &[&str]) -> Result<u32, ShaderError> {
+    let varyings : Vec<CString> = varyings.iter().filter_map(|varying| CString::new(*varying).ok()).collect();
+    let varying_pointers : Vec<*const GLchar> = varyings.iter().map(|varying| varying.as_ptr()).collect();
+    unsafe {
+        let shader_program = gl::CreateProgram();
+        for shader in shaders.iter() {
+            gl::AttachShader(shader_program, *shader);
+        }
+        if !varying_pointers.is_empty() {
+            gl::TransformFeedbackVaryings(shader_program, varying_pointers.len() as GLsizei, varying_pointers.as_ptr(), gl::INTERLEAVED_ATTRIBS);
+        }
+        gl::LinkProgram(shader_program);
 
-            shader_program = gl::CreateProgram();
-            gl::AttachShader(shader_program, vertex_shader);
-            gl::AttachShader(shader_program, fragment_shader);
-            gl::LinkProgram(shader_program);
-
-            let mut success = gl::FALSE as GLint;
-            let mut info_log = Vec::with_capacity(2048);
-            info_log.set_len(2048 - 1); // subtract 1 to skip the trailing null character
-            gl::GetProgramiv(shader_program, gl::LINK_STATUS, &mut success);
-            if success != gl::TRUE as GLint {
-                gl::GetProgramInfoLog(shader_program, 2000, ptr::null_mut(), info_log.as_mut_ptr() as *mut GLchar);
-                println!("ERROR::SHADER::PROGRAM::COMPILATION_FAILED\n{}", str::from_utf8(&info_log).unwrap());
+        let mut success = gl::FALSE as GLint;
+        gl::GetProgramiv(shader_program, gl::LINK_STATUS, &mut success);
+        if success != gl::TRUE as GLint {
+            let mut log_length : GLint = 0;
+            gl::GetProgramiv(shader_program, gl::INFO_LOG_LENGTH, &mut log_length);
+            let mut info_log = vec![0u8; log_length.max(1) as usize];
+            let mut length : GLsizei = 0;
+            gl::GetProgramInfoLog(shader_program, info_log.len() as GLsizei, &mut length, info_log.as_mut_ptr() as *mut GLchar);
+            gl::DeleteProgram(shader_program);
+            for shader in shaders.iter() {
+                gl::DeleteShader(*shader);
             }
+            // link errors don't point into any source
+            return Err(ShaderError::new(ShaderStage::Link, &String::from_utf8_lossy(&info_log[..length as usize]), &[]));
+        }
+        Ok(shader_program)
+    }
+}
+// the shader object, or its whole info log
+fn compile_shader(shader_type : GLenum, source : &str) -> Result<u32, String> {
+    let c_str = CString::new(source.as_bytes()).map_err(|_| "shader source contains a null character".to_string())?;
+    unsafe {
+        let shader = gl::CreateShader(shader_type);
+        gl::ShaderSource(shader, 1, &c_str.as_ptr(), ptr::null());
+        gl::CompileShader(shader);
+
+        let mut success = gl::FALSE as GLint;
+        gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut success);
+        if success != gl::TRUE as GLint {
+            let mut log_length : GLint = 0;
+            gl::GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut log_length);
+            let mut info_log = vec![0u8; log_length.max(1) as usize];
+            let mut length : GLsizei = 0;
+            gl::GetShaderInfoLog(shader, info_log.len() as GLsizei, &mut length, info_log.as_mut_ptr() as *mut GLchar);
+            gl::DeleteShader(shader);
+            return Err(String::from_utf8_lossy(&info_log[..length as usize]).to_string());
         }
-        ShaderProgram::new_init(vertex_shader, fragment_shader, shader_program)
+        Ok(shader)
     }
 }
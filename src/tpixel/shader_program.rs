@@ -5,6 +5,7 @@ pub struct ShaderProgram {
     vertex_shader : u32,
     fragment_shader : u32,
     shader_program : u32,
+    dependencies : Vec<String>, // the shader directory files it was built from, includes too even if built in
 }
 
 impl ShaderProgram {
@@ -13,6 +14,7 @@ impl ShaderProgram {
             vertex_shader : 0,
             fragment_shader : 0,
             shader_program : 0,
+            dependencies : Vec::new(),
         }
     }
     pub fn new_init(vertex_shader : u32, fragment_shader : u32, shader_program : u32) -> ShaderProgram {
@@ -20,24 +22,45 @@ impl ShaderProgram {
             vertex_shader : vertex_shader,
             fragment_shader : fragment_shader,
             shader_program : shader_program,
+            dependencies : Vec::new(),
         }
     }
+    pub(crate) fn with_dependencies(mut self, dependencies : Vec<String>) -> ShaderProgram {
+        self.dependencies = dependencies;
+        self
+    }
+    pub(crate) fn dependencies(&self) -> &[String] {
+        &self.dependencies
+    }
+    // whether any of the changed files went into it
+    pub fn depends_on(&self, changed : &[String]) -> bool {
+        changed.iter().any(|name| self.dependencies.contains(name))
+    }
+    // -1 like a uniform the program doesn't have when the name can't be passed to gl
     pub fn get_uniform_location(&self, uniform_name : &str) -> i32 {
-        let c_str = CString::new(uniform_name).unwrap();
+        let c_str = match CString::new(uniform_name) {
+            Ok(c_str) => c_str,
+            Err(_) => return -1,
+        };
         unsafe {
             gl::GetUniformLocation(self.shader_program, c_str.as_ptr())
         }
     }
-    pub fn use_program(&self) {
-        unsafe {
-            gl::UseProgram(self.shader_program);
-        }
+    pub fn is_linked(&self) -> bool {
+        self.shader_program != 0
+    }
+    // bound through GlState::use_program so redundant switches are skipped
+    pub fn get_id(&self) -> u32 {
+        self.shader_program
     }
     pub fn drop(&mut self) {
         unsafe {
-            //gl::DeleteShader(self.vertex_shader); // 0s are ignored it's fine
-            //gl::DeleteShader(self.fragment_shader);
-            //gl::DeleteProgram(self.shader_program);
+            gl::DeleteShader(self.vertex_shader); // 0s are ignored it's fine
+            gl::DeleteShader(self.fragment_shader);
+            gl::DeleteProgram(self.shader_program);
         }
+        self.vertex_shader = 0;
+        self.fragment_shader = 0;
+        self.shader_program = 0;
     }
 }
@@ -0,0 +1,26 @@
+// lighting features that can be turned off on slower machines, each combination is a shader variant
+#[derive(Copy, Clone, PartialEq)]
+pub struct ShaderQuality {
+    pub shadows : bool, // also skips drawing the shadow maps
+    pub specular : bool,
+}
+
+impl ShaderQuality {
+    pub fn new() -> ShaderQuality {
+        ShaderQuality {
+            shadows : true,
+            specular : true,
+        }
+    }
+    // what the lighting shaders are built with
+    pub(crate) fn defines(&self) -> Vec<&'static str> {
+        let mut defines : Vec<&'static str> = Vec::new();
+        if !self.shadows {
+            defines.push("TPIXEL_NO_SHADOWS");
+        }
+        if !self.specular {
+            defines.push("TPIXEL_NO_SPECULAR");
+        }
+        defines
+    }
+}
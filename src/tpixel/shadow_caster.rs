@@ -0,0 +1,93 @@
+use crate::tpixel::vector2::Vector2;
+
+pub enum ShadowShape {
+    // in the local space of the entity's sprite if it has one, otherwise world space,
+    // concave is fine but the outline must not cross itself, crossing polygons cast no shadow
+    Polygon(Vec<Vector2>),
+    SpriteAlpha, // uses the alpha of the entity's sprite
+}
+
+// shadows are traced through an occlusion image of the view, so only casters inside the camera's
+// view block light, one just off screen won't shadow what is on screen
+// a sprite on the same entity can switch the shadow off with SPRITE_CASTS_SHADOW
+pub struct ShadowCaster {
+    pub shape : ShadowShape,
+}
+
+impl ShadowCaster {
+    pub(crate) fn clone(&self) -> ShadowCaster {
+        ShadowCaster {
+            shape : match &self.shape {
+                ShadowShape::Polygon(points) => ShadowShape::Polygon(points.clone()),
+                ShadowShape::SpriteAlpha => ShadowShape::SpriteAlpha,
+            },
+        }
+    }
+}
+
+// ear clipping, the triangles as indices into points, empty when the outline crosses itself
+pub(crate) fn triangulate(points : &[Vector2]) -> Vec<[usize; 3]> {
+    let mut triangles : Vec<[usize; 3]> = Vec::new();
+    if points.len() < 3 || crosses_itself(points) {
+        return triangles;
+    }
+    // walk counter clockwise whatever order the points were given in
+    let mut area = 0f32;
+    for i in 0..points.len() {
+        let a = points[i];
+        let b = points[(i + 1) % points.len()];
+        area += a.x * b.y - b.x * a.y;
+    }
+    let mut remaining : Vec<usize> = if area >= 0f32 {
+        (0..points.len()).collect()
+    } else {
+        (0..points.len()).rev().collect()
+    };
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let ear = (0..count).find(|&i| {
+            let a = points[remaining[(i + count - 1) % count]];
+            let b = points[remaining[i]];
+            let c = points[remaining[(i + 1) % count]];
+            if cross(a, b, c) <= 0f32 {
+                return false;
+            }
+            // no other corner may sit inside the ear
+            remaining.iter()
+                .filter(|&&index| index != remaining[(i + count - 1) % count] && index != remaining[i] && index != remaining[(i + 1) % count])
+                .all(|&index| !inside_triangle(points[index], a, b, c))
+        });
+        match ear {
+            Some(i) => {
+                triangles.push([remaining[(i + count - 1) % count], remaining[i], remaining[(i + 1) % count]]);
+                remaining.remove(i);
+            },
+            None => return Vec::new(),
+        }
+    }
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+    triangles
+}
+fn crosses_itself(points : &[Vector2]) -> bool {
+    let count = points.len();
+    for i in 0..count {
+        // edges sharing a corner with edge i can't cross it
+        for j in i + 2..count {
+            if i == 0 && j == count - 1 {
+                continue;
+            }
+            let (a, b) = (points[i], points[(i + 1) % count]);
+            let (c, d) = (points[j], points[(j + 1) % count]);
+            if cross(a, b, c) * cross(a, b, d) < 0f32 && cross(c, d, a) * cross(c, d, b) < 0f32 {
+                return true;
+            }
+        }
+    }
+    false
+}
+fn cross(a : Vector2, b : Vector2, c : Vector2) -> f32 {
+    (b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x)
+}
+fn inside_triangle(point : Vector2, a : Vector2, b : Vector2, c : Vector2) -> bool {
+    cross(a, b, point) >= 0f32 && cross(b, c, point) >= 0f32 && cross(c, a, point) >= 0f32
+}
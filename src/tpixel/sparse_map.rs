@@ -36,7 +36,9 @@ impl<T> SparseMap<T> {
     pub fn remove(&mut self, key : u32) {
         let index = self.map[&key];
         self.data.swap_remove(index);
-        *self.map.get_mut(&self.data[index].key).unwrap() = index;
+        if index < self.data.len() {
+            *self.map.get_mut(&self.data[index].key).unwrap() = index;
+        }
         self.map.remove(&key);
     }
     pub fn all_iter(&self) -> impl Iterator<Item = &SparseMapItem<T>> {
@@ -0,0 +1,26 @@
+use crate::tpixel::color::Color;
+use crate::tpixel::vector2::Vector2;
+
+pub struct SpotLight {
+    pub color : Color,
+    pub position : Vector2,
+    pub height : f32,
+    pub range : f32,
+    pub direction : Vector2,
+    pub inner_angle : f32, // radians from direction to where the falloff starts
+    pub outer_angle : f32, // radians from direction to where the light is fully gone, equal to inner_angle for a hard edge
+}
+
+impl SpotLight {
+    pub(crate) fn clone(&self) -> SpotLight {
+        SpotLight {
+            color : self.color,
+            position : self.position,
+            height : self.height,
+            range : self.range,
+            direction : self.direction,
+            inner_angle : self.inner_angle,
+            outer_angle : self.outer_angle,
+        }
+    }
+}
@@ -3,6 +3,16 @@ use crate::tpixel::color::Color;
 use crate::tpixel::matrix3x2::Matrix3x2;
 use crate::tpixel::rect::Rect;
 
+// bits of Sprite::flags
+pub const SPRITE_VISIBLE : u32 = 1 << 0;
+pub const SPRITE_UNLIT : u32 = 1 << 1; // drawn at full color regardless of lights
+// only gates the ShadowCaster component on the same entity, a sprite with no ShadowCaster casts nothing either way
+pub const SPRITE_CASTS_SHADOW : u32 = 1 << 2;
+pub const SPRITE_FLIP_X : u32 = 1 << 3; // mirrors the image and its normals, the transform is untouched
+pub const SPRITE_FLIP_Y : u32 = 1 << 4;
+pub const SPRITE_ALPHA_CUTOUT : u32 = 1 << 5; // pixels below 0.1 alpha leave the gbuffer alone, text glyphs set it
+
+#[repr(C)]
 pub struct Sprite { // IF YOU ADD STUFF HERE, REMEMBER TO UPDATE THE RENDERER AND ITS LAYOUT
     pub transform : Matrix3x2,
     pub pivot : Vector2,
@@ -10,7 +20,10 @@ pub struct Sprite { // IF YOU ADD STUFF HERE, REMEMBER TO UPDATE THE RENDERER AN
     pub uv_rect : Rect,
     pub z : f32,
     pub height : f32,
+    pub height_map_scale : f32, // scales the height map stored in the alpha of the normal texture
     pub(crate) material_id : u32,
+    pub layer : u32, // cameras only draw the sprite if this bit is set in their layer mask, below MAX_LAYERS (32)
+    pub flags : u32,
 }
 
 impl Sprite {
@@ -21,8 +34,34 @@ impl Sprite {
             color : self.color,
             uv_rect : self.uv_rect,
             z : self.z,
-            height : self.z,
+            height : self.height,
+            height_map_scale : self.height_map_scale,
             material_id : self.material_id,
+            layer : self.layer,
+            flags : self.flags,
+        }
+    }
+    pub fn set_flag(&mut self, flag : u32, enabled : bool) {
+        if enabled {
+            self.flags |= flag;
+        } else {
+            self.flags &= !flag;
         }
     }
+    pub fn has_flag(&self, flag : u32) -> bool {
+        self.flags & flag != 0
+    }
+    // mirrors the image and its normals, the transform is untouched
+    pub fn set_flip_x(&mut self, flip : bool) {
+        self.set_flag(SPRITE_FLIP_X, flip);
+    }
+    pub fn set_flip_y(&mut self, flip : bool) {
+        self.set_flag(SPRITE_FLIP_Y, flip);
+    }
+    pub fn get_flip_x(&self) -> bool {
+        self.has_flag(SPRITE_FLIP_X)
+    }
+    pub fn get_flip_y(&self) -> bool {
+        self.has_flag(SPRITE_FLIP_Y)
+    }
 }
@@ -2,7 +2,8 @@ use crate::tpixel::vector2::Vector2;
 use crate::tpixel::color::Color;
 use crate::tpixel::matrix3x2::Matrix3x2;
 use crate::tpixel::rect::Rect;
-use crate::tpixel::sprite::Sprite;
+use crate::tpixel::sprite::{Sprite, SPRITE_VISIBLE, SPRITE_CASTS_SHADOW};
+use crate::tpixel::nine_slice::{NineSlice, NineSliceMode};
 
 pub struct SpriteFactory {}
 
@@ -18,6 +19,29 @@ impl SpriteFactory {
             uv_rect : Rect::new_uv(),
             z : 0f32,
             height : 0f32,
+            height_map_scale : 0f32,
+            material_id : material_id,
+            layer : 0,
+            flags : SPRITE_VISIBLE | SPRITE_CASTS_SHADOW,
+        }
+    }
+    pub fn new_nine_slice(&self, material_id : u32, width : f32, height : f32) -> NineSlice {
+        NineSlice {
+            transform : Matrix3x2::new(),
+            size : Vector2 { x : width, y : height },
+            pivot : Vector2::new(),
+            color : Color::new(),
+            uv_rect : Rect::new_uv(),
+            border_left : 0f32,
+            border_right : 0f32,
+            border_bottom : 0f32,
+            border_top : 0f32,
+            mode : NineSliceMode::Stretch,
+            z : 0f32,
+            height : 0f32,
+            height_map_scale : 0f32,
+            layer : 0,
+            flags : SPRITE_VISIBLE,
             material_id : material_id,
         }
     }
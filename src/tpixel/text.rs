@@ -0,0 +1,45 @@
+use crate::tpixel::color::Color;
+use crate::tpixel::matrix3x2::Matrix3x2;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum TextAlign {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum TextSpace {
+    World, // drawn into the gbuffer with the sprites and lit like them
+    Screen, // drawn unlit on top of everything, transform is in canvas pixels from the bottom left
+}
+
+pub struct Text {
+    pub text : String,
+    pub transform : Matrix3x2, // the origin is on the baseline of the first line
+    pub color : Color,
+    pub scale : f32,
+    pub align : TextAlign,
+    pub wrap_width : f32, // in font pixels before scaling, 0 never wraps
+    pub z : f32,
+    pub layer : u32, // below MAX_LAYERS (32), like Sprite::layer
+    pub space : TextSpace,
+    pub(crate) font_id : u32,
+}
+
+impl Text {
+    pub(crate) fn clone(&self) -> Text {
+        Text {
+            text : self.text.clone(),
+            transform : self.transform,
+            color : self.color,
+            scale : self.scale,
+            align : self.align,
+            wrap_width : self.wrap_width,
+            z : self.z,
+            layer : self.layer,
+            space : self.space,
+            font_id : self.font_id,
+        }
+    }
+}
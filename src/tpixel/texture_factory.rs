@@ -1,20 +1,23 @@
 use std::path::Path;
-use std::os::raw::c_void;
 use std::collections::HashMap;
 use crate::tpixel::vector2::Vector2;
 use crate::tpixel::texture_info::TextureInfo;
+use crate::tpixel::resource_error::ResourceError;
+use crate::tpixel::render_backend::{RenderBackend, TextureDesc, TextureFormat, TextureFilter, TextureWrap};
 
 pub struct TextureFactory {
     textures : HashMap<String, TextureInfo>,
+    render_textures : HashMap<String, TextureInfo>, // by name, apart from the files so a name can't pick up an image
 }
 
 impl TextureFactory {
     pub fn new() -> TextureFactory {
         TextureFactory {
             textures : HashMap::new(),
+            render_textures : HashMap::new(),
         }
     }
-    pub fn new_texture(&mut self, texture_path : &str) -> &TextureInfo {
+    pub fn new_texture(&mut self, backend : &mut dyn RenderBackend, texture_path : &str) -> &TextureInfo {
         //let texture_wrap = self.textures.get_mut(texture_path);
         let mut texture_info : &mut TextureInfo;
         if self.textures.contains_key(texture_path) {
@@ -28,34 +31,107 @@ impl TextureFactory {
             });
             texture_info = self.textures.get_mut(texture_path).unwrap();
 
-            unsafe {
-                gl::GenTextures(1, &mut texture_info.id);
-                gl::BindTexture(gl::TEXTURE_2D, texture_info.id);
-
-                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as i32);
-                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as i32);
-                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
-                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
-
-                let img = image::open(&Path::new(texture_path)).expect("Failed to load texture");
-                let buffer = img.into_rgba8();
-                texture_info.size.x = buffer.width() as f32;
-                texture_info.size.y = buffer.height() as f32;
-                let width = buffer.width() as i32;
-                let height = buffer.height() as i32;
-                let data = buffer.into_raw();
-                gl::TexImage2D(gl::TEXTURE_2D,
-                    0,
-                    gl::RGBA as i32,
-                    width,
-                    height,
-                    0,
-                    gl::RGBA,
-                    gl::UNSIGNED_BYTE,
-                    &data[0] as *const u8 as *const c_void);
-                gl::GenerateMipmap(gl::TEXTURE_2D);
-            }
+            let img = image::open(&Path::new(texture_path)).expect("Failed to load texture");
+            let buffer = img.into_rgba8();
+            texture_info.size.x = buffer.width() as f32;
+            texture_info.size.y = buffer.height() as f32;
+            texture_info.id = backend.create_texture(&TextureDesc {
+                width : buffer.width() as i32,
+                height : buffer.height() as i32,
+                format : TextureFormat::Rgba8,
+                filter : TextureFilter::Nearest,
+                wrap : TextureWrap::Repeat,
+            }, Some(&buffer.into_raw()));
         }
         texture_info
     }
+    // empty texture for a camera to render into, asking for a name again shares the texture if the size matches
+    pub fn new_render_texture(&mut self, backend : &mut dyn RenderBackend, name : &str, width : i32, height : i32) -> Result<&TextureInfo, ResourceError> {
+        if self.render_textures.contains_key(name) {
+            let texture_info = self.render_textures.get_mut(name).unwrap();
+            if texture_info.size.x as i32 != width || texture_info.size.y as i32 != height {
+                return Err(ResourceError::new(name, &format!("render texture is already {}x{}, not {}x{}",
+                    texture_info.size.x as i32, texture_info.size.y as i32, width, height)));
+            }
+            texture_info.life_count += 1;
+            return Ok(texture_info);
+        }
+        let mut texture_info = TextureInfo {
+            id : 0u32,
+            size : Vector2 { x : width as f32, y : height as f32 },
+            life_count : 1u32,
+        };
+        texture_info.id = backend.create_texture(&TextureDesc {
+            width : width,
+            height : height,
+            format : TextureFormat::Rgba8,
+            filter : TextureFilter::Nearest,
+            wrap : TextureWrap::Clamp,
+        }, None);
+        self.render_textures.insert(name.to_string(), texture_info);
+        Ok(self.render_textures.get(name).unwrap())
+    }
+    // 1x1 texture of a single color, used as the default for channels a material has no image for
+    pub fn new_solid_texture(&mut self, backend : &mut dyn RenderBackend, name : &str, color : [u8; 4]) -> &TextureInfo {
+        if self.textures.contains_key(name) {
+            let texture_info = self.textures.get_mut(name).unwrap();
+            texture_info.life_count += 1;
+            return texture_info;
+        }
+        let mut texture_info = TextureInfo {
+            id : 0u32,
+            size : Vector2 { x : 1f32, y : 1f32 },
+            life_count : 1u32,
+        };
+        texture_info.id = backend.create_texture(&TextureDesc {
+            width : 1,
+            height : 1,
+            format : TextureFormat::Rgba8,
+            filter : TextureFilter::Nearest,
+            wrap : TextureWrap::Repeat,
+        }, Some(&color));
+        self.textures.insert(name.to_string(), texture_info);
+        self.textures.get(name).unwrap()
+    }
+    // texture from rgba pixels generated at runtime, rows go top to bottom like an image file
+    pub fn new_pixel_texture(&mut self, backend : &mut dyn RenderBackend, name : &str, width : i32, height : i32, pixels : &[u8]) -> Result<&TextureInfo, ResourceError> {
+        if self.textures.contains_key(name) {
+            let texture_info = self.textures.get_mut(name).unwrap();
+            texture_info.life_count += 1;
+            return Ok(texture_info);
+        }
+        if width <= 0 || height <= 0 || pixels.len() < (width * height * 4) as usize {
+            return Err(ResourceError::new(name, &format!("{} bytes of pixels for a {}x{} texture", pixels.len(), width, height)));
+        }
+        let mut texture_info = TextureInfo {
+            id : 0u32,
+            size : Vector2 { x : width as f32, y : height as f32 },
+            life_count : 1u32,
+        };
+        texture_info.id = backend.create_texture(&TextureDesc {
+            width : width,
+            height : height,
+            format : TextureFormat::Rgba8,
+            filter : TextureFilter::Nearest,
+            wrap : TextureWrap::Clamp,
+        }, Some(pixels));
+        self.textures.insert(name.to_string(), texture_info);
+        Ok(self.textures.get(name).unwrap())
+    }
+    // drops one reference, the texture is deleted once nothing uses it anymore
+    pub fn release_texture(&mut self, backend : &mut dyn RenderBackend, texture_id : u32) {
+        for textures in [&mut self.textures, &mut self.render_textures].iter_mut() {
+            let name = match textures.iter().find(|(_, texture_info)| texture_info.id == texture_id) {
+                Some((name, _)) => name.clone(),
+                None => continue,
+            };
+            let texture_info = textures.get_mut(&name).unwrap();
+            texture_info.life_count -= 1;
+            if texture_info.life_count == 0 {
+                backend.delete_texture(texture_info.id);
+                textures.remove(&name);
+            }
+            return;
+        }
+    }
 }
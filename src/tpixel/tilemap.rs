@@ -0,0 +1,92 @@
+use crate::tpixel::vector2::Vector2;
+use crate::tpixel::color::Color;
+use crate::tpixel::matrix3x2::Matrix3x2;
+
+pub const EMPTY_TILE : u32 = u32::MAX;
+pub const TILEMAP_CHUNK_SIZE : u32 = 32; // tiles per chunk side
+
+pub struct Tilemap {
+    pub transform : Matrix3x2, // tile 0, 0 has its bottom left corner at the origin
+    pub color : Color,
+    pub z : f32,
+    pub height : f32,
+    pub height_map_scale : f32,
+    pub layer : u32, // below MAX_LAYERS (32), like Sprite::layer
+    pub(crate) material_id : u32, // the tileset, tiles are numbered left to right, top to bottom
+    pub(crate) tile_size : Vector2, // in tileset pixels, also the size of a tile in the world
+    width : u32,
+    height_in_tiles : u32,
+    tiles : Vec<u32>,
+    // bumped whenever a tile in the chunk changes, the renderer rebuilds chunks whose revision it hasn't seen
+    chunk_revisions : Vec<u32>,
+}
+
+impl Tilemap {
+    pub fn new(material_id : u32, tile_size : Vector2, width : u32, height : u32) -> Tilemap {
+        let chunk_count = (Tilemap::chunks_across(width) * Tilemap::chunks_across(height)) as usize;
+        Tilemap {
+            transform : Matrix3x2::new(),
+            color : Color::new(),
+            z : 0f32,
+            height : 0f32,
+            height_map_scale : 0f32,
+            layer : 0,
+            material_id : material_id,
+            tile_size : tile_size,
+            width : width,
+            height_in_tiles : height,
+            tiles : vec![EMPTY_TILE; (width * height) as usize],
+            chunk_revisions : vec![1; chunk_count],
+        }
+    }
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+    pub fn get_height(&self) -> u32 {
+        self.height_in_tiles
+    }
+    // None outside the map
+    pub fn get_tile(&self, x : u32, y : u32) -> Option<u32> {
+        if x >= self.width || y >= self.height_in_tiles {
+            return None;
+        }
+        Some(self.tiles[(y * self.width + x) as usize])
+    }
+    // false if x, y is outside the map, nothing is changed then
+    pub fn set_tile(&mut self, x : u32, y : u32, tile : u32) -> bool {
+        if x >= self.width || y >= self.height_in_tiles {
+            return false;
+        }
+        let index = (y * self.width + x) as usize;
+        if self.tiles[index] == tile {
+            return true;
+        }
+        self.tiles[index] = tile;
+        let chunk = self.chunk_index(x / TILEMAP_CHUNK_SIZE, y / TILEMAP_CHUNK_SIZE);
+        self.chunk_revisions[chunk] = self.chunk_revisions[chunk].wrapping_add(1);
+        true
+    }
+    pub fn fill(&mut self, tile : u32) {
+        for y in 0..self.height_in_tiles {
+            for x in 0..self.width {
+                self.set_tile(x, y, tile);
+            }
+        }
+    }
+
+    pub(crate) fn chunks_x(&self) -> u32 {
+        Tilemap::chunks_across(self.width)
+    }
+    pub(crate) fn chunks_y(&self) -> u32 {
+        Tilemap::chunks_across(self.height_in_tiles)
+    }
+    pub(crate) fn chunk_index(&self, chunk_x : u32, chunk_y : u32) -> usize {
+        (chunk_y * self.chunks_x() + chunk_x) as usize
+    }
+    pub(crate) fn chunk_revision(&self, chunk : usize) -> u32 {
+        self.chunk_revisions[chunk]
+    }
+    fn chunks_across(tiles : u32) -> u32 {
+        (tiles + TILEMAP_CHUNK_SIZE - 1) / TILEMAP_CHUNK_SIZE
+    }
+}
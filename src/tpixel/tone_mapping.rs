@@ -0,0 +1,31 @@
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum ToneMappingOperator {
+    None, // exposure only, clamped like the scene looked before hdr
+    Reinhard,
+    Aces,
+    Filmic,
+}
+
+#[derive(Copy, Clone)]
+pub struct ToneMapping {
+    pub operator : ToneMappingOperator,
+    pub exposure : f32,
+    pub auto_exposure : bool,
+    pub auto_exposure_key : f32, // average luminance the auto exposure aims for
+    pub min_exposure : f32,
+    pub max_exposure : f32,
+}
+
+impl ToneMapping {
+    pub fn new() -> ToneMapping {
+        ToneMapping {
+            operator : ToneMappingOperator::None,
+            exposure : 1f32,
+            auto_exposure : false,
+            auto_exposure_key : 0.18f32,
+            min_exposure : 0.1f32,
+            max_exposure : 10f32,
+        }
+    }
+}
@@ -0,0 +1,23 @@
+use crate::tpixel::vector2::Vector2;
+use crate::tpixel::color::Color;
+
+#[derive(Copy, Clone)]
+pub enum UniformValue {
+    Int(i32),
+    Float(f32),
+    Vector2(Vector2),
+    Color(Color),
+}
+
+impl UniformValue {
+    pub(crate) fn set_uniform(&self, location : i32) {
+        unsafe {
+            match self {
+                UniformValue::Int(value) => gl::Uniform1i(location, *value),
+                UniformValue::Float(value) => gl::Uniform1f(location, *value),
+                UniformValue::Vector2(value) => gl::Uniform2f(location, value.x, value.y),
+                UniformValue::Color(value) => gl::Uniform4f(location, value.r, value.g, value.b, value.a),
+            }
+        }
+    }
+}
@@ -0,0 +1,21 @@
+
+#[derive(Copy, Clone)]
+pub struct Vector3 {
+    pub x : f32,
+    pub y : f32,
+    pub z : f32,
+}
+
+impl Vector3 {
+    pub fn new() -> Vector3 {
+        Vector3 { x : 0f32, y : 0f32, z : 0f32 }
+    }
+    pub fn normalized(&self) -> Vector3 {
+        let length = (self.x * self.x + self.y * self.y + self.z * self.z).sqrt();
+        if length > 0f32 {
+            Vector3 { x : self.x / length, y : self.y / length, z : self.z / length }
+        } else {
+            *self
+        }
+    }
+}